@@ -0,0 +1,180 @@
+//! 文件和图片附件：先上传文件拿到下载地址，再发送带有文件信息的消息
+//!
+//! 文件信息保存在 `MessageContent` 的 `properties` 中，`content` 为 `[文件] a.pdf (1.2 MB)` 这样的文字，
+//! 不认识附件的旧版本客户端仍能看到有人发了文件。
+use crate::config::settings;
+use crate::message::MessageContent;
+use crate::sanitize::sanitize_line;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use url::Url;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Attachment {
+    #[serde(deserialize_with = "crate::sanitize::line")]
+    pub name: String,
+    /// 文件大小，单位字节
+    pub size: u64,
+    pub url: String,
+    pub content_type: String,
+}
+
+impl Attachment {
+    /// 从消息内容中读取文件信息，文字消息返回 None
+    pub fn from_content(content: &MessageContent) -> Option<Self> {
+        let properties = content.properties.as_ref()?;
+        Some(Self {
+            name: sanitize_line(properties.get("name")?.as_str()?),
+            size: properties.get("size")?.as_u64()?,
+            url: properties.get("url")?.as_str()?.to_string(),
+            content_type: content.content_type.clone()?,
+        })
+    }
+
+    /// 发送附件消息时的消息内容
+    pub fn to_content(&self) -> MessageContent {
+        let properties = HashMap::from([
+            ("name".to_string(), Value::from(self.name.as_str())),
+            ("size".to_string(), Value::from(self.size)),
+            ("url".to_string(), Value::from(self.url.as_str())),
+        ]);
+        MessageContent { properties: Some(properties), content_type: Some(self.content_type.clone()), content: self.text() }
+    }
+
+    pub fn is_image(&self) -> bool {
+        self.content_type.starts_with("image/")
+    }
+
+    /// 下载地址和是否带上登录信息。服务端返回的可能是相对路径，拼接到 `server` 后面；
+    /// 地址由发送方填写，绝对地址只有协议、主机和端口都与 `server` 相同时才带上登录信息，
+    /// 不能只比较前缀，`http://chat.example.com.evil.net` 和 `http://chat.example.com@evil.net` 也以服务端地址开头
+    pub fn download_url(&self, server: &str) -> Result<(String, bool), String> {
+        if self.url.starts_with('/') && !self.url.starts_with("//") {
+            return Ok((format!("{server}{}", self.url), true));
+        }
+        let url = Url::parse(&self.url).map_err(|_| format!("下载地址不正确: {}", self.url))?;
+        let own_server = Url::parse(server).is_ok_and(|server| server.origin() == url.origin());
+        Ok((self.url.clone(), own_server))
+    }
+
+    /// 显示的文字，如 `[图片] a.png (12.0 KB)`
+    pub fn text(&self) -> String {
+        let kind = if self.is_image() { "图片" } else { "文件" };
+        format!("[{kind}] {} ({})", self.name, format_size(self.size))
+    }
+}
+
+/// 按扩展名判断文件类型
+pub fn content_type(name: &str) -> &'static str {
+    let extension = Path::new(name).extension().and_then(|ext| ext.to_str()).unwrap_or_default().to_lowercase();
+    match extension.as_str() {
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "svg" => "image/svg+xml",
+        "txt" | "md" | "log" => "text/plain",
+        "pdf" => "application/pdf",
+        "zip" => "application/zip",
+        "json" => "application/json",
+        "mp3" => "audio/mpeg",
+        "mp4" => "video/mp4",
+        _ => "application/octet-stream",
+    }
+}
+
+/// 文件大小，如 `1.2 MB`
+pub fn format_size(size: u64) -> String {
+    const UNITS: [&str; 4] = ["KB", "MB", "GB", "TB"];
+    if size < 1024 {
+        return format!("{size} B");
+    }
+    let mut value = size as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{value:.1} {}", UNITS[unit])
+}
+
+/// `~/` 开头的路径替换为用户目录
+pub fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), std::env::var_os("HOME")) {
+        (Some(rest), Some(home)) => PathBuf::from(home).join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+/// 默认的下载目录：配置的 `chat.download_dir`，其次是 ~/Downloads，都没有时为当前目录
+pub fn download_dir() -> PathBuf {
+    if let Some(dir) = &settings().chat.download_dir {
+        return expand_home(dir);
+    }
+    std::env::var_os("HOME")
+        .map(|home| PathBuf::from(home).join("Downloads"))
+        .filter(|dir| dir.is_dir())
+        .unwrap_or_else(|| PathBuf::from("."))
+}
+
+/// 下载保存的路径：去掉文件名中的目录，同名文件已存在时加上序号，如 `a (1).png`
+pub fn save_path(dir: &Path, name: &str) -> PathBuf {
+    let name = Path::new(name)
+        .file_name()
+        .and_then(|name| name.to_str())
+        .filter(|name| !name.starts_with('.'))
+        .unwrap_or("download");
+    let path = dir.join(name);
+    if !path.exists() {
+        return path;
+    }
+    let file = Path::new(name);
+    let stem = file.file_stem().and_then(|stem| stem.to_str()).unwrap_or(name);
+    let extension = file.extension().and_then(|ext| ext.to_str()).map(|ext| format!(".{ext}")).unwrap_or_default();
+    (1..)
+        .map(|index| dir.join(format!("{stem} ({index}){extension}")))
+        .find(|path| !path.exists())
+        .unwrap_or(path)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{content_type, format_size, save_path, Attachment};
+    use std::path::Path;
+
+    #[test]
+    fn test_attachment() {
+        let attachment = Attachment {
+            name: "a.png".to_string(),
+            size: 12 * 1024,
+            url: "/file/1".to_string(),
+            content_type: content_type("a.PNG").to_string(),
+        };
+        let content = attachment.to_content();
+        assert_eq!(content.content, "[图片] a.png (12.0 KB)");
+        assert_eq!(Attachment::from_content(&content), Some(attachment));
+        assert_eq!(content_type("a.tar.gz"), "application/octet-stream");
+        assert_eq!(format_size(100), "100 B");
+        assert_eq!(format_size(1536 * 1024), "1.5 MB");
+        assert_eq!(save_path(Path::new("/nonexistent"), "../../etc/passwd"), Path::new("/nonexistent/passwd"));
+        assert_eq!(save_path(Path::new("/nonexistent"), ".."), Path::new("/nonexistent/download"));
+    }
+
+    #[test]
+    fn test_download_url() {
+        let server = "http://chat.example.com";
+        let attachment = |url: &str| Attachment { name: "a".to_string(), size: 1, url: url.to_string(), content_type: "text/plain".to_string() };
+        assert_eq!(attachment("/file/1").download_url(server).unwrap(), ("http://chat.example.com/file/1".to_string(), true));
+        assert!(attachment("http://chat.example.com/file/1").download_url(server).unwrap().1);
+        assert!(attachment("http://chat.example.com:80/file/1").download_url(server).unwrap().1);
+        // 与服务端地址前缀相同的其他主机不带登录信息
+        assert!(!attachment("http://chat.example.com.evil.net/x").download_url(server).unwrap().1);
+        assert!(!attachment("http://chat.example.com@evil.net/x").download_url(server).unwrap().1);
+        assert!(!attachment("http://chat.example.com:8080/x").download_url(server).unwrap().1);
+        assert!(!attachment("https://chat.example.com/x").download_url(server).unwrap().1);
+        assert!(attachment("//evil.net/x").download_url(server).is_err());
+        assert!(attachment("file").download_url(server).is_err());
+    }
+}
@@ -0,0 +1,232 @@
+//! 聊天输入中的斜杠命令
+//!
+//! 以 `/` 开头的输入是命令，以 `//` 开头时去掉一个 `/` 后作为普通消息发送。
+//! 命令名可以只输入开头，如 `/hist`，能唯一确定命令即可。
+
+/// 命令说明
+pub struct CommandSpec {
+    pub name: &'static str,
+    /// 参数格式，`<>` 为必填，`[]` 为可选
+    pub usage: &'static str,
+    pub description: &'static str,
+}
+
+/// 所有命令，按帮助中的顺序排列
+pub const COMMANDS: &[CommandSpec] = &[
+    CommandSpec { name: "exit", usage: "", description: "退出聊天" },
+    CommandSpec { name: "help", usage: "[命令]", description: "查看命令说明" },
+    CommandSpec { name: "history", usage: "[条数]", description: "重新显示最近的聊天记录" },
+    CommandSpec { name: "more", usage: "", description: "显示更早的聊天记录" },
+    CommandSpec { name: "reply", usage: "<序号> <内容>", description: "回复消息，序号 1 为最新的一条" },
+    CommandSpec { name: "edit", usage: "<序号> <内容>", description: "编辑自己发出的消息" },
+    CommandSpec { name: "recall", usage: "<序号>", description: "撤回自己发出的消息" },
+    CommandSpec { name: "react", usage: "<序号> [表情]", description: "回应消息，再次回应相同的表情时取消" },
+    CommandSpec { name: "search", usage: "<关键字>", description: "搜索聊天记录" },
+    CommandSpec { name: "send-file", usage: "<路径>", description: "发送文件或图片" },
+    CommandSpec { name: "download", usage: "<消息id> [目录]", description: "下载附件，默认保存到下载目录" },
+    CommandSpec { name: "clear", usage: "", description: "清屏" },
+    CommandSpec { name: "me", usage: "<动作>", description: "以第三人称发送动作，如 /me 去吃饭了" },
+    CommandSpec { name: "who", usage: "", description: "查看会话成员" },
+    CommandSpec { name: "mute", usage: "", description: "开启或关闭免打扰" },
+    CommandSpec { name: "retry", usage: "", description: "重发发送失败的消息" },
+    CommandSpec { name: "encrypt", usage: "[on|off]", description: "开启或关闭单聊的端到端加密" },
+    CommandSpec { name: "fingerprint", usage: "", description: "查看双方密钥的指纹" },
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    Exit,
+    Help(Option<String>),
+    History(Option<usize>),
+    More,
+    Reply { index: usize, text: String },
+    Edit { index: usize, text: String },
+    Recall(usize),
+    React { index: usize, emoji: Option<String> },
+    Search(String),
+    SendFile(String),
+    Download { mid: i64, dir: Option<String> },
+    Clear,
+    Me(String),
+    Who,
+    Mute,
+    Retry,
+    /// 开启或关闭加密，未指定时切换
+    Encrypt(Option<bool>),
+    Fingerprint,
+}
+
+/// 聊天输入：普通消息或命令
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChatInput {
+    Text(String),
+    Command(Command),
+}
+
+/// 解析聊天输入，命令不存在或参数有误时返回错误提示。
+/// 消息只去掉末尾的换行和空白，保留开头的缩进，如缩进的代码
+pub fn parse(input: &str) -> Result<ChatInput, String> {
+    let input = input.trim_end();
+    if input.trim_start().is_empty() {
+        return Ok(ChatInput::Text(String::new()));
+    }
+    if let Some(text) = input.strip_prefix("//") {
+        return Ok(ChatInput::Text(format!("/{text}")));
+    }
+    let Some(command) = input.strip_prefix('/') else {
+        return Ok(ChatInput::Text(input.to_string()));
+    };
+    let (name, args) = command.split_once(char::is_whitespace).unwrap_or((command, ""));
+    let spec = find(name)?;
+    let args = args.trim();
+    let usage = || format!("用法：/{} {}", spec.name, spec.usage).trim_end().to_string();
+    let command = match spec.name {
+        "help" => Command::Help(Some(args.trim_start_matches('/').to_string()).filter(|name| !name.is_empty())),
+        "history" if args.is_empty() => Command::History(None),
+        "history" => Command::History(Some(args.parse().map_err(|_| usage())?)),
+        "reply" | "edit" => {
+            let (index, text) = args.split_once(char::is_whitespace).ok_or_else(usage)?;
+            let index = index.parse::<usize>().ok().filter(|index| *index > 0).ok_or_else(usage)?;
+            let text = text.trim().to_string();
+            if spec.name == "reply" {
+                Command::Reply { index, text }
+            } else {
+                Command::Edit { index, text }
+            }
+        }
+        "recall" => Command::Recall(args.parse::<usize>().ok().filter(|index| *index > 0).ok_or_else(usage)?),
+        "react" => {
+            let (index, emoji) = args.split_once(char::is_whitespace).unwrap_or((args, ""));
+            let index = index.parse::<usize>().ok().filter(|index| *index > 0).ok_or_else(usage)?;
+            Command::React { index, emoji: Some(emoji.trim().to_string()).filter(|emoji| !emoji.is_empty()) }
+        }
+        "search" | "me" | "send-file" if args.is_empty() => return Err(usage()),
+        "send-file" => Command::SendFile(args.to_string()),
+        "download" => {
+            let (mid, dir) = args.split_once(char::is_whitespace).unwrap_or((args, ""));
+            let mid = mid.trim_start_matches('#').parse().map_err(|_| usage())?;
+            Command::Download { mid, dir: Some(dir.trim().to_string()).filter(|dir| !dir.is_empty()) }
+        }
+        "encrypt" => Command::Encrypt(match args {
+            "" => None,
+            "on" => Some(true),
+            "off" => Some(false),
+            _ => return Err(usage()),
+        }),
+        "search" => Command::Search(args.to_string()),
+        "me" => Command::Me(args.to_string()),
+        _ if !args.is_empty() => return Err(format!("/{} 不需要参数", spec.name)),
+        "exit" => Command::Exit,
+        "more" => Command::More,
+        "clear" => Command::Clear,
+        "who" => Command::Who,
+        "mute" => Command::Mute,
+        "fingerprint" => Command::Fingerprint,
+        "retry" => Command::Retry,
+        name => return Err(format!("/{name} 暂不支持")),
+    };
+    Ok(ChatInput::Command(command))
+}
+
+/// 按名称或唯一的开头查找命令
+fn find(name: &str) -> Result<&'static CommandSpec, String> {
+    if let Some(spec) = COMMANDS.iter().find(|spec| spec.name == name) {
+        return Ok(spec);
+    }
+    let matched = COMMANDS.iter().filter(|spec| !name.is_empty() && spec.name.starts_with(name)).collect::<Vec<_>>();
+    match matched.as_slice() {
+        [spec] => Ok(spec),
+        [] => Err(format!("未知命令 /{name}，输入 /help 查看可用命令")),
+        _ => Err(format!(
+            "/{name} 可能是：{}",
+            matched.iter().map(|spec| format!("/{}", spec.name)).collect::<Vec<_>>().join("、")
+        )),
+    }
+}
+
+/// 补全命令名：输入以 `/` 开头且还没有参数时，返回以输入开头的命令
+pub fn complete(input: &str) -> Vec<String> {
+    match input.strip_prefix('/') {
+        Some(name) if !name.starts_with('/') && !name.contains(char::is_whitespace) => COMMANDS
+            .iter()
+            .filter(|spec| spec.name.starts_with(name))
+            .map(|spec| format!("/{}", spec.name))
+            .collect(),
+        _ => vec![],
+    }
+}
+
+/// 帮助内容，未指定命令时列出所有命令
+pub fn help(name: Option<&str>) -> Result<Vec<String>, String> {
+    let line = |spec: &CommandSpec| format!("{:<24}{}", format!("/{} {}", spec.name, spec.usage), spec.description);
+    match name {
+        Some(name) => find(name).map(|spec| vec![line(spec)]),
+        None => {
+            let mut lines = COMMANDS.iter().map(line).collect::<Vec<_>>();
+            lines.push("以 / 开头的消息请输入 //".to_string());
+            Ok(lines)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{complete, parse, ChatInput, Command, COMMANDS};
+
+    fn command(input: &str) -> Command {
+        match parse(input).unwrap() {
+            ChatInput::Command(command) => command,
+            ChatInput::Text(text) => panic!("unexpected text {text}"),
+        }
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(parse("exit").unwrap(), ChatInput::Text("exit".to_string()));
+        assert_eq!(parse("//exit").unwrap(), ChatInput::Text("/exit".to_string()));
+        assert_eq!(command("/exit"), Command::Exit);
+        assert_eq!(command("/hist 5"), Command::History(Some(5)));
+        assert_eq!(command("/help /reply"), Command::Help(Some("reply".to_string())));
+        assert_eq!(command("/reply 2 好的"), Command::Reply { index: 2, text: "好的".to_string() });
+        assert_eq!(command("/me 去吃饭了"), Command::Me("去吃饭了".to_string()));
+        assert_eq!(command("/ret"), Command::Retry);
+        assert_eq!(command("/edit 1 你好"), Command::Edit { index: 1, text: "你好".to_string() });
+        assert_eq!(command("/rec 2"), Command::Recall(2));
+        assert_eq!(parse("/recall").unwrap_err(), "用法：/recall <序号>");
+        assert_eq!(command("/react 1"), Command::React { index: 1, emoji: None });
+        assert_eq!(command("/react 1 🎉"), Command::React { index: 1, emoji: Some("🎉".to_string()) });
+        assert_eq!(command("/send-file ~/a b.png"), Command::SendFile("~/a b.png".to_string()));
+        assert_eq!(command("/down #98 /tmp"), Command::Download { mid: 98, dir: Some("/tmp".to_string()) });
+        assert_eq!(parse("/download").unwrap_err(), "用法：/download <消息id> [目录]");
+        assert_eq!(command("/enc off"), Command::Encrypt(Some(false)));
+        assert_eq!(parse("/encrypt yes").unwrap_err(), "用法：/encrypt [on|off]");
+        assert_eq!(command("/fin"), Command::Fingerprint);
+        assert_eq!(parse("/m").unwrap_err(), "/m 可能是：/more、/me、/mute");
+        assert_eq!(parse("/foo").unwrap_err(), "未知命令 /foo，输入 /help 查看可用命令");
+        assert_eq!(parse("/reply 0 好的").unwrap_err(), "用法：/reply <序号> <内容>");
+        assert_eq!(parse("/exit now").unwrap_err(), "/exit 不需要参数");
+        assert_eq!(parse("/history x").unwrap_err(), "用法：/history [条数]");
+        // 消息保留开头的缩进
+        assert_eq!(parse("    let a = 1;\n").unwrap(), ChatInput::Text("    let a = 1;".to_string()));
+        assert_eq!(parse("  \n").unwrap(), ChatInput::Text(String::new()));
+        assert_eq!(command("/exit\n"), Command::Exit);
+    }
+
+    #[test]
+    fn test_every_command_parsed() {
+        // 新增的命令需要在 parse 中处理
+        for spec in COMMANDS {
+            let err = parse(&format!("/{}", spec.name)).err().unwrap_or_default();
+            assert!(!err.ends_with("暂不支持"), "{err}");
+        }
+    }
+
+    #[test]
+    fn test_complete() {
+        assert_eq!(complete("/m"), vec!["/more", "/me", "/mute"]);
+        assert_eq!(complete("/he"), vec!["/help"]);
+        assert!(complete("/me hi").is_empty());
+        assert!(complete("//").is_empty());
+        assert!(complete("hi").is_empty());
+    }
+}
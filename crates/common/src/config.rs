@@ -0,0 +1,303 @@
+//! 分层配置：内置默认值 < ~/.config/chat-cli/config.toml < CHAT_CLI_* 环境变量 < profile < 命令行参数
+//!
+//! ```toml
+//! server = "http://localhost:3000"
+//! # 默认使用的 profile，未配置时使用 `profile use` 选中的 profile
+//! profile = "staging"
+//!
+//! [timeout]
+//! connect = 5
+//! request = 10
+//!
+//! [page_size]
+//! recent_chat = 100
+//! user_search = 10
+//! history = 20
+//!
+//! [ui]
+//! theme = "dark"
+//! keymap = "default"
+//! bell = true
+//!
+//! [chat]
+//! recall_limit = 120
+//! download_dir = "~/Downloads"
+//!
+//! [notify]
+//! desktop = "osc777"
+//! only_mentions = false
+//! quiet_hours = "22:00-08:00"
+//! status_line = true
+//! command = "notify-send \"$CHAT_CLI_TITLE\" \"$CHAT_CLI_BODY\""
+//! ```
+//!
+//! 环境变量中嵌套的配置项使用 `__` 分隔，如 `CHAT_CLI_TIMEOUT__REQUEST=30`。
+use crate::notify::{Desktop, QuietHours};
+use crate::profile::Profiles;
+use ::config::{Config, ConfigError, Environment, File, Map};
+use serde::Deserialize;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::Duration;
+
+/// 当前生效的配置
+static SETTINGS: OnceLock<Settings> = OnceLock::new();
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Settings {
+    /// 服务端地址
+    pub server: String,
+    /// 当前使用的 profile，服务端地址取自 profile
+    #[serde(default)]
+    pub profile: Option<String>,
+    pub timeout: Timeout,
+    pub page_size: PageSize,
+    pub ui: Ui,
+    pub chat: Chat,
+    pub notify: Notify,
+}
+
+/// 请求超时时间，单位秒
+#[derive(Debug, Clone, Deserialize)]
+pub struct Timeout {
+    pub connect: u64,
+    pub request: u64,
+}
+
+impl Timeout {
+    pub fn connect(&self) -> Duration {
+        Duration::from_secs(self.connect)
+    }
+
+    pub fn request(&self) -> Duration {
+        Duration::from_secs(self.request)
+    }
+}
+
+/// 各列表每次拉取的条数
+#[derive(Debug, Clone, Deserialize)]
+pub struct PageSize {
+    pub recent_chat: u32,
+    /// 搜索用户时每页显示的条数
+    pub user_search: usize,
+    /// 打开会话时显示的聊天记录条数，`/more` 每次多显示的条数
+    pub history: usize,
+}
+
+/// 终端界面相关配置
+#[derive(Debug, Clone, Deserialize)]
+pub struct Ui {
+    /// 主题名称，内置 dark、light、high_contrast，或 theme.toml 中自定义的主题
+    pub theme: String,
+    /// 快捷键方案：default、vim、emacs
+    pub keymap: String,
+    /// 其他会话收到新消息时响铃，是否通知见 `Notify`
+    pub bell: bool,
+}
+
+/// 其他会话的新消息通知，响铃由 `ui.bell` 控制
+#[derive(Debug, Clone, Deserialize)]
+pub struct Notify {
+    /// 桌面通知的控制序列：off、osc9、osc777
+    pub desktop: Desktop,
+    /// 只在有人@我时通知
+    pub only_mentions: bool,
+    /// 免打扰时段，如 "22:00-08:00"，时段内不通知
+    #[serde(default)]
+    pub quiet_hours: Option<QuietHours>,
+    /// 把未读数写入状态文件，供 tmux/screen 的状态栏显示
+    pub status_line: bool,
+    /// 收到通知时通过 sh 执行的命令，标题和正文在环境变量 CHAT_CLI_TITLE、CHAT_CLI_BODY 中
+    #[serde(default)]
+    pub command: Option<String>,
+}
+
+/// 配置文件目录：$XDG_CONFIG_HOME/chat-cli，默认 ~/.config/chat-cli
+pub fn config_dir() -> Option<PathBuf> {
+    std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .map(|dir| dir.join("chat-cli"))
+}
+
+/// 数据目录：$XDG_DATA_HOME/chat-cli，默认 ~/.local/share/chat-cli
+pub fn data_dir() -> Option<PathBuf> {
+    std::env::var_os("XDG_DATA_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/share")))
+        .map(|dir| dir.join("chat-cli"))
+}
+
+/// 聊天相关配置
+#[derive(Debug, Clone, Deserialize)]
+pub struct Chat {
+    /// 发出多少秒内的消息可以撤回
+    pub recall_limit: u64,
+    /// 附件的默认下载目录，未配置时为 ~/Downloads
+    #[serde(default)]
+    pub download_dir: Option<String>,
+}
+
+/// 数据目录下按账号隔离的文件，如 `accounts/http___localhost_3000-1.json`
+pub fn account_path(kind: &str, server: &str, uid: i32) -> Option<PathBuf> {
+    let server = server
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect::<String>();
+    data_dir().map(|dir| dir.join(kind).join(format!("{server}-{uid}.json")))
+}
+
+/// 写入只有自己可以读写的文件，如密钥、token、联系人和未发出的消息。
+/// 先写入同目录下创建时权限即为 0600 的临时文件，再替换原文件，中途不会有其他人可读的内容
+pub fn write_private(path: &Path, content: &str) -> Result<(), String> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|err| format!("创建{}失败: {err}", dir.display()))?;
+    }
+    let tmp = path.with_extension("tmp");
+    // 上次遗留的临时文件权限可能不对，删除后重新创建
+    let _ = fs::remove_file(&tmp);
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let written = options
+        .open(&tmp)
+        .and_then(|mut file| file.write_all(content.as_bytes()).and_then(|_| file.sync_all()))
+        .and_then(|_| fs::rename(&tmp, path));
+    if let Err(err) = written {
+        let _ = fs::remove_file(&tmp);
+        return Err(format!("写入{}失败: {err}", path.display()));
+    }
+    Ok(())
+}
+
+impl Settings {
+    /// 按优先级合并各层配置：`config_dir` 下的 config.toml、`env` 中 `CHAT_CLI_` 开头的环境变量、
+    /// `profiles` 中选中的 profile，`server`、`profile` 为命令行指定的服务端地址和 profile
+    pub fn load(
+        config_dir: Option<&Path>,
+        env: Map<String, String>,
+        profiles: &Profiles,
+        server: Option<String>,
+        profile: Option<String>,
+    ) -> Result<Self, ConfigError> {
+        let mut builder = Config::builder()
+            .set_default("server", "http://localhost:3000")?
+            .set_default("timeout.connect", 5)?
+            .set_default("timeout.request", 10)?
+            .set_default("page_size.recent_chat", 100)?
+            .set_default("page_size.user_search", 10)?
+            .set_default("page_size.history", 20)?
+            .set_default("ui.theme", "dark")?
+            .set_default("ui.keymap", "default")?
+            .set_default("ui.bell", true)?
+            .set_default("chat.recall_limit", 120)?
+            .set_default("notify.desktop", "off")?
+            .set_default("notify.only_mentions", false)?
+            .set_default("notify.status_line", false)?;
+        if let Some(dir) = config_dir {
+            builder = builder.add_source(File::from(dir.join("config.toml")).required(false));
+        }
+        builder = builder.add_source(
+            Environment::with_prefix("CHAT_CLI")
+                .prefix_separator("_")
+                .separator("__")
+                .try_parsing(true)
+                .source(Some(env)),
+        );
+        if let Some(profile) = profile {
+            builder = builder.set_override("profile", profile)?;
+        }
+        let mut settings = builder.build()?.try_deserialize::<Settings>()?;
+        if settings.profile.is_none() {
+            settings.profile = profiles.current.clone();
+        }
+        if let Some(name) = &settings.profile {
+            let profile = profiles
+                .get(name)
+                .ok_or_else(|| ConfigError::Message(format!("profile {name} 不存在")))?;
+            settings.server = profile.server.clone();
+        }
+        if let Some(server) = server {
+            settings.server = server;
+        }
+        settings.server = settings.server.trim_end_matches('/').to_string();
+        Ok(settings)
+    }
+}
+
+/// 从配置目录、环境变量和 profiles.json 加载配置，程序启动时调用一次
+pub fn init(server: Option<String>, profile: Option<String>) -> Result<&'static Settings, ConfigError> {
+    let env = std::env::vars_os().filter_map(|(key, value)| Some((key.into_string().ok()?, value.into_string().ok()?))).collect();
+    let profiles = Profiles::load().map_err(ConfigError::Message)?;
+    let settings = Settings::load(config_dir().as_deref(), env, &profiles, server, profile)?;
+    Ok(SETTINGS.get_or_init(|| settings))
+}
+
+/// 当前配置，需要先调用 `init`
+pub fn settings() -> &'static Settings {
+    SETTINGS.get().expect("config::init not called")
+}
+
+#[cfg(test)]
+mod test {
+    use super::{write_private, Map, Settings};
+    use crate::profile::{Profile, Profiles};
+
+    #[test]
+    fn test_layered_settings() {
+        // 配置文件覆盖默认值，环境变量覆盖配置文件，命令行参数覆盖环境变量
+        let dir = std::env::temp_dir().join(format!("chat-cli-config-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("config.toml"), "[timeout]\nconnect = 3\nrequest = 20\n").unwrap();
+        let env = Map::from([
+            ("CHAT_CLI_TIMEOUT__REQUEST".to_string(), "30".to_string()),
+            ("CHAT_CLI_SERVER".to_string(), "http://env:3000".to_string()),
+            ("OTHER_SERVER".to_string(), "http://other:3000".to_string()),
+        ]);
+        let profiles = Profiles::default();
+        let settings = Settings::load(Some(&dir), env.clone(), &profiles, None, None).unwrap();
+        assert_eq!(settings.server, "http://env:3000");
+        assert_eq!(settings.timeout.request, 30);
+        assert_eq!(settings.timeout.connect, 3);
+        assert_eq!(settings.page_size.recent_chat, 100);
+
+        let settings = Settings::load(Some(&dir), env.clone(), &profiles, Some("https://chat.example.com/".to_string()), None).unwrap();
+        assert_eq!(settings.server, "https://chat.example.com");
+        // 指定不存在的 profile 时报错
+        assert!(Settings::load(Some(&dir), env.clone(), &profiles, None, Some("staging".to_string())).is_err());
+
+        // 选中的 profile 覆盖环境变量中的服务端地址
+        let mut profiles = Profiles::default();
+        let staging = Profile { name: "staging".to_string(), server: "https://staging.example.com".to_string(), username: None, token: None };
+        profiles.add(staging).unwrap();
+        let settings = Settings::load(None, env, &profiles, None, None).unwrap();
+        assert_eq!(settings.profile.as_deref(), Some("staging"));
+        assert_eq!(settings.server, "https://staging.example.com");
+        assert_eq!(settings.timeout.connect, 5);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_write_private() {
+        let dir = std::env::temp_dir().join(format!("chat-cli-test-{}", std::process::id()));
+        let path = dir.join("keys").join("a.json");
+        write_private(&path, "secret").unwrap();
+        write_private(&path, "new").unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "new");
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(std::fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+        }
+        assert!(!path.with_extension("tmp").exists());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
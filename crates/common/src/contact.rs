@@ -0,0 +1,138 @@
+//! 本地保存的好友备注、黑名单和免打扰设置，按账号隔离
+//!
+//! 保存在 ~/.local/share/chat-cli/accounts/ 下，每个服务端上的每个账号一个文件。
+use crate::config::{account_path, write_private};
+use crate::message::MessageTarget;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::PathBuf;
+
+/// 被屏蔽用户的消息内容
+pub const BLOCKED_MESSAGE: &str = "[已屏蔽的消息]";
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ContactBook {
+    #[serde(skip)]
+    path: Option<PathBuf>,
+    /// 用户id -> 备注
+    #[serde(default)]
+    aliases: BTreeMap<i32, String>,
+    /// 用户id -> 屏蔽时的名称
+    #[serde(default)]
+    blocked: BTreeMap<i32, String>,
+    /// 免打扰的会话，如 `MessageTargetGroup:1`
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    muted: BTreeSet<String>,
+}
+
+impl ContactBook {
+    /// 读取账号的备注和黑名单，文件不存在时为空
+    pub fn load(server: &str, uid: i32) -> Result<Self, String> {
+        let path = account_path("accounts", server, uid);
+        let mut book = match path.as_ref().filter(|path| path.exists()) {
+            None => Self::default(),
+            Some(path) => {
+                let content = fs::read_to_string(path).map_err(|err| format!("读取{}失败: {err}", path.display()))?;
+                serde_json::from_str(&content).map_err(|err| format!("解析{}失败: {err}", path.display()))?
+            }
+        };
+        book.path = path;
+        Ok(book)
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let path = self.path.as_ref().ok_or("无法确定数据目录")?;
+        let content = serde_json::to_string_pretty(self).map_err(|err| err.to_string())?;
+        write_private(path, &content)
+    }
+
+    pub fn alias(&self, uid: i32) -> Option<&str> {
+        self.aliases.get(&uid).map(String::as_str)
+    }
+
+    /// 设置备注，为空时删除备注
+    pub fn set_alias(&mut self, uid: i32, alias: &str) {
+        let alias = alias.trim();
+        if alias.is_empty() {
+            self.aliases.remove(&uid);
+        } else {
+            self.aliases.insert(uid, alias.to_string());
+        }
+    }
+
+    /// 显示的名称，有备注时使用备注
+    pub fn display_name(&self, uid: i32, name: &str) -> String {
+        self.alias(uid).unwrap_or(name).to_string()
+    }
+
+    pub fn is_blocked(&self, uid: i32) -> bool {
+        self.blocked.contains_key(&uid)
+    }
+
+    pub fn block(&mut self, uid: i32, name: &str) {
+        self.blocked.insert(uid, name.to_string());
+    }
+
+    /// 取消屏蔽，返回是否在黑名单中
+    pub fn unblock(&mut self, uid: i32) -> bool {
+        self.blocked.remove(&uid).is_some()
+    }
+
+    /// 黑名单：用户id和屏蔽时的名称
+    pub fn blocked(&self) -> impl Iterator<Item = (i32, &str)> {
+        self.blocked.iter().map(|(uid, name)| (*uid, name.as_str()))
+    }
+
+    /// 免打扰的会话不响铃、不通知，也不计入未读总数
+    pub fn is_muted(&self, target: MessageTarget) -> bool {
+        self.muted.contains(&String::from(target))
+    }
+
+    pub fn set_muted(&mut self, target: MessageTarget, muted: bool) {
+        if muted {
+            self.muted.insert(target.into());
+        } else {
+            self.muted.remove(&String::from(target));
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::ContactBook;
+    use crate::message::{MessageTarget, MessageTargetGroup, MessageTargetUser};
+
+    #[test]
+    fn test_alias_and_block() {
+        let mut book = ContactBook::default();
+        book.set_alias(1, " 老王 ");
+        assert_eq!(book.display_name(1, "wang"), "老王");
+        assert_eq!(book.display_name(2, "li"), "li");
+        book.set_alias(1, "");
+        assert_eq!(book.display_name(1, "wang"), "wang");
+
+        book.block(2, "li");
+        assert!(book.is_blocked(2));
+        assert_eq!(book.blocked().collect::<Vec<_>>(), vec![(2, "li")]);
+        assert!(book.unblock(2));
+        assert!(!book.unblock(2));
+
+        let json = serde_json::to_string(&book).unwrap();
+        assert_eq!(json, r#"{"aliases":{},"blocked":{}}"#);
+    }
+
+    #[test]
+    fn test_mute() {
+        let mut book = ContactBook::default();
+        let group = MessageTarget::Group(MessageTargetGroup { gid: 1 });
+        book.set_muted(group, true);
+        assert!(book.is_muted(group));
+        // 群号与用户id相同时互不影响
+        assert!(!book.is_muted(MessageTarget::User(MessageTargetUser { uid: 1 })));
+        let json = serde_json::to_string(&book).unwrap();
+        assert_eq!(json, r#"{"aliases":{},"blocked":{},"muted":["MessageTargetGroup:1"]}"#);
+        book.set_muted(group, false);
+        assert!(!book.is_muted(group));
+    }
+}
@@ -1,11 +1,11 @@
-use chrono::{DateTime, Local, NaiveDateTime, Offset};
+use chrono::{DateTime, Datelike, Local, NaiveDateTime, Offset};
 
 /// 自定义 Option<DateTime> 序列化
 pub mod opt_native_datetime_format {
     use chrono::NaiveDateTime;
     use serde::{self, Deserialize, Deserializer, Serializer};
 
-    const FORMAT: &'static str = "%Y-%m-%d %H:%M:%S";
+    const FORMAT: &str = "%Y-%m-%d %H:%M:%S";
 
     pub type OK = ();
 
@@ -37,7 +37,7 @@ pub mod native_datetime_format {
     use chrono::NaiveDateTime;
     use serde::{self, Deserialize, Deserializer, Serializer};
 
-    const FORMAT: &'static str = "%Y-%m-%d %H:%M:%S";
+    const FORMAT: &str = "%Y-%m-%d %H:%M:%S";
 
     // The signature of a serialize_with function must follow the pattern:
     //
@@ -76,7 +76,7 @@ pub mod opt_datetime_format {
     use chrono::{DateTime, Local, Offset};
     use serde::{self, Deserialize, Deserializer, Serializer};
 
-    const FORMAT: &'static str = "%Y-%m-%d %H:%M:%S";
+    const FORMAT: &str = "%Y-%m-%d %H:%M:%S";
 
     pub type OK = ();
 
@@ -96,7 +96,7 @@ pub mod opt_datetime_format {
     {
         match String::deserialize(deserializer) {
             Ok(s) => Ok(Some(
-                format!("{} {}", s, Local::now().offset().fix().to_string())
+                format!("{} {}", s, Local::now().offset().fix())
                     .parse::<DateTime<Local>>()
                     .map_err(serde::de::Error::custom)?,
             )),
@@ -110,7 +110,7 @@ pub mod datetime_format {
     use chrono::{DateTime, Local, Offset};
     use serde::{self, Deserialize, Deserializer, Serializer};
 
-    const FORMAT: &'static str = "%Y-%m-%d %H:%M:%S";
+    const FORMAT: &str = "%Y-%m-%d %H:%M:%S";
 
     // The signature of a serialize_with function must follow the pattern:
     //
@@ -150,3 +150,42 @@ pub mod datetime_format {
 pub fn native_datetime_2_datetime(value: NaiveDateTime) -> DateTime<Local> {
     DateTime::<Local>::from_naive_utc_and_offset(value, Local::now().offset().fix())
 }
+
+/// 相对于当前时间的简短描述，如“刚刚”、“5分钟前”、“昨天 18:30”
+pub fn relative_time(time: DateTime<Local>, now: DateTime<Local>) -> String {
+    let elapsed = now.signed_duration_since(time);
+    if elapsed.num_minutes() < 1 {
+        "刚刚".to_string()
+    } else if elapsed.num_hours() < 1 {
+        format!("{}分钟前", elapsed.num_minutes())
+    } else if time.date_naive() == now.date_naive() {
+        time.format("%H:%M").to_string()
+    } else if now.date_naive().pred_opt() == Some(time.date_naive()) {
+        time.format("昨天 %H:%M").to_string()
+    } else if time.year() == now.year() {
+        time.format("%m-%d").to_string()
+    } else {
+        time.format("%Y-%m-%d").to_string()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::relative_time;
+    use chrono::{Local, TimeZone};
+
+    #[test]
+    fn test_relative_time() {
+        let now = Local.with_ymd_and_hms(2024, 9, 12, 12, 0, 0).unwrap();
+        let at = |d, h, m| Local.with_ymd_and_hms(2024, 9, d, h, m, 0).unwrap();
+        assert_eq!(relative_time(now, now), "刚刚");
+        // 服务端时间略快于本地时间
+        assert_eq!(relative_time(at(12, 12, 1), now), "刚刚");
+        assert_eq!(relative_time(at(12, 11, 55), now), "5分钟前");
+        assert_eq!(relative_time(at(12, 8, 30), now), "08:30");
+        assert_eq!(relative_time(at(11, 18, 30), now), "昨天 18:30");
+        assert_eq!(relative_time(at(1, 18, 30), now), "09-01");
+        let last_year = Local.with_ymd_and_hms(2023, 9, 1, 0, 0, 0).unwrap();
+        assert_eq!(relative_time(last_year, now), "2023-09-01");
+    }
+}
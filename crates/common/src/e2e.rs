@@ -0,0 +1,330 @@
+//! 单聊的端到端加密：每个账号在本地生成身份密钥，公钥通过服务端交换，消息内容在客户端加密和解密
+//!
+//! 双方的身份密钥做 X25519 密钥交换，加上每条消息随机的盐经 HKDF-SHA256 派生出消息密钥，
+//! 用 ChaCha20-Poly1305 加密。只有持有任一方私钥的人才能算出密钥，所以解密成功也就验证了发送人。
+//! 双方都能解密同一条消息，聊天记录中自己发出的消息同样可以显示；代价是没有前向保密，私钥泄露后历史消息可以被解密。
+//!
+//! 对方的公钥只在 `/encrypt on` 时从发布公钥的接口获取并记住，不会采用消息中携带的公钥，
+//! 之后变化时提示，需要核对指纹后重新开启加密才会使用新的公钥。
+//! 密钥保存在 ~/.local/share/chat-cli/keys/ 下，不会上传私钥。
+use crate::config::{account_path, write_private};
+use crate::message::{HistoryMsg, MessageContent, MessageTarget, MessageTargetUser};
+use crate::sanitize::sanitize;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, OsRng, Payload};
+use chacha20poly1305::{AeadCore, ChaCha20Poly1305, KeyInit, Nonce};
+use hkdf::Hkdf;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::PathBuf;
+use x25519_dalek::{PublicKey, StaticSecret};
+
+/// 加密消息的 content_type
+pub const CONTENT_TYPE: &str = "application/x-chat-e2e";
+/// 加密消息的文字内容，不支持加密的客户端看到的是这段文字
+pub const ENCRYPTED_MESSAGE: &str = "[加密消息]";
+/// 派生消息密钥时的上下文，升级加密方式时修改
+const INFO: &[u8] = b"chat-cli e2e v1";
+
+/// 加密后的消息，保存在 `MessageContent` 的 `properties` 中，二进制内容都是 base64
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Envelope {
+    pub sender_key: String,
+    pub recipient_key: String,
+    pub salt: String,
+    pub nonce: String,
+    pub ciphertext: String,
+}
+
+impl Envelope {
+    /// 从消息内容中读取加密的消息，普通消息返回 None
+    pub fn from_content(content: &MessageContent) -> Option<Self> {
+        if content.content_type.as_deref() != Some(CONTENT_TYPE) {
+            return None;
+        }
+        let properties = content.properties.clone()?;
+        serde_json::from_value(Value::Object(properties.into_iter().collect())).ok()
+    }
+
+    /// 发送加密消息时的消息内容
+    pub fn to_content(&self) -> MessageContent {
+        let properties = match serde_json::to_value(self) {
+            Ok(Value::Object(properties)) => properties.into_iter().collect(),
+            _ => HashMap::new(),
+        };
+        MessageContent { properties: Some(properties), content_type: Some(CONTENT_TYPE.to_string()), content: ENCRYPTED_MESSAGE.to_string() }
+    }
+}
+
+/// 公钥的指纹：SHA-256 的前 16 字节，每 2 字节一组，双方当面或通过其他渠道核对
+pub fn fingerprint(public_key: &str) -> String {
+    let digest = Sha256::digest(public_key.as_bytes());
+    digest[..16].chunks(2).map(|chunk| format!("{:02x}{:02x}", chunk[0], chunk[1])).collect::<Vec<_>>().join(" ")
+}
+
+/// 收到对方的公钥时与记住的公钥比较的结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyCheck {
+    /// 第一次见到，已经记住
+    New,
+    Same,
+    /// 与记住的公钥不同，需要核对指纹
+    Changed,
+}
+
+/// 本账号的身份密钥、记住的对方公钥和开启了加密的会话
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct KeyStore {
+    #[serde(skip)]
+    path: Option<PathBuf>,
+    /// 身份私钥
+    secret: String,
+    /// 对方的公钥，按用户id
+    #[serde(default)]
+    peers: HashMap<i32, String>,
+    /// 对方以前信任过的公钥，只用于解密自己更早发出的消息
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    retired: HashMap<i32, Vec<String>>,
+    /// 开启了加密的单聊
+    #[serde(default)]
+    enabled: HashSet<i32>,
+}
+
+impl KeyStore {
+    /// 读取账号的密钥，第一次使用时生成身份密钥并保存
+    pub fn load(server: &str, uid: i32) -> Result<Self, String> {
+        let path = account_path("keys", server, uid);
+        let (mut store, generated) = match path.as_ref().filter(|path| path.exists()) {
+            Some(path) => {
+                let content = fs::read_to_string(path).map_err(|err| format!("读取{}失败: {err}", path.display()))?;
+                (serde_json::from_str(&content).map_err(|err| format!("解析{}失败: {err}", path.display()))?, false)
+            }
+            None => (Self::generate(), true),
+        };
+        store.path = path;
+        if generated {
+            store.save()?;
+        }
+        Ok(store)
+    }
+
+    pub(crate) fn generate() -> Self {
+        let secret = StaticSecret::random_from_rng(OsRng);
+        Self { secret: STANDARD.encode(secret.to_bytes()), ..Self::default() }
+    }
+
+    /// 保存密钥，文件只有自己可以读写
+    pub fn save(&self) -> Result<(), String> {
+        let path = self.path.as_ref().ok_or("无法确定数据目录")?;
+        let content = serde_json::to_string_pretty(self).map_err(|err| err.to_string())?;
+        write_private(path, &content)
+    }
+
+    fn secret(&self) -> Result<StaticSecret, String> {
+        let bytes: [u8; 32] = decode(&self.secret)?.try_into().map_err(|_| "身份密钥已损坏".to_string())?;
+        Ok(StaticSecret::from(bytes))
+    }
+
+    /// 自己的公钥，发布到服务端
+    pub fn public_key(&self) -> Result<String, String> {
+        Ok(STANDARD.encode(PublicKey::from(&self.secret()?).as_bytes()))
+    }
+
+    pub fn is_enabled(&self, uid: i32) -> bool {
+        self.enabled.contains(&uid)
+    }
+
+    pub fn set_enabled(&mut self, uid: i32, enabled: bool) {
+        if enabled {
+            self.enabled.insert(uid);
+        } else {
+            self.enabled.remove(&uid);
+        }
+    }
+
+    /// 记住的对方公钥
+    pub fn peer_key(&self, uid: i32) -> Option<&str> {
+        self.peers.get(&uid).map(String::as_str)
+    }
+
+    /// 比较对方的公钥，第一次见到时记住
+    pub fn check(&mut self, uid: i32, public_key: &str) -> KeyCheck {
+        match self.peers.get(&uid) {
+            Some(key) if key == public_key => KeyCheck::Same,
+            Some(_) => KeyCheck::Changed,
+            None => {
+                self.peers.insert(uid, public_key.to_string());
+                KeyCheck::New
+            }
+        }
+    }
+
+    /// 核对指纹后信任对方的新公钥
+    pub fn trust(&mut self, uid: i32, public_key: &str) {
+        if let Some(old) = self.peers.insert(uid, public_key.to_string()).filter(|old| old != public_key) {
+            self.retired.entry(uid).or_default().push(old);
+        }
+    }
+
+    /// 用记住的对方公钥加密发给 `uid` 的消息
+    pub fn encrypt(&self, uid: i32, plaintext: &str) -> Result<Envelope, String> {
+        let recipient_key = self.peer_key(uid).ok_or("还没有对方的公钥")?.to_string();
+        let sender_key = self.public_key()?;
+        let mut salt = [0; 16];
+        OsRng.fill_bytes(&mut salt);
+        let cipher = self.cipher(&recipient_key, &salt)?;
+        let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let aad = [sender_key.as_bytes(), recipient_key.as_bytes()].concat();
+        let ciphertext = cipher
+            .encrypt(&nonce, Payload { msg: plaintext.as_bytes(), aad: &aad })
+            .map_err(|_| "加密失败".to_string())?;
+        Ok(Envelope {
+            sender_key,
+            recipient_key,
+            salt: STANDARD.encode(salt),
+            nonce: STANDARD.encode(nonce),
+            ciphertext: STANDARD.encode(ciphertext),
+        })
+    }
+
+    /// 开启了加密的单聊返回加密后的消息，其他会话返回 None
+    pub fn encrypt_for(&self, target: MessageTarget, plaintext: &str) -> Result<Option<Envelope>, String> {
+        match target {
+            MessageTarget::User(MessageTargetUser { uid }) if self.is_enabled(uid) => self.encrypt(uid, plaintext).map(Some),
+            _ => Ok(None),
+        }
+    }
+
+    /// 解密单聊中的消息，`peer` 为对方的用户id，`from_me` 为是否是自己发出的消息。
+    /// 对方发来的消息只接受记住的公钥，公钥变化时返回错误；自己发出的消息也要求接收方是信任过的公钥，
+    /// 否则服务端可以把自己的公钥当作接收方，伪造“我”发出的加密消息
+    pub fn decrypt(&self, peer: i32, from_me: bool, envelope: &Envelope) -> Result<String, String> {
+        let my_key = self.public_key()?;
+        let (expected, other) = if from_me { (&envelope.sender_key, &envelope.recipient_key) } else { (&envelope.recipient_key, &envelope.sender_key) };
+        if *expected != my_key {
+            return Err("不是用当前密钥加密的消息".to_string());
+        }
+        let retired = from_me && self.retired.get(&peer).is_some_and(|keys| keys.contains(other));
+        match self.peer_key(peer) {
+            Some(key) if key == other => {}
+            _ if retired => {}
+            None => return Err("还没有对方的公钥".to_string()),
+            Some(_) if from_me => return Err("接收方的公钥没有经过信任，无法验证".to_string()),
+            Some(_) => return Err("对方的密钥已变化，无法验证".to_string()),
+        }
+        let cipher = self.cipher(other, &decode(&envelope.salt)?)?;
+        let nonce = decode(&envelope.nonce)?;
+        if nonce.len() != 12 {
+            return Err("消息已损坏".to_string());
+        }
+        let aad = [envelope.sender_key.as_bytes(), envelope.recipient_key.as_bytes()].concat();
+        let plaintext = cipher
+            .decrypt(Nonce::from_slice(&nonce), Payload { msg: &decode(&envelope.ciphertext)?, aad: &aad })
+            .map_err(|_| "解密失败，消息可能被篡改".to_string())?;
+        String::from_utf8(plaintext).map(|text| sanitize(&text)).map_err(|_| "消息已损坏".to_string())
+    }
+
+    /// 解密聊天记录中的加密消息，`msg` 替换为原文，无法解密时显示原因。
+    /// 消息中的公钥经过服务端，可能被替换，还没有记住对方的公钥时不采用，显示为无法解密
+    pub fn reveal(&self, msg: &mut HistoryMsg, peer: i32, my_uid: i32) {
+        let Some(envelope) = msg.encrypted.as_ref() else {
+            return;
+        };
+        msg.msg = match self.decrypt(peer, msg.from_uid == my_uid, envelope) {
+            Ok(text) => text,
+            Err(err) => format!("[无法解密的消息：{err}]"),
+        };
+    }
+
+    /// 与对方公钥交换后派生这条消息的密钥，双方算出的密钥相同
+    fn cipher(&self, other_key: &str, salt: &[u8]) -> Result<ChaCha20Poly1305, String> {
+        let other: [u8; 32] = decode(other_key)?.try_into().map_err(|_| "公钥格式不正确".to_string())?;
+        let shared = self.secret()?.diffie_hellman(&PublicKey::from(other));
+        // 两个公钥按固定顺序放入上下文，双方一致
+        let mut keys = [self.public_key()?, other_key.to_string()];
+        keys.sort();
+        let info = [INFO, keys[0].as_bytes(), keys[1].as_bytes()].concat();
+        let mut key = [0; 32];
+        Hkdf::<Sha256>::new(Some(salt), shared.as_bytes())
+            .expand(&info, &mut key)
+            .map_err(|_| "派生密钥失败".to_string())?;
+        Ok(ChaCha20Poly1305::new(&key.into()))
+    }
+}
+
+fn decode(text: &str) -> Result<Vec<u8>, String> {
+    STANDARD.decode(text).map_err(|_| "消息已损坏".to_string())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{fingerprint, Envelope, KeyCheck, KeyStore};
+    use crate::message::HistoryMsg;
+    use chrono::Local;
+
+    #[test]
+    fn test_encrypt() {
+        let mut alice = KeyStore::generate();
+        let mut bob = KeyStore::generate();
+        let (alice_key, bob_key) = (alice.public_key().unwrap(), bob.public_key().unwrap());
+        assert_eq!(alice.check(2, &bob_key), KeyCheck::New);
+        assert_eq!(alice.check(2, &bob_key), KeyCheck::Same);
+        bob.check(1, &alice_key);
+
+        let envelope = alice.encrypt(2, "你好").unwrap();
+        let content = envelope.to_content();
+        assert_eq!(content.content, "[加密消息]");
+        assert!(!serde_json::to_string(&content).unwrap().contains("你好"));
+        assert_eq!(Envelope::from_content(&content).as_ref(), Some(&envelope));
+        assert_eq!(bob.decrypt(1, false, &envelope).unwrap(), "你好");
+        // 自己发出的消息也能解密
+        assert_eq!(alice.decrypt(2, true, &envelope).unwrap(), "你好");
+
+        let mut tampered = envelope.clone();
+        tampered.ciphertext = envelope.ciphertext.replace(&envelope.ciphertext[..4], "AAAA");
+        assert!(bob.decrypt(1, false, &tampered).is_err());
+
+        // 冒充 alice 的人有自己的密钥，bob 记住的是 alice 的公钥
+        let mut mallory = KeyStore::generate();
+        mallory.check(2, &bob_key);
+        let forged = mallory.encrypt(2, "转账给我").unwrap();
+        assert_eq!(bob.decrypt(1, false, &forged).unwrap_err(), "对方的密钥已变化，无法验证");
+        assert_eq!(bob.check(1, &mallory.public_key().unwrap()), KeyCheck::Changed);
+
+        // 还没有记住对方的公钥时不采用消息中的公钥
+        // 换了设备的 bob 只有身份密钥，还没有获取 alice 的公钥
+        let new_device = KeyStore { secret: bob.secret.clone(), ..KeyStore::default() };
+        let mut msg = HistoryMsg {
+            mid: 1,
+            msg: "[加密消息]".to_string(),
+            time: Local::now(),
+            from_uid: 1,
+            edited: false,
+            recalled: false,
+            reactions: vec![],
+            attachment: None,
+            encrypted: Some(envelope),
+        };
+        new_device.reveal(&mut msg, 1, 3);
+        assert_eq!(msg.msg, "[无法解密的消息：还没有对方的公钥]");
+        assert!(new_device.peer_key(1).is_none());
+
+        // 服务端用自己的公钥冒充接收方，伪造“我”发出的消息
+        let server = KeyStore::generate();
+        let forged = Envelope { sender_key: alice_key.clone(), recipient_key: server.public_key().unwrap(), ..alice.encrypt(2, "x").unwrap() };
+        assert_eq!(alice.decrypt(2, true, &forged).unwrap_err(), "接收方的公钥没有经过信任，无法验证");
+        // 信任对方的新公钥后，发给旧公钥的消息仍然可以解密
+        let old = alice.encrypt(2, "以前的消息").unwrap();
+        alice.trust(2, &mallory.public_key().unwrap());
+        assert_eq!(alice.decrypt(2, true, &old).unwrap(), "以前的消息");
+        assert!(alice.decrypt(2, true, &alice.encrypt(2, "新的消息").unwrap()).is_ok());
+
+        assert_eq!(fingerprint(&alice_key).len(), 39);
+        assert_ne!(fingerprint(&alice_key), fingerprint(&bob_key));
+    }
+}
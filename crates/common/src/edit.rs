@@ -0,0 +1,111 @@
+//! 编辑和撤回自己发出的消息
+//!
+//! 编辑和撤回通过消息流以 `MessageDetail::Edit`、`MessageDetail::Recall` 推送，客户端在聊天记录中原地更新。
+use crate::message::{ChatMessagePayload, HistoryMsg, MessageDetail};
+use chrono::{DateTime, Local};
+
+/// 用消息流推送的编辑或撤回更新聊天记录，只能修改同一个人发出的消息，返回修改后的消息。
+/// 编辑的内容是明文，服务端可以随意伪造，加密消息不接受编辑
+pub fn amend<'a>(history: &'a mut [HistoryMsg], payload: &ChatMessagePayload) -> Option<&'a HistoryMsg> {
+    let mid = payload.detail.amended()?;
+    let msg = history.iter_mut().find(|msg| msg.mid == mid && msg.from_uid == payload.from_uid)?;
+    match &payload.detail {
+        MessageDetail::Recall(_) => msg.recalled = true,
+        _ if msg.encrypted.is_some() => return None,
+        detail => {
+            msg.msg = detail.get_content();
+            msg.edited = true;
+        }
+    }
+    Some(msg)
+}
+
+/// 按序号找到要编辑或撤回的消息，序号 1 为最新的一条，与 `/reply` 相同
+pub fn own_message<'a>(history: &[&'a HistoryMsg], index: usize, my_uid: i32) -> Result<&'a HistoryMsg, String> {
+    let msg = history
+        .len()
+        .checked_sub(index)
+        .map(|position| history[position])
+        .ok_or_else(|| format!("没有第{index}条消息"))?;
+    if msg.from_uid != my_uid {
+        return Err("只能修改自己发出的消息".to_string());
+    }
+    if msg.recalled {
+        return Err("消息已撤回".to_string());
+    }
+    Ok(msg)
+}
+
+/// 发出超过 `limit` 秒的消息不能撤回
+pub fn check_recall(msg: &HistoryMsg, now: DateTime<Local>, limit: u64) -> Result<(), String> {
+    if now.signed_duration_since(msg.time).num_seconds() > i64::try_from(limit).unwrap_or(i64::MAX) {
+        return Err(format!("只能撤回{}内发出的消息", format_limit(limit)));
+    }
+    Ok(())
+}
+
+fn format_limit(limit: u64) -> String {
+    if limit >= 60 && limit.is_multiple_of(60) {
+        format!("{}分钟", limit / 60)
+    } else {
+        format!("{limit}秒")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{amend, check_recall, own_message};
+    use crate::e2e::Envelope;
+    use crate::message::{
+        ChatMessagePayload, HistoryMsg, MessageContent, MessageDetail, MessageEdit, MessageRecall, MessageTarget, MessageTargetUser,
+        RECALLED_MESSAGE,
+    };
+    use chrono::{Duration, Local};
+
+    fn msg(mid: i64, from_uid: i32) -> HistoryMsg {
+        HistoryMsg { mid, msg: format!("m{mid}"), time: Local::now(), from_uid, edited: false, recalled: false, reactions: vec![], attachment: None, encrypted: None }
+    }
+
+    fn payload(from_uid: i32, detail: MessageDetail) -> ChatMessagePayload {
+        ChatMessagePayload { from_uid, created_at: Local::now(), target: MessageTarget::User(MessageTargetUser { uid: 2 }), detail, client_id: None }
+    }
+
+    #[test]
+    fn test_amend() {
+        let mut history = vec![msg(1, 1), msg(2, 2)];
+        let edit = MessageDetail::Edit(MessageEdit { mid: 1, content: MessageContent::text("hello") });
+        assert_eq!(amend(&mut history, &payload(1, edit.clone())).unwrap().display(), "hello（已编辑）");
+        // 不能修改别人的消息
+        assert!(amend(&mut history, &payload(1, MessageDetail::Recall(MessageRecall { mid: 2 }))).is_none());
+        assert!(amend(&mut history, &payload(1, MessageDetail::Recall(MessageRecall { mid: 1 }))).is_some());
+        assert_eq!(history[0].display(), RECALLED_MESSAGE);
+
+        let visible = history.iter().collect::<Vec<_>>();
+        assert_eq!(own_message(&visible, 2, 1).unwrap_err(), "消息已撤回");
+        assert_eq!(own_message(&visible, 1, 1).unwrap_err(), "只能修改自己发出的消息");
+        assert_eq!(own_message(&visible, 3, 1).unwrap_err(), "没有第3条消息");
+
+        let mut old = msg(3, 1);
+        old.time = Local::now() - Duration::seconds(121);
+        assert_eq!(check_recall(&old, Local::now(), 120).unwrap_err(), "只能撤回2分钟内发出的消息");
+        assert!(check_recall(&old, Local::now(), 300).is_ok());
+    }
+
+    #[test]
+    fn test_amend_encrypted() {
+        let envelope = Envelope {
+            sender_key: "a".to_string(),
+            recipient_key: "b".to_string(),
+            salt: String::new(),
+            nonce: String::new(),
+            ciphertext: String::new(),
+        };
+        let mut history = vec![HistoryMsg { encrypted: Some(envelope), ..msg(1, 1) }];
+        // 明文的编辑不能替换加密消息的内容，撤回仍然可以
+        let edit = MessageDetail::Edit(MessageEdit { mid: 1, content: MessageContent::text("转账给我") });
+        assert!(amend(&mut history, &payload(1, edit)).is_none());
+        assert_eq!(history[0].display(), "m1");
+        assert!(amend(&mut history, &payload(1, MessageDetail::Recall(MessageRecall { mid: 1 }))).is_some());
+        assert_eq!(history[0].display(), RECALLED_MESSAGE);
+    }
+}
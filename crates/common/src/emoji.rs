@@ -0,0 +1,220 @@
+//! 表情短代码：输入 `:+1:` 发送 👍，终端界面中输入 `:` 加两个以上字符时提示候选表情
+use crate::fuzzy;
+
+/// 短代码和对应的表情，与常见聊天软件的短代码一致
+pub const SHORTCODES: &[(&str, &str)] = &[
+    ("+1", "👍"),
+    ("thumbsup", "👍"),
+    ("-1", "👎"),
+    ("thumbsdown", "👎"),
+    ("ok_hand", "👌"),
+    ("clap", "👏"),
+    ("pray", "🙏"),
+    ("muscle", "💪"),
+    ("wave", "👋"),
+    ("raised_hands", "🙌"),
+    ("point_up", "☝️"),
+    ("eyes", "👀"),
+    ("smile", "😄"),
+    ("grin", "😁"),
+    ("joy", "😂"),
+    ("rofl", "🤣"),
+    ("sweat_smile", "😅"),
+    ("wink", "😉"),
+    ("blush", "😊"),
+    ("heart_eyes", "😍"),
+    ("kissing_heart", "😘"),
+    ("thinking", "🤔"),
+    ("neutral_face", "😐"),
+    ("smirk", "😏"),
+    ("unamused", "😒"),
+    ("roll_eyes", "🙄"),
+    ("grimacing", "😬"),
+    ("relieved", "😌"),
+    ("sleeping", "😴"),
+    ("sunglasses", "😎"),
+    ("confused", "😕"),
+    ("worried", "😟"),
+    ("open_mouth", "😮"),
+    ("astonished", "😲"),
+    ("flushed", "😳"),
+    ("cry", "😢"),
+    ("sob", "😭"),
+    ("scream", "😱"),
+    ("angry", "😠"),
+    ("rage", "😡"),
+    ("skull", "💀"),
+    ("poop", "💩"),
+    ("clown", "🤡"),
+    ("ghost", "👻"),
+    ("robot", "🤖"),
+    ("heart", "❤️"),
+    ("broken_heart", "💔"),
+    ("sparkling_heart", "💖"),
+    ("fire", "🔥"),
+    ("sparkles", "✨"),
+    ("star", "⭐"),
+    ("zap", "⚡"),
+    ("boom", "💥"),
+    ("100", "💯"),
+    ("tada", "🎉"),
+    ("confetti_ball", "🎊"),
+    ("gift", "🎁"),
+    ("cake", "🍰"),
+    ("beer", "🍺"),
+    ("coffee", "☕"),
+    ("pizza", "🍕"),
+    ("rocket", "🚀"),
+    ("bug", "🐛"),
+    ("warning", "⚠️"),
+    ("white_check_mark", "✅"),
+    ("heavy_check_mark", "✔️"),
+    ("x", "❌"),
+    ("question", "❓"),
+    ("exclamation", "❗"),
+    ("bulb", "💡"),
+    ("memo", "📝"),
+    ("calendar", "📅"),
+    ("lock", "🔒"),
+    ("key", "🔑"),
+    ("link", "🔗"),
+    ("bell", "🔔"),
+    ("mute", "🔇"),
+    ("hourglass", "⌛"),
+    ("see_no_evil", "🙈"),
+    ("dog", "🐶"),
+    ("cat", "🐱"),
+    ("sun", "☀️"),
+    ("rainbow", "🌈"),
+    ("cn", "🇨🇳"),
+    ("us", "🇺🇸"),
+    ("thumbsup_tone1", "👍🏻"),
+    ("thumbsup_tone5", "👍🏿"),
+];
+
+/// 短代码对应的表情
+pub fn lookup(code: &str) -> Option<&'static str> {
+    SHORTCODES.iter().find(|(name, _)| *name == code).map(|(_, emoji)| *emoji)
+}
+
+/// 短代码中可以出现的字符
+fn is_code_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_' || c == '+' || c == '-'
+}
+
+/// 把文字中认识的 `:短代码:` 替换为表情，不认识的原样保留；行内代码和 ``` 代码块中的不替换
+pub fn expand(text: &str) -> String {
+    let mut expanded = String::with_capacity(text.len());
+    let mut in_code = false;
+    for line in text.split_inclusive('\n') {
+        if line.trim_start().starts_with("```") {
+            in_code = !in_code;
+            expanded.push_str(line);
+        } else if in_code {
+            expanded.push_str(line);
+        } else {
+            expand_line(line, &mut expanded);
+        }
+    }
+    expanded
+}
+
+/// 替换代码块外的一行，`行内代码` 原样保留，与 Markdown 的解析一致
+fn expand_line(line: &str, expanded: &mut String) {
+    let mut rest = line;
+    while let Some(start) = rest.find('`') {
+        expand_codes(&rest[..start], expanded);
+        let after = &rest[start + 1..];
+        match after.find('`').filter(|end| *end > 0) {
+            Some(end) => {
+                expanded.push_str(&rest[start..start + end + 2]);
+                rest = &after[end + 1..];
+            }
+            None => {
+                expanded.push('`');
+                rest = after;
+            }
+        }
+    }
+    expand_codes(rest, expanded);
+}
+
+fn expand_codes(text: &str, expanded: &mut String) {
+    let mut rest = text;
+    while let Some(start) = rest.find(':') {
+        expanded.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let end = after.find(|c| !is_code_char(c));
+        match end.filter(|end| after[*end..].starts_with(':')).and_then(|end| Some((end, lookup(&after[..end])?))) {
+            Some((end, emoji)) => {
+                expanded.push_str(emoji);
+                rest = &after[end + 1..];
+            }
+            None => {
+                expanded.push(':');
+                rest = after;
+            }
+        }
+    }
+    expanded.push_str(rest);
+}
+
+/// 光标前正在输入的短代码：返回 `:` 的字符位置和已输入的前缀，至少输入两个字符才提示，
+/// 避免 `10:30` 这样的时间或 `:)` 触发；正在输入行内代码时不提示
+pub fn pending(text: &str, cursor: usize) -> Option<(usize, String)> {
+    let before = text.chars().take(cursor).collect::<Vec<_>>();
+    if before.iter().filter(|c| **c == '`').count() % 2 == 1 {
+        return None;
+    }
+    let start = before.iter().rposition(|c| !is_code_char(*c))?;
+    let prefix = before[start + 1..].iter().collect::<String>();
+    if before[start] != ':' || prefix.chars().count() < 2 || (start > 0 && before[start - 1].is_ascii_alphanumeric()) {
+        return None;
+    }
+    Some((start, prefix))
+}
+
+/// 匹配前缀的短代码和表情，前缀匹配的排在前面，同一个表情只出现一次
+pub fn candidates(prefix: &str) -> Vec<(&'static str, &'static str)> {
+    let mut matched = fuzzy::rank(prefix, SHORTCODES.iter().collect(), |(name, _)| name)
+        .into_iter()
+        .filter(|(name, _)| fuzzy::score(prefix, name).is_some())
+        .copied()
+        .collect::<Vec<_>>();
+    matched.sort_by_key(|(name, _)| !name.starts_with(prefix));
+    let mut seen = vec![];
+    matched.retain(|(_, emoji)| {
+        let first = !seen.contains(emoji);
+        seen.push(*emoji);
+        first
+    });
+    matched
+}
+
+#[cfg(test)]
+mod test {
+    use super::{candidates, expand, pending};
+
+    #[test]
+    fn test_expand() {
+        assert_eq!(expand(":+1: 好的:tada:"), "👍 好的🎉");
+        assert_eq!(expand("10:30 开会 :unknown: :)"), "10:30 开会 :unknown: :)");
+        assert_eq!(expand("::fire::"), ":🔥:");
+        assert_eq!(expand(":cn:"), "🇨🇳");
+        // 代码中的短代码不替换
+        assert_eq!(expand("`a:x:b` :x: ``:x:"), "`a:x:b` ❌ ``❌");
+        assert_eq!(expand(":+1:\n```\nlet a = b::c:x:;\n```\n:fire:"), "👍\n```\nlet a = b::c:x:;\n```\n🔥");
+    }
+
+    #[test]
+    fn test_pending() {
+        assert_eq!(pending("好的 :ta", 6), Some((3, "ta".to_string())));
+        assert_eq!(pending("好的 :t", 5), None);
+        assert_eq!(pending("10:30", 5), None);
+        assert_eq!(pending(":ta", 3), Some((0, "ta".to_string())));
+        assert_eq!(pending("`a :ta", 6), None);
+        assert_eq!(pending("`a` :ta", 7), Some((4, "ta".to_string())));
+        assert_eq!(candidates("thumbsu").first(), Some(&("thumbsup", "👍")));
+        assert!(candidates("ta").iter().any(|(_, emoji)| *emoji == "🎉"));
+    }
+}
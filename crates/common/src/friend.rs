@@ -0,0 +1,119 @@
+//! 好友申请
+use crate::datetime::datetime_format;
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
+
+/// 收到的好友申请
+#[derive(Debug, Clone, Deserialize)]
+pub struct FriendReqVo {
+    pub id: i32,
+    pub request_id: i32,
+    #[serde(deserialize_with = "crate::sanitize::line")]
+    pub request_name: String,
+    #[serde(with = "datetime_format")]
+    pub create_time: DateTime<Local>,
+    #[serde(default, deserialize_with = "crate::sanitize::optional_text")]
+    pub reason: Option<String>,
+    pub status: FriendRequestStatus,
+}
+
+impl FriendReqVo {
+    pub fn reason(&self) -> &str {
+        self.reason.as_deref().unwrap_or("请求添加好友")
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FriendRequestStatus {
+    WAIT,
+    APPROVE,
+    REJECT,
+}
+
+impl FriendRequestStatus {
+    /// 申请列表的筛选项，None 表示全部
+    pub const FILTERS: [Option<FriendRequestStatus>; 4] = [
+        Some(FriendRequestStatus::WAIT),
+        Some(FriendRequestStatus::APPROVE),
+        Some(FriendRequestStatus::REJECT),
+        None,
+    ];
+
+    pub fn matches(filter: Option<FriendRequestStatus>, status: FriendRequestStatus) -> bool {
+        filter.is_none_or(|filter| filter == status)
+    }
+
+    pub fn filter_name(filter: Option<FriendRequestStatus>) -> String {
+        filter.map_or("全部".to_string(), |status| status.to_string())
+    }
+}
+
+impl Display for FriendRequestStatus {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                FriendRequestStatus::WAIT => "待处理",
+                FriendRequestStatus::APPROVE => "已同意",
+                FriendRequestStatus::REJECT => "已拒绝",
+            }
+        )
+    }
+}
+
+/// 批量处理好友申请的结果
+#[derive(Debug, Default)]
+pub struct ReviewSummary {
+    pub approved: u32,
+    pub rejected: u32,
+    /// 处理失败的申请人和原因
+    pub failed: Vec<(String, String)>,
+}
+
+impl ReviewSummary {
+    pub fn record(&mut self, name: &str, status: FriendRequestStatus, result: Result<(), String>) {
+        match (result, status) {
+            (Err(err), _) => self.failed.push((name.to_string(), err)),
+            (Ok(_), FriendRequestStatus::APPROVE) => self.approved += 1,
+            (Ok(_), FriendRequestStatus::REJECT) => self.rejected += 1,
+            (Ok(_), FriendRequestStatus::WAIT) => {}
+        }
+    }
+}
+
+impl Display for ReviewSummary {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "同意 {} 条，拒绝 {} 条", self.approved, self.rejected)?;
+        if !self.failed.is_empty() {
+            let failed = self
+                .failed
+                .iter()
+                .map(|(name, err)| format!("{name}（{err}）"))
+                .collect::<Vec<_>>()
+                .join("，");
+            write!(f, "，失败 {} 条：{failed}", self.failed.len())?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{FriendRequestStatus, ReviewSummary};
+
+    #[test]
+    fn test_review_summary() {
+        let mut summary = ReviewSummary::default();
+        summary.record("alice", FriendRequestStatus::APPROVE, Ok(()));
+        summary.record("bob", FriendRequestStatus::APPROVE, Ok(()));
+        summary.record("carol", FriendRequestStatus::REJECT, Ok(()));
+        assert_eq!(summary.to_string(), "同意 2 条，拒绝 1 条");
+        summary.record("dave", FriendRequestStatus::REJECT, Err("HTTP 404".to_string()));
+        assert_eq!(summary.to_string(), "同意 2 条，拒绝 1 条，失败 1 条：dave（HTTP 404）");
+
+        assert!(FriendRequestStatus::matches(None, FriendRequestStatus::REJECT));
+        assert!(!FriendRequestStatus::matches(Some(FriendRequestStatus::WAIT), FriendRequestStatus::REJECT));
+    }
+}
@@ -0,0 +1,68 @@
+//! 模糊匹配，用于对搜索结果排序
+
+/// 匹配得分，越高越相关；查询的字符不能按顺序出现在文本中时返回 None
+///
+/// 完全匹配 > 前缀匹配 > 连续子串 > 按顺序出现的字符，同类匹配中间隔越少、越靠前得分越高。
+pub fn score(query: &str, text: &str) -> Option<u32> {
+    let query = query.trim().to_lowercase();
+    let text = text.to_lowercase();
+    if query.is_empty() {
+        return Some(0);
+    }
+    if text == query {
+        return Some(3000);
+    }
+    if text.starts_with(&query) {
+        return Some(2000 - length_penalty(&text, &query));
+    }
+    if let Some(index) = text.find(&query) {
+        let position = text[..index].chars().count() as u32;
+        return Some(1000 - position.min(500) - length_penalty(&text, &query));
+    }
+    // 按顺序查找每个字符，累计间隔
+    let mut chars = text.chars().enumerate();
+    let mut first = None;
+    let mut last = 0;
+    let mut gaps = 0u32;
+    for q in query.chars() {
+        let (index, _) = chars.find(|(_, c)| *c == q)?;
+        match first {
+            None => first = Some(index),
+            Some(_) => gaps += (index - last - 1) as u32,
+        }
+        last = index;
+    }
+    let position = first.unwrap_or(0) as u32;
+    Some(500u32.saturating_sub(gaps * 10 + position))
+}
+
+/// 文本比查询长出的部分，短的结果排在前面
+fn length_penalty(text: &str, query: &str) -> u32 {
+    (text.chars().count() - query.chars().count()).min(100) as u32
+}
+
+/// 按匹配得分从高到低排序，不匹配的排在最后，得分相同时保持原有顺序
+pub fn rank<T>(query: &str, items: Vec<T>, key: impl Fn(&T) -> &str) -> Vec<T> {
+    let mut scored = items
+        .into_iter()
+        .map(|item| (score(query, key(&item)), item))
+        .collect::<Vec<_>>();
+    scored.sort_by(|(a, _), (b, _)| b.cmp(a));
+    scored.into_iter().map(|(_, item)| item).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::{rank, score};
+
+    #[test]
+    fn test_fuzzy_rank() {
+        assert!(score("ali", "Alice") > score("ali", "Natalie"));
+        assert!(score("alice", "alice") > score("alice", "alice2"));
+        assert!(score("ace", "alice") > score("ace", "a_l_i_c_e"));
+        assert_eq!(score("bob", "alice"), None);
+
+        let names = vec!["bobalice", "xbob", "alice", "al_ice", "carol", "alice1"];
+        assert_eq!(rank("alice", names, |name| name), vec!["alice", "alice1", "bobalice", "al_ice", "xbob", "carol"]);
+    }
+}
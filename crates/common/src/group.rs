@@ -0,0 +1,109 @@
+//! 群聊
+use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
+
+/// 群不存在或已被移出群聊时的提示
+pub const GROUP_GONE: &str = "群聊已解散或你已不在群中";
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct GroupVo {
+    pub gid: i32,
+    #[serde(deserialize_with = "crate::sanitize::line")]
+    pub name: String,
+    /// 群主id
+    pub owner: i32,
+    /// 置顶公告
+    #[serde(default, deserialize_with = "crate::sanitize::optional_text")]
+    pub announcement: Option<String>,
+}
+
+impl GroupVo {
+    pub fn is_owner(&self, uid: i32) -> bool {
+        self.owner == uid
+    }
+}
+
+/// 群成员角色，按权限从高到低排序
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Ord, PartialOrd, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GroupRole {
+    Owner,
+    Admin,
+    #[default]
+    Member,
+}
+
+impl GroupRole {
+    /// 群主和管理员可以发布公告、移除普通成员
+    pub fn can_manage(&self) -> bool {
+        *self != GroupRole::Member
+    }
+
+    /// 是否可以移除指定角色的成员：群主可以移除任何人，管理员只能移除普通成员
+    pub fn can_remove(&self, target: GroupRole) -> bool {
+        match self {
+            GroupRole::Owner => target != GroupRole::Owner,
+            GroupRole::Admin => target == GroupRole::Member,
+            GroupRole::Member => false,
+        }
+    }
+}
+
+impl Display for GroupRole {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GroupRole::Owner => write!(f, "群主"),
+            GroupRole::Admin => write!(f, "管理员"),
+            GroupRole::Member => write!(f, "成员"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct GroupMember {
+    pub uid: i32,
+    #[serde(deserialize_with = "crate::sanitize::line")]
+    pub name: String,
+    #[serde(default)]
+    pub role: GroupRole,
+}
+
+/// 成员列表按角色排序，群主在最前
+pub fn sort_members(members: &mut [GroupMember]) {
+    members.sort_by_key(|member| member.role);
+}
+
+/// 当前用户在群中的角色，不在成员列表中时视为普通成员
+pub fn role_of(members: &[GroupMember], uid: i32) -> GroupRole {
+    members
+        .iter()
+        .find(|member| member.uid == uid)
+        .map_or(GroupRole::Member, |member| member.role)
+}
+
+/// 群相关接口返回 404 或 403 时，表示群已解散或当前用户已不在群中
+pub fn is_gone(status: u16) -> bool {
+    status == 404 || status == 403
+}
+
+#[cfg(test)]
+mod test {
+    use super::{role_of, sort_members, GroupMember, GroupRole};
+
+    #[test]
+    fn test_roles() {
+        let mut members: Vec<GroupMember> = serde_json::from_str(
+            r#"[{"uid":3,"name":"c"},{"uid":2,"name":"b","role":"admin"},{"uid":1,"name":"a","role":"owner"}]"#,
+        )
+        .unwrap();
+        sort_members(&mut members);
+        assert_eq!(members.iter().map(|member| member.uid).collect::<Vec<_>>(), vec![1, 2, 3]);
+        assert_eq!(role_of(&members, 2), GroupRole::Admin);
+        assert_eq!(role_of(&members, 4), GroupRole::Member);
+
+        assert!(GroupRole::Owner.can_remove(GroupRole::Admin));
+        assert!(!GroupRole::Admin.can_remove(GroupRole::Admin));
+        assert!(GroupRole::Admin.can_remove(GroupRole::Member));
+        assert!(!GroupRole::Member.can_manage());
+    }
+}
@@ -0,0 +1,24 @@
+//! chat-cli 与 ui 共用的代码
+pub mod attachment;
+pub mod command;
+pub mod config;
+pub mod contact;
+pub mod datetime;
+pub mod e2e;
+pub mod edit;
+pub mod emoji;
+pub mod friend;
+pub mod fuzzy;
+pub mod group;
+pub mod markdown;
+pub mod mention;
+pub mod message;
+pub mod notify;
+pub mod outbox;
+pub mod profile;
+pub mod reaction;
+pub mod sanitize;
+pub mod receipt;
+pub mod text;
+pub mod typing;
+pub mod unread;
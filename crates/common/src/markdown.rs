@@ -0,0 +1,202 @@
+//! 消息中的 Markdown 子集：**粗体**、*斜体*、`行内代码`、``` 代码块、> 引用和链接
+//!
+//! 只解析为带样式的文字片段，由命令行和终端界面各自决定怎么显示；不认识的标记原样显示。
+use crate::sanitize::sanitize;
+use crate::text::display_width;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Style {
+    pub bold: bool,
+    pub italic: bool,
+    pub code: bool,
+}
+
+/// 一段样式相同的文字，`link` 为链接地址
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Span {
+    pub text: String,
+    pub style: Style,
+    pub link: Option<String>,
+}
+
+/// 消息中的一行
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Block {
+    Text(Vec<Span>),
+    Quote(Vec<Span>),
+    /// 代码块中的一行，不解析其中的标记
+    Code(String),
+}
+
+/// 去掉控制序列后按行解析，未闭合的代码块延续到消息末尾
+pub fn parse(text: &str) -> Vec<Block> {
+    let text = sanitize(text);
+    let mut blocks = vec![];
+    let mut in_code = false;
+    for line in text.lines() {
+        if line.trim_start().starts_with("```") {
+            in_code = !in_code;
+            continue;
+        }
+        blocks.push(if in_code {
+            Block::Code(line.replace('\t', "    "))
+        } else if let Some(quote) = line.strip_prefix('>') {
+            Block::Quote(inline(quote.strip_prefix(' ').unwrap_or(quote)))
+        } else {
+            Block::Text(inline(line))
+        });
+    }
+    blocks
+}
+
+/// 解析一行中的行内标记
+pub fn inline(text: &str) -> Vec<Span> {
+    let mut spans = vec![];
+    parse_inline(text, Style::default(), &mut spans);
+    spans
+}
+
+fn parse_inline(text: &str, style: Style, spans: &mut Vec<Span>) {
+    let mut plain = String::new();
+    let mut rest = text;
+    while let Some(c) = rest.chars().next() {
+        if let Some((span, remaining)) = code(rest, style).or_else(|| link(rest, style)) {
+            flush(&mut plain, style, spans);
+            spans.push(span);
+            rest = remaining;
+            continue;
+        }
+        if let Some((inner, remaining, emphasis)) = emphasis(rest) {
+            flush(&mut plain, style, spans);
+            let style = match emphasis {
+                Emphasis::Bold => Style { bold: true, ..style },
+                Emphasis::Italic => Style { italic: true, ..style },
+            };
+            parse_inline(inner, style, spans);
+            rest = remaining;
+            continue;
+        }
+        // 链接只从单词开头识别
+        if c.is_alphanumeric() {
+            let end = rest.find(|c: char| !c.is_alphanumeric()).unwrap_or(rest.len());
+            plain.push_str(&rest[..end]);
+            rest = &rest[end..];
+        } else {
+            plain.push(c);
+            rest = &rest[c.len_utf8()..];
+        }
+    }
+    flush(&mut plain, style, spans);
+}
+
+fn flush(plain: &mut String, style: Style, spans: &mut Vec<Span>) {
+    if !plain.is_empty() {
+        spans.push(Span { text: std::mem::take(plain), style, link: None });
+    }
+}
+
+/// `行内代码`
+fn code(text: &str, style: Style) -> Option<(Span, &str)> {
+    let inner = text.strip_prefix('`')?;
+    let end = inner.find('`').filter(|end| *end > 0)?;
+    let span = Span { text: inner[..end].to_string(), style: Style { code: true, ..style }, link: None };
+    Some((span, &inner[end + 1..]))
+}
+
+enum Emphasis {
+    Bold,
+    Italic,
+}
+
+/// `**粗体**` 和 `*斜体*`，标记内侧不能是空格，避免把 `a * b * c` 当作斜体
+fn emphasis(text: &str) -> Option<(&str, &str, Emphasis)> {
+    let (marker, emphasis) = if text.starts_with("**") { ("**", Emphasis::Bold) } else { ("*", Emphasis::Italic) };
+    let inner = text.strip_prefix(marker)?;
+    if inner.starts_with(char::is_whitespace) {
+        return None;
+    }
+    let end = inner.find(marker).filter(|end| *end > 0 && !inner[..*end].ends_with(char::is_whitespace))?;
+    Some((&inner[..end], &inner[end + marker.len()..], emphasis))
+}
+
+/// `[文字](地址)` 和直接写出的 http(s) 地址
+fn link(text: &str, style: Style) -> Option<(Span, &str)> {
+    if let Some(inner) = text.strip_prefix('[') {
+        let (label, rest) = inner.split_once("](")?;
+        let (url, rest) = rest.split_once(')')?;
+        if label.is_empty() || label.contains(']') || !is_url(url) {
+            return None;
+        }
+        return Some((Span { text: label.to_string(), style, link: Some(url.to_string()) }, rest));
+    }
+    if !is_url(text.split(char::is_whitespace).next()?) {
+        return None;
+    }
+    let end = text.find(char::is_whitespace).unwrap_or(text.len());
+    // 句末的标点不算在链接里
+    let end = text[..end].trim_end_matches(['.', ',', ';', ':', '!', '?', ')', '\'', '"', '，', '。', '；', '：', '！', '？', '）']).len();
+    let url = &text[..end];
+    Some((Span { text: url.to_string(), style, link: Some(url.to_string()) }, &text[end..]))
+}
+
+fn is_url(text: &str) -> bool {
+    ["http://", "https://"]
+        .iter()
+        .any(|scheme| text.strip_prefix(scheme).is_some_and(|rest| !rest.is_empty() && !rest.contains(char::is_whitespace)))
+}
+
+/// 给代码块加上边框，`lines` 为连续的代码行
+pub fn code_box(lines: &[&str]) -> Vec<String> {
+    let width = lines.iter().map(|line| display_width(line)).max().unwrap_or(0);
+    let mut boxed = vec![format!("┌{}┐", "─".repeat(width + 2))];
+    boxed.extend(lines.iter().map(|line| format!("│ {line}{} │", " ".repeat(width - display_width(line)))));
+    boxed.push(format!("└{}┘", "─".repeat(width + 2)));
+    boxed
+}
+
+#[cfg(test)]
+mod test {
+    use super::{code_box, inline, parse, Block, Span, Style};
+
+    fn plain(text: &str) -> Span {
+        Span { text: text.to_string(), style: Style::default(), link: None }
+    }
+
+    #[test]
+    fn test_inline() {
+        let bold = Style { bold: true, ..Style::default() };
+        let code = Style { code: true, ..Style::default() };
+        assert_eq!(
+            inline("**看** `cargo run` *了*"),
+            vec![
+                Span { text: "看".to_string(), style: bold, link: None },
+                plain(" "),
+                Span { text: "cargo run".to_string(), style: code, link: None },
+                plain(" "),
+                Span { text: "了".to_string(), style: Style { italic: true, ..Style::default() }, link: None },
+            ]
+        );
+        // 不完整的标记原样显示
+        assert_eq!(inline("a * b * c"), vec![plain("a * b * c")]);
+        assert_eq!(inline("**没闭合"), vec![plain("**没闭合")]);
+        let link = |text: &str, url: &str| Span { text: text.to_string(), style: Style::default(), link: Some(url.to_string()) };
+        assert_eq!(inline("见 https://a.com/x?y=1。"), vec![plain("见 "), link("https://a.com/x?y=1", "https://a.com/x?y=1"), plain("。")]);
+        assert_eq!(inline("[文档](https://a.com)"), vec![link("文档", "https://a.com")]);
+        assert_eq!(inline("xhttps://a.com"), vec![plain("xhttps://a.com")]);
+    }
+
+    #[test]
+    fn test_parse() {
+        let blocks = parse("> 引用\n```rust\nfn main() {}\n\tx\n```\n完\u{1b}[2J");
+        assert_eq!(
+            blocks,
+            vec![
+                Block::Quote(vec![plain("引用")]),
+                Block::Code("fn main() {}".to_string()),
+                Block::Code("    x".to_string()),
+                Block::Text(vec![plain("完")]),
+            ]
+        );
+        assert_eq!(code_box(&["你好", "abc"]), vec!["┌──────┐", "│ 你好 │", "│ abc  │", "└──────┘"]);
+    }
+}
@@ -0,0 +1,121 @@
+//! @提及：补全、展开和识别
+//!
+//! 提及使用对方的用户名而不是备注，这样对方才能识别出提到了自己。
+use crate::fuzzy;
+use std::collections::HashSet;
+
+/// 用户名中可以出现的字符，用户名只能是英文和数字，中文紧跟在提及后面时不算用户名
+fn is_name_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+/// 光标前正在输入的提及：返回 `@` 的字符位置和已输入的前缀
+///
+/// `@` 前面紧跟用户名字符时（如邮箱）不算提及。
+pub fn pending(text: &str, cursor: usize) -> Option<(usize, String)> {
+    let before = text.chars().take(cursor).collect::<Vec<_>>();
+    let start = before.iter().rposition(|c| !is_name_char(*c))?;
+    if before[start] != '@' || (start > 0 && is_name_char(before[start - 1])) {
+        return None;
+    }
+    Some((start, before[start + 1..].iter().collect()))
+}
+
+/// 匹配前缀的候选名称，按相关度排序并去重
+pub fn candidates(prefix: &str, names: &[String]) -> Vec<String> {
+    let mut matched = fuzzy::rank(prefix, names.iter().collect(), |name| name.as_str())
+        .into_iter()
+        .filter(|name| fuzzy::score(prefix, name).is_some())
+        .cloned()
+        .collect::<Vec<_>>();
+    let mut seen = HashSet::new();
+    matched.retain(|name| seen.insert(name.clone()));
+    matched
+}
+
+/// 文本中的所有提及：`@` 的字节位置和用户名
+fn tokens(text: &str) -> Vec<(usize, &str)> {
+    let mut tokens = vec![];
+    let mut prev = None;
+    for (index, c) in text.char_indices() {
+        if c == '@' && !prev.is_some_and(is_name_char) {
+            let rest = &text[index + 1..];
+            let end = rest.find(|c| !is_name_char(c)).unwrap_or(rest.len());
+            if end > 0 {
+                tokens.push((index, &rest[..end]));
+            }
+        }
+        prev = Some(c);
+    }
+    tokens
+}
+
+/// 消息是否提到了指定用户
+pub fn mentions(text: &str, name: &str) -> bool {
+    !name.is_empty() && tokens(text).iter().any(|(_, token)| *token == name)
+}
+
+/// 发送前展开不完整的提及，如 `@bo` 只匹配 `bob` 时展开为 `@bob`
+///
+/// 匹配多个名称时返回错误，列出可能的名称；没有匹配的保持原样。
+pub fn expand(text: &str, names: &[String]) -> Result<String, String> {
+    let mut expanded = String::with_capacity(text.len());
+    let mut last = 0;
+    for (index, token) in tokens(text) {
+        if names.iter().any(|name| name == token) {
+            continue;
+        }
+        let matched = candidates(token, names)
+            .into_iter()
+            .filter(|name| name.to_lowercase().starts_with(&token.to_lowercase()))
+            .collect::<Vec<_>>();
+        match matched.as_slice() {
+            [] => {}
+            [name] => {
+                expanded.push_str(&text[last..index + 1]);
+                expanded.push_str(name);
+                last = index + 1 + token.len();
+            }
+            _ => return Err(format!("@{token} 可能是：{}", matched.join("、"))),
+        }
+    }
+    expanded.push_str(&text[last..]);
+    Ok(expanded)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{candidates, expand, mentions, pending};
+
+    fn names() -> Vec<String> {
+        ["bob", "bobby", "alice"].iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn test_pending() {
+        assert_eq!(pending("hi @bo", 6), Some((3, "bo".to_string())));
+        assert_eq!(pending("@", 1), Some((0, String::new())));
+        assert_eq!(pending("你好@al 在吗", 5), Some((2, "al".to_string())));
+        assert_eq!(pending("a@bo", 4), None);
+        assert_eq!(pending("hi @bo ", 7), None);
+        assert_eq!(candidates("bo", &names()), vec!["bob", "bobby"]);
+    }
+
+    #[test]
+    fn test_mentions() {
+        assert!(mentions("@bob 开会了", "bob"));
+        assert!(mentions("开会了@bob", "bob"));
+        assert!(mentions("@bob开会了", "bob"));
+        assert!(!mentions("@bobby 开会了", "bob"));
+        assert!(!mentions("bob@bob.com", "bob"));
+        assert!(!mentions("@bob", ""));
+    }
+
+    #[test]
+    fn test_expand() {
+        assert_eq!(expand("@ali 开会", &names()).unwrap(), "@alice 开会");
+        assert_eq!(expand("@bob 和 @ali", &names()).unwrap(), "@bob 和 @alice");
+        assert_eq!(expand("@home 见", &names()).unwrap(), "@home 见");
+        assert_eq!(expand("@bo 在吗", &names()).unwrap_err(), "@bo 可能是：bob、bobby");
+    }
+}
@@ -0,0 +1,376 @@
+use crate::attachment::Attachment;
+use crate::datetime::datetime_format;
+use crate::e2e::Envelope;
+use crate::reaction::Reaction;
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Message {
+    ChatMessage(ChatMessage),
+    Heartbeat(HeartbeatMessage),
+    Typing(TypingMessage),
+    ReadIndex(ReadIndexMessage),
+    Reaction(ReactionMessage),
+}
+
+// 也可以使用strum库来实现
+impl Display for Message {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Message::ChatMessage(_) => "Chat",
+                Message::Heartbeat(_) => "Heartbeat",
+                Message::Typing(_) => "Typing",
+                Message::ReadIndex(_) => "ReadIndex",
+                Message::Reaction(_) => "Reaction",
+            }
+        )
+    }
+}
+
+impl Message {
+    /// 客户端认识的消息类型
+    const KINDS: [&'static str; 5] = ["ChatMessage", "Heartbeat", "Typing", "ReadIndex", "Reaction"];
+
+    /// 从一段SSE数据中解析出消息，没有data行时返回None
+    ///
+    /// 服务端和客户端版本不一致时，不认识的消息类型同样返回None，不影响其他消息。
+    pub fn from_sse(sse_message: &str) -> Option<serde_json::Result<Message>> {
+        let data = sse_message
+            .lines()
+            .find(|line| line.starts_with("data:"))
+            .map(|line| line.trim_start_matches("data:").trim())
+            .filter(|line| !line.is_empty())?;
+        match serde_json::from_str::<Message>(data) {
+            Err(_) if Self::is_unknown(data) => None,
+            result => Some(result),
+        }
+    }
+
+    fn is_unknown(data: &str) -> bool {
+        match serde_json::from_str::<serde_json::Value>(data) {
+            Ok(serde_json::Value::Object(object)) => object.keys().all(|kind| !Self::KINDS.contains(&kind.as_str())),
+            _ => false,
+        }
+    }
+}
+
+/// 正在输入的通知，单聊的 `target` 是接收方，群聊是群
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TypingMessage {
+    pub from_uid: i32,
+    pub target: MessageTarget,
+}
+
+/// 其他人的已读位置更新，单聊的 `target` 是自己，群聊是群
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReadIndexMessage {
+    pub uid: i32,
+    pub target: MessageTarget,
+    pub mid: i64,
+}
+
+impl ReadIndexMessage {
+    /// 已读位置所属的会话：群聊为群本身，单聊为对方
+    pub fn conversation(&self) -> MessageTarget {
+        match self.target {
+            MessageTarget::User(_) => MessageTarget::User(MessageTargetUser { uid: self.uid }),
+            target => target,
+        }
+    }
+}
+
+/// 对消息的表情回应，`removed` 为 true 时是取消回应；`target` 与聊天消息相同
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReactionMessage {
+    pub from_uid: i32,
+    pub target: MessageTarget,
+    pub mid: i64,
+    #[serde(deserialize_with = "crate::sanitize::line")]
+    pub emoji: String,
+    #[serde(default)]
+    pub removed: bool,
+}
+
+impl ReactionMessage {
+    /// 回应所属的会话：群聊为群本身，单聊为对方
+    pub fn conversation(&self, my_uid: i32) -> MessageTarget {
+        match self.target {
+            MessageTarget::User(_) if self.from_uid != my_uid => MessageTarget::User(MessageTargetUser { uid: self.from_uid }),
+            target => target,
+        }
+    }
+}
+
+impl TypingMessage {
+    /// 通知所属的会话：群聊为群本身，单聊为对方
+    pub fn conversation(&self) -> MessageTarget {
+        match self.target {
+            MessageTarget::User(_) => MessageTarget::User(MessageTargetUser { uid: self.from_uid }),
+            target => target,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HeartbeatMessage {
+    #[serde(with = "datetime_format")]
+    time: DateTime<Local>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct ChatMessage {
+    /// Message id
+    pub mid: i64,
+    pub payload: ChatMessagePayload,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ChatMessagePayload {
+    /// Sender id
+    pub from_uid: i32,
+
+    #[serde(with = "datetime_format")]
+    /// The create time of the message.
+    pub created_at: DateTime<Local>,
+
+    /// Message target
+    pub target: MessageTarget,
+
+    /// Message detail
+    pub detail: MessageDetail,
+
+    /// 发送时客户端生成的id，用于识别重发的消息，旧版本服务端没有这个字段
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_id: Option<String>,
+}
+
+impl ChatMessagePayload {
+    /// 消息所属的会话：群聊为群本身，单聊为对方
+    pub fn conversation(&self, my_uid: i32) -> MessageTarget {
+        match self.target {
+            MessageTarget::User(_) if self.from_uid != my_uid => {
+                MessageTarget::User(MessageTargetUser { uid: self.from_uid })
+            }
+            target => target,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, Hash, Eq, PartialEq)]
+pub enum MessageTarget {
+    User(MessageTargetUser),
+    Group(MessageTargetGroup),
+}
+
+impl From<MessageTarget> for String {
+    fn from(value: MessageTarget) -> Self {
+        match value {
+            MessageTarget::User(MessageTargetUser { uid }) => format!("MessageTargetUser:{uid}"),
+            MessageTarget::Group(MessageTargetGroup { gid }) => {
+                format!("MessageTargetGroup:{gid}")
+            }
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, Hash, Eq, PartialEq)]
+pub struct MessageTargetUser {
+    pub uid: i32,
+}
+
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, Hash, Eq, PartialEq)]
+pub struct MessageTargetGroup {
+    pub gid: i32,
+}
+
+/// 撤回的消息显示的内容
+pub const RECALLED_MESSAGE: &str = "[消息已撤回]";
+/// 编辑过的消息后面的标记
+pub const EDITED_MARK: &str = "（已编辑）";
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub enum MessageDetail {
+    Normal(MessageNormal),
+    Replay(MessageReplay),
+    /// 编辑之前发出的消息
+    Edit(MessageEdit),
+    /// 撤回之前发出的消息
+    Recall(MessageRecall),
+}
+
+impl MessageDetail {
+    pub fn get_content(&self) -> String {
+        match self {
+            MessageDetail::Normal(msg) => msg.content.content.clone(),
+            MessageDetail::Replay(msg) => msg.content.content.clone(),
+            MessageDetail::Edit(msg) => msg.content.content.clone(),
+            MessageDetail::Recall(_) => RECALLED_MESSAGE.to_string(),
+        }
+    }
+
+    /// 消息的内容，撤回时为 None
+    pub fn content(&self) -> Option<&MessageContent> {
+        match self {
+            MessageDetail::Normal(MessageNormal { content })
+            | MessageDetail::Replay(MessageReplay { content, .. })
+            | MessageDetail::Edit(MessageEdit { content, .. }) => Some(content),
+            MessageDetail::Recall(_) => None,
+        }
+    }
+
+    /// 编辑或撤回的消息id，新消息返回 None
+    pub fn amended(&self) -> Option<i64> {
+        match self {
+            MessageDetail::Edit(MessageEdit { mid, .. }) | MessageDetail::Recall(MessageRecall { mid }) => Some(*mid),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct MessageNormal {
+    pub content: MessageContent,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct MessageReplay {
+    pub mid: i64,
+    pub content: MessageContent,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct MessageEdit {
+    pub mid: i64,
+    pub content: MessageContent,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct MessageRecall {
+    pub mid: i64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct MessageContent {
+    /// Extended attributes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub properties: Option<HashMap<String, Value>>,
+    /// Content type, 普通文字消息为空
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content_type: Option<String>,
+    /// Content
+    #[serde(deserialize_with = "crate::sanitize::text")]
+    pub content: String,
+}
+
+impl MessageContent {
+    /// 文字消息
+    pub fn text(content: &str) -> Self {
+        Self { properties: None, content_type: None, content: content.to_string() }
+    }
+}
+
+/// 更新已读位置
+#[derive(Serialize)]
+pub enum UpdateReadIndex {
+    User { target_uid: i32, mid: i64 },
+    Group { target_gid: i32, mid: i64 },
+}
+
+/// 单聊和群聊的历史消息
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct HistoryMsg {
+    /// 消息id
+    pub mid: i64,
+    /// 消息内容
+    #[serde(deserialize_with = "crate::sanitize::text")]
+    pub msg: String,
+    /// 消息发送时间
+    #[serde(with = "datetime_format")]
+    pub time: DateTime<Local>,
+    /// 消息发送者id
+    pub from_uid: i32,
+    /// 是否编辑过
+    #[serde(default)]
+    pub edited: bool,
+    /// 是否已撤回
+    #[serde(default)]
+    pub recalled: bool,
+    /// 表情回应，按第一次回应的顺序
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub reactions: Vec<Reaction>,
+    /// 附件消息的文件信息
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub attachment: Option<Attachment>,
+    /// 端到端加密的消息，解密后 `msg` 为消息原文
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub encrypted: Option<Envelope>,
+}
+
+impl HistoryMsg {
+    /// 显示的内容：撤回的消息显示 `RECALLED_MESSAGE`，编辑过的消息后面加上标记
+    pub fn display(&self) -> String {
+        if self.recalled {
+            RECALLED_MESSAGE.to_string()
+        } else if self.edited {
+            format!("{}{EDITED_MARK}", self.msg)
+        } else {
+            self.msg.clone()
+        }
+    }
+}
+
+impl From<&ChatMessage> for HistoryMsg {
+    fn from(message: &ChatMessage) -> Self {
+        Self {
+            mid: message.mid,
+            msg: message.payload.detail.get_content(),
+            time: message.payload.created_at,
+            from_uid: message.payload.from_uid,
+            edited: false,
+            recalled: false,
+            reactions: vec![],
+            attachment: message.payload.detail.content().and_then(Attachment::from_content),
+            encrypted: message.payload.detail.content().and_then(Envelope::from_content),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Message;
+    use serde_json::json;
+
+    #[test]
+    fn test_from_sse() {
+        let typing = "event: message\ndata: {\"Typing\":{\"from_uid\":10,\"target\":{\"User\":{\"uid\":11}}}}\n";
+        assert!(matches!(Message::from_sse(typing), Some(Ok(Message::Typing(message))) if message.from_uid == 10));
+        assert!(Message::from_sse("data: {\"Presence\":{\"uid\":1}}").is_none());
+        assert!(matches!(Message::from_sse("data: {\"Typing\":{}}"), Some(Err(_))));
+        assert!(Message::from_sse("event: message\n").is_none());
+        let read = "data: {\"ReadIndex\":{\"uid\":10,\"target\":{\"User\":{\"uid\":11}},\"mid\":98}}";
+        assert!(matches!(Message::from_sse(read), Some(Ok(Message::ReadIndex(message))) if message.mid == 98));
+        let reaction = "data: {\"Reaction\":{\"from_uid\":10,\"target\":{\"User\":{\"uid\":11}},\"mid\":98,\"emoji\":\"👍\"}}";
+        assert!(matches!(Message::from_sse(reaction), Some(Ok(Message::Reaction(message))) if !message.removed));
+    }
+
+    #[test]
+    fn test_get_friend_history() {
+        let history = json!({"ChatMessage":{"mid":98,"payload":{"from_uid":10,"created_at":"2024-09-12T23:15:05.264972+08:00","target":{"User":{"uid":11}},"detail":{"Normal":{"content":{"content":"hello world!!!!!"}}}}}});
+        let result = serde_json::from_slice::<super::Message>(history.to_string().as_bytes());
+        match result {
+            Ok(msg) => {
+                println!("{:?}", msg)
+            }
+            Err(err) => {
+                println!("{}", err)
+            }
+        }
+    }
+}
@@ -0,0 +1,245 @@
+//! 其他会话的新消息通知：响铃、桌面通知控制序列、tmux/screen 状态栏和自定义通知命令
+//!
+//! 是否通知由免打扰、免打扰时段和“只通知@我”决定，通知方式见配置中的 `[notify]`。
+use crate::config::{data_dir, settings, Notify};
+use crate::sanitize::sanitize_line;
+use crate::text::{single_line, truncate};
+use chrono::NaiveTime;
+use serde::Deserialize;
+use std::fs;
+use std::io::{stdout, Write};
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+/// 通知正文的最大显示宽度
+const BODY_WIDTH: usize = 80;
+
+/// 桌面通知使用的控制序列，终端不支持时会被忽略
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Desktop {
+    Off,
+    /// iTerm2、Windows Terminal、WezTerm 等
+    Osc9,
+    /// rxvt、foot、VTE 系终端等
+    Osc777,
+}
+
+/// 免打扰时段，如 `22:00-08:00`，结束时间早于开始时间时跨过午夜
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct QuietHours {
+    start: NaiveTime,
+    end: NaiveTime,
+}
+
+impl TryFrom<String> for QuietHours {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        let error = || format!("免打扰时段 {value} 格式不正确，应为 22:00-08:00");
+        let (start, end) = value.split_once('-').ok_or_else(error)?;
+        let parse = |time: &str| NaiveTime::parse_from_str(time.trim(), "%H:%M").map_err(|_| error());
+        Ok(Self { start: parse(start)?, end: parse(end)? })
+    }
+}
+
+impl QuietHours {
+    pub fn contains(&self, time: NaiveTime) -> bool {
+        if self.start <= self.end {
+            self.start <= time && time < self.end
+        } else {
+            time >= self.start || time < self.end
+        }
+    }
+}
+
+/// 是否通知：免打扰时段内都不通知，只通知@我或会话免打扰时只在有人@我时通知
+pub fn should_notify(config: &Notify, muted: bool, mentioned: bool, now: NaiveTime) -> bool {
+    if config.quiet_hours.is_some_and(|quiet| quiet.contains(now)) {
+        return false;
+    }
+    mentioned || !(muted || config.only_mentions)
+}
+
+/// 一条通知，`title` 为会话名称
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Notification {
+    pub title: String,
+    pub body: String,
+    pub mentioned: bool,
+}
+
+impl Notification {
+    pub fn new(conversation: &str, sender: &str, content: &str, mentioned: bool) -> Self {
+        Self {
+            title: sanitize_line(conversation),
+            body: truncate(&single_line(&sanitize_line(&format!("{sender}: {content}"))), BODY_WIDTH),
+            mentioned,
+        }
+    }
+
+    /// 桌面通知的控制序列，OSC 777 的标题中不能有分号
+    pub fn sequence(&self, desktop: Desktop) -> Option<String> {
+        match desktop {
+            Desktop::Off => None,
+            Desktop::Osc9 => Some(format!("\x1b]9;{}: {}\x07", self.title, self.body)),
+            Desktop::Osc777 => Some(format!("\x1b]777;notify;{};{}\x07", self.title.replace(';', ","), self.body)),
+        }
+    }
+}
+
+/// 终端复用器，控制序列需要包装后才能传给外层终端
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Multiplexer {
+    None,
+    Tmux,
+    Screen,
+}
+
+impl Multiplexer {
+    pub fn detect() -> Self {
+        if std::env::var_os("TMUX").is_some() {
+            Self::Tmux
+        } else if std::env::var_os("STY").is_some() {
+            Self::Screen
+        } else {
+            Self::None
+        }
+    }
+
+    /// tmux 需要开启 `allow-passthrough`
+    pub fn passthrough(&self, sequence: &str) -> String {
+        match self {
+            Self::None => sequence.to_string(),
+            Self::Tmux => format!("\x1bPtmux;{}\x1b\\", sequence.replace('\x1b', "\x1b\x1b")),
+            Self::Screen => format!("\x1bP{sequence}\x1b\\"),
+        }
+    }
+}
+
+/// 状态栏显示的文字，没有未读消息时为空，如 `💬 3 @1`
+pub fn status_text(unread: u32, mentions: usize) -> String {
+    match (unread, mentions) {
+        (0, 0) => String::new(),
+        (unread, 0) => format!("💬 {unread}"),
+        (unread, mentions) => format!("💬 {unread} @{mentions}"),
+    }
+}
+
+/// 状态文件：$XDG_DATA_HOME/chat-cli/status，tmux 的 `status-right` 或 screen 的 `backtick` 读取后显示
+pub fn status_path() -> Option<PathBuf> {
+    data_dir().map(|dir| dir.join("status"))
+}
+
+/// 发出通知并维护状态文件，退出时清空状态文件
+#[derive(Debug, Default)]
+pub struct Notifier {
+    /// 最后一次写入状态文件的文字
+    status: Option<String>,
+}
+
+impl Notifier {
+    /// 按配置响铃、发出桌面通知并执行通知命令，通知命令失败时忽略
+    pub fn notify(&self, notification: &Notification) {
+        let mut output = String::new();
+        if settings().ui.bell {
+            output.push('\x07');
+        }
+        if let Some(sequence) = notification.sequence(settings().notify.desktop) {
+            output.push_str(&Multiplexer::detect().passthrough(&sequence));
+        }
+        if !output.is_empty() {
+            let mut stdout = stdout();
+            let _ = stdout.write_all(output.as_bytes()).and_then(|_| stdout.flush());
+        }
+        if let Some(command) = &settings().notify.command {
+            run_command(command, notification);
+        }
+    }
+
+    /// 未读数变化时更新状态文件
+    pub fn update_status(&mut self, unread: u32, mentions: usize) {
+        if !settings().notify.status_line {
+            return;
+        }
+        let text = status_text(unread, mentions);
+        if self.status.as_ref() == Some(&text) {
+            return;
+        }
+        if let Some(path) = status_path() {
+            if path.parent().is_some_and(|dir| fs::create_dir_all(dir).is_ok()) && fs::write(&path, &text).is_ok() {
+                self.status = Some(text);
+            }
+        }
+    }
+}
+
+impl Drop for Notifier {
+    fn drop(&mut self) {
+        if self.status.as_ref().is_some_and(|status| !status.is_empty()) {
+            self.update_status(0, 0);
+        }
+    }
+}
+
+/// 通过 shell 执行通知命令，标题和正文放在环境变量中，不拼接到命令里
+fn run_command(command: &str, notification: &Notification) {
+    let child = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .env("CHAT_CLI_TITLE", &notification.title)
+        .env("CHAT_CLI_BODY", &notification.body)
+        .env("CHAT_CLI_MENTIONED", if notification.mentioned { "1" } else { "0" })
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn();
+    // 在后台等待命令结束，避免留下僵尸进程
+    if let Ok(mut child) = child {
+        std::thread::spawn(move || child.wait());
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{should_notify, status_text, Desktop, Multiplexer, Notification, QuietHours};
+    use crate::config::Notify;
+    use chrono::NaiveTime;
+
+    fn time(text: &str) -> NaiveTime {
+        NaiveTime::parse_from_str(text, "%H:%M").unwrap()
+    }
+
+    #[test]
+    fn test_should_notify() {
+        let quiet = QuietHours::try_from("22:00-08:00".to_string()).unwrap();
+        assert!(quiet.contains(time("23:30")) && quiet.contains(time("07:59")));
+        assert!(!quiet.contains(time("08:00")) && !quiet.contains(time("12:00")));
+        assert!(QuietHours::try_from("12:00-13:00".to_string()).unwrap().contains(time("12:30")));
+        assert!(QuietHours::try_from("22:00".to_string()).is_err());
+
+        let mut config = Notify { desktop: Desktop::Off, only_mentions: false, quiet_hours: None, status_line: false, command: None };
+        assert!(should_notify(&config, false, false, time("12:00")));
+        // 免打扰的会话只在有人@我时通知
+        assert!(!should_notify(&config, true, false, time("12:00")));
+        assert!(should_notify(&config, true, true, time("12:00")));
+        config.only_mentions = true;
+        assert!(!should_notify(&config, false, false, time("12:00")));
+        assert!(should_notify(&config, false, true, time("12:00")));
+        config.quiet_hours = Some(quiet);
+        assert!(!should_notify(&config, false, true, time("23:00")));
+    }
+
+    #[test]
+    fn test_sequence() {
+        let notification = Notification::new("a;b", "bob", "你好\n\x1b]0;x\x07世界", false);
+        assert_eq!(notification.body, "bob: 你好 世界");
+        assert_eq!(notification.sequence(Desktop::Osc9).unwrap(), "\x1b]9;a;b: bob: 你好 世界\x07");
+        assert_eq!(notification.sequence(Desktop::Osc777).unwrap(), "\x1b]777;notify;a,b;bob: 你好 世界\x07");
+        assert_eq!(notification.sequence(Desktop::Off), None);
+        assert_eq!(Multiplexer::Tmux.passthrough("\x1b]9;hi\x07"), "\x1bPtmux;\x1b\x1b]9;hi\x07\x1b\\");
+        assert_eq!(status_text(0, 0), "");
+        assert_eq!(status_text(3, 1), "💬 3 @1");
+    }
+}
@@ -0,0 +1,256 @@
+//! 发送失败的消息：保存在 ~/.local/share/chat-cli/outbox/ 下，按指数退避自动重发
+//!
+//! 每条消息带有客户端生成的id，消息流返回这条消息（请求超时但服务端已收到）时从队列中移除，不会重复发送。
+//! 加密消息在加入队列前加密，文件中只保存密文。
+use crate::config::{account_path, write_private};
+use crate::e2e::{Envelope, KeyStore, ENCRYPTED_MESSAGE};
+use crate::message::{ChatMessagePayload, MessageTarget, MessageTargetUser};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+
+/// 第一次重发前等待的毫秒数，之后每次翻倍
+pub const RETRY_BASE: i64 = 2_000;
+/// 重发间隔的上限
+pub const RETRY_MAX: i64 = 5 * 60_000;
+/// 超过这个次数后不再自动重发，等待手动重试
+pub const MAX_ATTEMPTS: u32 = 6;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Queued {
+    pub client_id: String,
+    pub target: MessageTarget,
+    /// 消息原文，加密消息保存到文件时替换为占位文字
+    pub msg: String,
+    /// 加密后的消息，重发时发送同一份密文
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub encrypted: Option<Envelope>,
+    /// 加入队列的时间，毫秒时间戳
+    pub created_at: i64,
+    /// 回复的消息id
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reply: Option<i64>,
+    /// 已经失败的次数
+    pub attempts: u32,
+    /// 下次重发的时间，毫秒时间戳
+    pub next_retry: i64,
+}
+
+impl Queued {
+    /// 多次重发都失败，需要手动重试
+    pub fn failed(&self) -> bool {
+        self.attempts >= MAX_ATTEMPTS
+    }
+}
+
+/// 第 `attempts` 次失败后的等待时间
+pub fn backoff(attempts: u32) -> i64 {
+    RETRY_BASE.saturating_mul(1 << attempts.saturating_sub(1).min(20)).min(RETRY_MAX)
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Outbox {
+    #[serde(skip)]
+    path: Option<PathBuf>,
+    #[serde(default)]
+    queue: Vec<Queued>,
+    /// 上一个客户端id的序号，同一毫秒内发出多条消息时区分
+    #[serde(default)]
+    sequence: u32,
+}
+
+impl Outbox {
+    /// 读取账号未发出的消息，文件不存在时为空
+    pub fn load(server: &str, uid: i32) -> Result<Self, String> {
+        let path = account_path("outbox", server, uid);
+        let mut outbox = match path.as_ref().filter(|path| path.exists()) {
+            None => Self::default(),
+            Some(path) => {
+                let content = fs::read_to_string(path).map_err(|err| format!("读取{}失败: {err}", path.display()))?;
+                serde_json::from_str(&content).map_err(|err| format!("解析{}失败: {err}", path.display()))?
+            }
+        };
+        outbox.path = path;
+        Ok(outbox)
+    }
+
+    /// 用自己的密钥还原加密消息的原文，解密失败时显示占位文字
+    pub fn reveal(&mut self, keys: &KeyStore) {
+        for queued in &mut self.queue {
+            if let (Some(envelope), MessageTarget::User(MessageTargetUser { uid })) = (&queued.encrypted, queued.target) {
+                if let Ok(text) = keys.decrypt(uid, true, envelope) {
+                    queued.msg = text;
+                }
+            }
+        }
+    }
+
+    /// 队列为空时删除文件
+    pub fn save(&self) -> Result<(), String> {
+        let path = self.path.as_ref().ok_or("无法确定数据目录")?;
+        if self.queue.is_empty() {
+            return match fs::remove_file(path) {
+                Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(format!("删除{}失败: {err}", path.display())),
+                _ => Ok(()),
+            };
+        }
+        // 未发出的消息内容只有自己可以读，加密消息不写入原文
+        let queue = self
+            .queue
+            .iter()
+            .map(|queued| match queued.encrypted {
+                Some(_) => Queued { msg: ENCRYPTED_MESSAGE.to_string(), ..queued.clone() },
+                None => queued.clone(),
+            })
+            .collect();
+        let outbox = Self { path: None, queue, sequence: self.sequence };
+        let content = serde_json::to_string_pretty(&outbox).map_err(|err| err.to_string())?;
+        write_private(path, &content)
+    }
+
+    /// 发送前加入队列，返回加入的消息；发送成功后按客户端id调用 `sent` 移除
+    pub fn push(&mut self, target: MessageTarget, msg: &str, encrypted: Option<Envelope>, reply: Option<i64>, now: i64) -> Queued {
+        self.sequence = self.sequence.wrapping_add(1);
+        let queued = Queued {
+            client_id: format!("{now:x}-{:x}", self.sequence),
+            target,
+            msg: msg.to_string(),
+            encrypted,
+            created_at: now,
+            reply,
+            attempts: 0,
+            next_retry: now,
+        };
+        self.queue.push(queued.clone());
+        queued
+    }
+
+    pub fn sent(&mut self, client_id: &str) {
+        self.queue.retain(|queued| queued.client_id != client_id);
+    }
+
+    /// 发送失败，按失败次数推迟下次重发
+    pub fn attempt_failed(&mut self, client_id: &str, now: i64) {
+        if let Some(queued) = self.queue.iter_mut().find(|queued| queued.client_id == client_id) {
+            queued.attempts += 1;
+            queued.next_retry = now + backoff(queued.attempts);
+        }
+    }
+
+    /// 到了重发时间的消息，按发送顺序
+    pub fn due(&self, now: i64) -> Vec<Queued> {
+        self.queue.iter().filter(|queued| !queued.failed() && queued.next_retry <= now).cloned().collect()
+    }
+
+    /// 手动重试会话中的消息，返回重试的条数
+    pub fn retry(&mut self, target: MessageTarget, now: i64) -> usize {
+        let mut count = 0;
+        for queued in self.queue.iter_mut().filter(|queued| queued.target == target) {
+            queued.attempts = 0;
+            queued.next_retry = now;
+            count += 1;
+        }
+        count
+    }
+
+    /// 消息流返回了自己发出的消息，按客户端id从队列中移除对应的消息，返回是否移除了。
+    /// 不按内容匹配，避免移除内容相同的另一条消息；没有客户端id的加密消息按密文匹配，每次加密的密文都不同
+    pub fn echoed(&mut self, payload: &ChatMessagePayload) -> bool {
+        let position = match &payload.client_id {
+            Some(client_id) => self.queue.iter().position(|queued| &queued.client_id == client_id),
+            None => payload.detail.content().and_then(Envelope::from_content).and_then(|envelope| {
+                self.queue
+                    .iter()
+                    .position(|queued| queued.encrypted.as_ref().is_some_and(|queued| queued.ciphertext == envelope.ciphertext))
+            }),
+        };
+        position.map(|index| self.queue.remove(index)).is_some()
+    }
+
+    /// 会话中还没有发出的消息
+    pub fn queued(&self, target: MessageTarget) -> impl Iterator<Item = &Queued> {
+        self.queue.iter().filter(move |queued| queued.target == target)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{backoff, Outbox, MAX_ATTEMPTS, RETRY_BASE, RETRY_MAX};
+    use crate::e2e::KeyStore;
+    use crate::message::{ChatMessagePayload, MessageContent, MessageDetail, MessageNormal, MessageTarget, MessageTargetUser};
+    use chrono::Local;
+
+    #[test]
+    fn test_outbox() {
+        assert_eq!(backoff(1), RETRY_BASE);
+        assert_eq!(backoff(3), RETRY_BASE * 4);
+        assert_eq!(backoff(30), RETRY_MAX);
+
+        let target = MessageTarget::User(MessageTargetUser { uid: 2 });
+        let mut outbox = Outbox::default();
+        let first = outbox.push(target, "hi", None, None, 0).client_id;
+        let second = outbox.push(target, "在吗", None, None, 0).client_id;
+        assert_ne!(first, second);
+        assert_eq!(outbox.due(0).len(), 2);
+        outbox.attempt_failed(&first, 0);
+        assert_eq!(outbox.due(RETRY_BASE - 1).len(), 1);
+        assert_eq!(outbox.due(RETRY_BASE).len(), 2);
+        for _ in 1..MAX_ATTEMPTS {
+            outbox.attempt_failed(&first, 0);
+        }
+        assert!(outbox.queued(target).next().unwrap().failed());
+        assert_eq!(outbox.due(i64::MAX).len(), 1);
+        assert_eq!(outbox.retry(target, 10), 2);
+        assert_eq!(outbox.due(10).len(), 2);
+
+        let payload = ChatMessagePayload {
+            from_uid: 1,
+            created_at: Local::now(),
+            target,
+            detail: MessageDetail::Normal(MessageNormal { content: MessageContent::text("在吗") }),
+            client_id: None,
+        };
+        // 没有客户端id时不按内容匹配
+        assert!(!outbox.echoed(&payload));
+        let payload = ChatMessagePayload { client_id: Some(second.clone()), ..payload };
+        assert!(outbox.echoed(&payload));
+        assert!(!outbox.echoed(&payload));
+        outbox.sent(&first);
+        assert_eq!(outbox.queued(target).count(), 0);
+    }
+
+    #[test]
+    fn test_encrypted() {
+        let mut alice = KeyStore::generate();
+        let bob = KeyStore::generate();
+        alice.check(2, &bob.public_key().unwrap());
+        let target = MessageTarget::User(MessageTargetUser { uid: 2 });
+        let dir = std::env::temp_dir().join(format!("chat-cli-outbox-{}", std::process::id()));
+        let path = dir.join("outbox.json");
+        let mut outbox = Outbox { path: Some(path.clone()), ..Outbox::default() };
+        let envelope = alice.encrypt(2, "密码是1234").unwrap();
+        outbox.push(target, "密码是1234", Some(envelope.clone()), None, 0);
+        outbox.push(target, "明文", None, None, 0);
+        outbox.save().unwrap();
+        // 文件中只有密文
+        let content = std::fs::read_to_string(&path).unwrap();
+        assert!(!content.contains("密码是1234") && content.contains(&envelope.ciphertext));
+        let mut loaded: Outbox = serde_json::from_str(&content).unwrap();
+        loaded.reveal(&alice);
+        assert_eq!(loaded.queued(target).map(|queued| queued.msg.as_str()).collect::<Vec<_>>(), ["密码是1234", "明文"]);
+
+        // 没有带回客户端id的加密消息按密文匹配
+        let payload = ChatMessagePayload {
+            from_uid: 1,
+            created_at: Local::now(),
+            target,
+            detail: MessageDetail::Normal(MessageNormal { content: alice.encrypt(2, "密码是1234").unwrap().to_content() }),
+            client_id: None,
+        };
+        assert!(!outbox.echoed(&payload));
+        let payload = ChatMessagePayload { detail: MessageDetail::Normal(MessageNormal { content: envelope.to_content() }), ..payload };
+        assert!(outbox.echoed(&payload));
+        assert_eq!(outbox.queued(target).count(), 1);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
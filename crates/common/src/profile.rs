@@ -0,0 +1,138 @@
+//! 服务端与账号配置，保存在 ~/.config/chat-cli/profiles.json
+//!
+//! 每个 profile 对应一个服务端地址和一个账号，登陆后的 token 也保存在这里，
+//! 切换 profile 时可以直接恢复会话。
+use crate::config::{config_dir, write_private};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Profile {
+    pub name: String,
+    /// 服务端地址
+    pub server: String,
+    /// 登陆用户名
+    #[serde(default)]
+    pub username: Option<String>,
+    /// 最近一次登陆或刷新得到的 token
+    #[serde(default)]
+    pub token: Option<String>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Profiles {
+    /// `profile use` 选中的 profile
+    #[serde(default)]
+    pub current: Option<String>,
+    #[serde(default)]
+    pub profiles: Vec<Profile>,
+}
+
+impl Profiles {
+    pub fn path() -> Option<PathBuf> {
+        config_dir().map(|dir| dir.join("profiles.json"))
+    }
+
+    /// 读取 profile 列表，文件不存在时为空
+    pub fn load() -> Result<Self, String> {
+        let Some(path) = Self::path().filter(|path| path.exists()) else {
+            return Ok(Self::default());
+        };
+        let content = fs::read_to_string(&path).map_err(|err| format!("读取{}失败: {err}", path.display()))?;
+        serde_json::from_str(&content).map_err(|err| format!("解析{}失败: {err}", path.display()))
+    }
+
+    /// 保存 profile 列表，文件中包含 token，仅当前用户可读写
+    pub fn save(&self) -> Result<(), String> {
+        let path = Self::path().ok_or("无法确定配置目录")?;
+        let content = serde_json::to_string_pretty(self).map_err(|err| err.to_string())?;
+        write_private(&path, &content)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Profile> {
+        self.profiles.iter().find(|profile| profile.name == name)
+    }
+
+    pub fn current(&self) -> Option<&Profile> {
+        self.current.as_deref().and_then(|name| self.get(name))
+    }
+
+    pub fn add(&mut self, profile: Profile) -> Result<(), String> {
+        if self.get(&profile.name).is_some() {
+            return Err(format!("profile {} 已存在", profile.name));
+        }
+        // 第一个 profile 默认选中
+        if self.profiles.is_empty() {
+            self.current = Some(profile.name.clone());
+        }
+        self.profiles.push(profile);
+        Ok(())
+    }
+
+    pub fn remove(&mut self, name: &str) -> Result<Profile, String> {
+        let index = self
+            .profiles
+            .iter()
+            .position(|profile| profile.name == name)
+            .ok_or_else(|| format!("profile {name} 不存在"))?;
+        if self.current.as_deref() == Some(name) {
+            self.current = None;
+        }
+        Ok(self.profiles.remove(index))
+    }
+
+    pub fn use_profile(&mut self, name: &str) -> Result<(), String> {
+        if self.get(name).is_none() {
+            return Err(format!("profile {name} 不存在"));
+        }
+        self.current = Some(name.to_string());
+        Ok(())
+    }
+
+    /// 登陆、刷新 token 或退出登陆后保存会话，重新读取文件以免覆盖其他进程的修改
+    pub fn save_session(name: &str, username: Option<String>, token: Option<String>) -> Result<(), String> {
+        let mut profiles = Self::load()?;
+        let profile = profiles
+            .profiles
+            .iter_mut()
+            .find(|profile| profile.name == name)
+            .ok_or_else(|| format!("profile {name} 不存在"))?;
+        if username.is_some() {
+            profile.username = username;
+        }
+        profile.token = token;
+        profiles.save()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Profile, Profiles};
+
+    fn profile(name: &str) -> Profile {
+        Profile {
+            name: name.to_string(),
+            server: format!("https://{name}.example.com"),
+            username: None,
+            token: None,
+        }
+    }
+
+    #[test]
+    fn test_manage_profiles() {
+        let mut profiles = Profiles::default();
+        profiles.add(profile("staging")).unwrap();
+        profiles.add(profile("prod")).unwrap();
+        assert!(profiles.add(profile("prod")).is_err());
+        assert_eq!(profiles.current().unwrap().name, "staging");
+
+        profiles.use_profile("prod").unwrap();
+        assert!(profiles.use_profile("dev").is_err());
+        assert_eq!(profiles.current().unwrap().server, "https://prod.example.com");
+
+        profiles.remove("prod").unwrap();
+        assert!(profiles.current().is_none());
+        assert!(profiles.remove("prod").is_err());
+    }
+}
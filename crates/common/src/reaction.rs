@@ -0,0 +1,94 @@
+//! 消息的表情回应：按表情汇总回应的人，显示为 `👍 3  🎉 1`
+use crate::emoji;
+use crate::message::{HistoryMsg, ReactionMessage};
+use serde::{Deserialize, Serialize};
+
+/// 表情选择器中的表情
+pub const PICKER: [&str; 8] = ["👍", "❤️", "😂", "🎉", "😮", "😢", "🙏", "👀"];
+
+/// 一个表情和回应的人
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Reaction {
+    #[serde(deserialize_with = "crate::sanitize::line")]
+    pub emoji: String,
+    pub uids: Vec<i32>,
+}
+
+/// 添加或取消回应，返回是否有变化
+pub fn apply(reactions: &mut Vec<Reaction>, uid: i32, emoji: &str, removed: bool) -> bool {
+    let index = reactions.iter().position(|reaction| reaction.emoji == emoji);
+    match (index, removed) {
+        (Some(index), true) => {
+            let reaction = &mut reactions[index];
+            let count = reaction.uids.len();
+            reaction.uids.retain(|id| *id != uid);
+            let changed = reaction.uids.len() != count;
+            if reaction.uids.is_empty() {
+                reactions.remove(index);
+            }
+            changed
+        }
+        (None, true) => false,
+        (Some(index), false) if reactions[index].uids.contains(&uid) => false,
+        (Some(index), false) => {
+            reactions[index].uids.push(uid);
+            true
+        }
+        (None, false) => {
+            reactions.push(Reaction { emoji: emoji.to_string(), uids: vec![uid] });
+            true
+        }
+    }
+}
+
+/// 在聊天记录中找到回应的消息并更新，返回更新后的消息
+pub fn react<'a>(history: &'a mut [HistoryMsg], message: &ReactionMessage) -> Option<&'a HistoryMsg> {
+    let msg = history.iter_mut().find(|msg| msg.mid == message.mid)?;
+    apply(&mut msg.reactions, message.from_uid, &message.emoji, message.removed).then_some(&*msg)
+}
+
+/// 表情选择器中的序号（从 1 开始）或 `:短代码:` 转换为表情，其他输入原样返回
+pub fn resolve(input: &str) -> String {
+    match input.parse::<usize>() {
+        Ok(index) if (1..=PICKER.len()).contains(&index) => PICKER[index - 1].to_string(),
+        _ => emoji::expand(input),
+    }
+}
+
+/// 是否已经用这个表情回应过，再次选择时取消回应
+pub fn reacted(reactions: &[Reaction], uid: i32, emoji: &str) -> bool {
+    reactions.iter().any(|reaction| reaction.emoji == emoji && reaction.uids.contains(&uid))
+}
+
+/// 汇总显示，没有回应时为空
+pub fn summary(reactions: &[Reaction]) -> String {
+    reactions
+        .iter()
+        .map(|reaction| format!("{} {}", reaction.emoji, reaction.uids.len()))
+        .collect::<Vec<_>>()
+        .join("  ")
+}
+
+#[cfg(test)]
+mod test {
+    use super::{apply, reacted, resolve, summary};
+
+    #[test]
+    fn test_reactions() {
+        let mut reactions = vec![];
+        assert!(apply(&mut reactions, 1, "👍", false));
+        assert!(apply(&mut reactions, 2, "🎉", false));
+        assert!(apply(&mut reactions, 3, "👍", false));
+        assert!(!apply(&mut reactions, 3, "👍", false));
+        assert_eq!(summary(&reactions), "👍 2  🎉 1");
+        assert!(reacted(&reactions, 3, "👍"));
+        assert!(apply(&mut reactions, 2, "🎉", true));
+        assert!(!apply(&mut reactions, 2, "🎉", true));
+        assert_eq!(summary(&reactions), "👍 2");
+        assert!(!reacted(&reactions, 2, "👍"));
+        assert_eq!(resolve("1"), "👍");
+        assert_eq!(resolve("9"), "9");
+        assert_eq!(resolve("🚀"), "🚀");
+        assert_eq!(resolve(":tada:"), "🎉");
+    }
+}
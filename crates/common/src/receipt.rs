@@ -0,0 +1,133 @@
+//! 发出消息的状态：发送中 → 已发送（服务端返回成功）→ 已送达（消息流返回了消息id）→ 已读（对方的已读位置超过该消息）
+use std::fmt::{Display, Formatter};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Delivery {
+    Pending,
+    Sent,
+    Delivered,
+    Read,
+}
+
+impl Display for Delivery {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Delivery::Pending => "○",
+                Delivery::Sent => "✓",
+                Delivery::Delivered => "✓✓",
+                Delivery::Read => "已读",
+            }
+        )
+    }
+}
+
+/// 还没有送达的消息
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Outgoing {
+    pub id: u64,
+    /// 发送时的客户端id，消息流返回时按它对应
+    pub client_id: String,
+    pub content: String,
+    pub state: Delivery,
+}
+
+/// 一个会话中自己发出的消息的状态
+#[derive(Debug, Default)]
+pub struct Receipts {
+    outgoing: Vec<Outgoing>,
+    next_id: u64,
+    /// 对方的已读位置，群聊中为其他成员中最靠后的已读位置
+    peer_read: Option<i64>,
+}
+
+impl Receipts {
+    /// 开始发送，返回本地id
+    pub fn send(&mut self, content: &str, client_id: &str) -> u64 {
+        self.next_id += 1;
+        self.outgoing.push(Outgoing {
+            id: self.next_id,
+            client_id: client_id.to_string(),
+            content: content.to_string(),
+            state: Delivery::Pending,
+        });
+        self.next_id
+    }
+
+    /// 服务端返回成功
+    pub fn sent(&mut self, id: u64) {
+        if let Some(outgoing) = self.outgoing.iter_mut().find(|outgoing| outgoing.id == id) {
+            outgoing.state = Delivery::Sent;
+        }
+    }
+
+    /// 发送失败，不再跟踪
+    pub fn failed(&mut self, id: u64) {
+        self.outgoing.retain(|outgoing| outgoing.id != id);
+    }
+
+    /// 消息流返回了自己发出的消息，之后按消息id判断状态；返回是否是正在跟踪的消息。
+    /// 按客户端id对应，内容相同的消息不会混淆，服务端没有返回客户端id时不对应
+    pub fn delivered(&mut self, client_id: Option<&str>) -> bool {
+        let index = client_id.and_then(|client_id| self.outgoing.iter().position(|outgoing| outgoing.client_id == client_id));
+        index.map(|index| self.outgoing.remove(index)).is_some()
+    }
+
+    /// 对方的已读位置更新，返回是否前进了
+    pub fn read(&mut self, mid: i64) -> bool {
+        if self.peer_read.is_some_and(|read| read >= mid) {
+            return false;
+        }
+        self.peer_read = Some(mid);
+        true
+    }
+
+    /// 已送达的消息的状态
+    pub fn state(&self, mid: i64) -> Delivery {
+        if self.peer_read.is_some_and(|read| read >= mid) {
+            Delivery::Read
+        } else {
+            Delivery::Delivered
+        }
+    }
+
+    /// 还没有送达的消息，按发送顺序
+    pub fn outgoing(&self) -> &[Outgoing] {
+        &self.outgoing
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Delivery, Receipts};
+
+    #[test]
+    fn test_receipts() {
+        let mut receipts = Receipts::default();
+        let first = receipts.send("hi", "a-1");
+        let second = receipts.send("在吗", "a-2");
+        let third = receipts.send("hi", "a-3");
+        receipts.sent(first);
+        assert_eq!(
+            receipts.outgoing().iter().map(|outgoing| outgoing.state).collect::<Vec<_>>(),
+            vec![Delivery::Sent, Delivery::Pending, Delivery::Pending]
+        );
+        receipts.failed(second);
+        // 内容相同的两条消息按客户端id区分
+        assert!(receipts.delivered(Some("a-3")));
+        assert!(!receipts.delivered(Some("a-3")));
+        assert!(!receipts.delivered(None));
+        assert_eq!(receipts.outgoing().iter().map(|outgoing| outgoing.id).collect::<Vec<_>>(), vec![first]);
+        receipts.failed(third);
+        assert!(receipts.delivered(Some("a-1")));
+        assert!(receipts.outgoing().is_empty());
+        assert_eq!(receipts.state(10), Delivery::Delivered);
+        assert!(receipts.read(10));
+        assert!(!receipts.read(9));
+        assert_eq!(receipts.state(10), Delivery::Read);
+        assert_eq!(receipts.state(11), Delivery::Delivered);
+        assert_eq!(Delivery::Read.to_string(), "已读");
+    }
+}
@@ -0,0 +1,134 @@
+//! 服务端返回的文字在显示前统一清理：消息、用户名、群名、好友申请理由等
+//!
+//! 对方可以在这些文字中放入终端控制序列清屏、修改窗口标题或伪造消息，也可以用双向文字控制符颠倒显示顺序。
+//! 控制序列和双向控制符直接去掉，其他控制字符替换为可见的符号（如 `␇`）。
+//! 反序列化时通过 `#[serde(deserialize_with = "...")]` 清理，之后的代码拿到的都是可以直接显示的文字。
+use serde::{Deserialize, Deserializer};
+
+/// 清理多行文字，保留换行和制表符
+pub fn sanitize(text: &str) -> String {
+    let mut sanitized = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\n' | '\t' => sanitized.push(c),
+            '\r' if chars.peek() == Some(&'\n') => {}
+            // CSI：ESC [ 参数 结束字符
+            '\u{1b}' if chars.peek() == Some(&'[') => {
+                chars.next();
+                skip_csi(&mut chars);
+            }
+            '\u{9b}' => skip_csi(&mut chars),
+            // OSC、DCS 等字符串序列，以 BEL 或 ESC \ 结束
+            '\u{1b}' if matches!(chars.peek(), Some(']' | 'P' | 'X' | '^' | '_')) => {
+                chars.next();
+                skip_string(&mut chars);
+            }
+            '\u{9d}' | '\u{90}' | '\u{98}' | '\u{9e}' | '\u{9f}' => skip_string(&mut chars),
+            // 其他序列：中间字符加一个结束字符，如 ESC 7、ESC ( B、ESC # 8、ESC % G
+            '\u{1b}' => skip_escape(&mut chars),
+            c if is_bidi_control(c) => {}
+            c if c.is_control() => sanitized.push(visible(c)),
+            c => sanitized.push(c),
+        }
+    }
+    sanitized
+}
+
+/// 清理单行文字，如用户名、群名，换行和制表符也替换为空格
+pub fn sanitize_line(text: &str) -> String {
+    sanitize(text).replace(['\n', '\t'], " ")
+}
+
+/// 反序列化时清理多行文字
+pub fn text<'de, D>(deserializer: D) -> Result<String, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    String::deserialize(deserializer).map(|text| sanitize(&text))
+}
+
+/// 反序列化时清理单行文字
+pub fn line<'de, D>(deserializer: D) -> Result<String, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    String::deserialize(deserializer).map(|text| sanitize_line(&text))
+}
+
+/// 反序列化时清理可以为空的多行文字
+pub fn optional_text<'de, D>(deserializer: D) -> Result<Option<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Option::<String>::deserialize(deserializer).map(|text| text.map(|text| sanitize(&text)))
+}
+
+/// 改变文字显示方向的控制符，可以让 `exe.txt` 显示成 `txt.exe`
+fn is_bidi_control(c: char) -> bool {
+    matches!(c, '\u{061c}' | '\u{200e}' | '\u{200f}' | '\u{202a}'..='\u{202e}' | '\u{2066}'..='\u{2069}')
+}
+
+/// 控制字符对应的可见符号，C0 控制字符使用 Unicode 的控制图形符号，其他的显示为 `�`
+fn visible(c: char) -> char {
+    match c {
+        '\u{0}'..='\u{1f}' => char::from_u32(0x2400 + c as u32).unwrap_or('\u{fffd}'),
+        '\u{7f}' => '\u{2421}',
+        _ => '\u{fffd}',
+    }
+}
+
+fn skip_csi(chars: &mut impl Iterator<Item = char>) {
+    for c in chars.by_ref() {
+        if ('\u{40}'..='\u{7e}').contains(&c) {
+            break;
+        }
+    }
+}
+
+fn skip_escape(chars: &mut std::iter::Peekable<impl Iterator<Item = char>>) {
+    while chars.next_if(|c| ('\u{20}'..='\u{2f}').contains(c)).is_some() {}
+    chars.next_if(|c| ('\u{30}'..='\u{7e}').contains(c));
+}
+
+fn skip_string(chars: &mut std::iter::Peekable<impl Iterator<Item = char>>) {
+    while let Some(c) = chars.next() {
+        match c {
+            '\u{7}' | '\u{9c}' => break,
+            '\u{1b}' => {
+                if chars.peek() == Some(&'\\') {
+                    chars.next();
+                }
+                break;
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{sanitize, sanitize_line};
+    use crate::message::HistoryMsg;
+
+    #[test]
+    fn test_sanitize() {
+        assert_eq!(sanitize("\u{1b}[2J\u{1b}[31mred\u{1b}[0m"), "red");
+        assert_eq!(sanitize("\u{1b}]0;title\u{7}hi"), "hi");
+        assert_eq!(sanitize("\u{1b}]8;;http://a\u{1b}\\link\u{1b}]8;;\u{1b}\\"), "link");
+        assert_eq!(sanitize("a\r\nb\tc\u{8}\u{1b}7"), "a\nb\tc␈");
+        assert_eq!(sanitize("你好\u{9b}1mworld\u{7}\r"), "你好world␇␍");
+        assert_eq!(sanitize("invoice\u{202e}txt.exe"), "invoicetxt.exe");
+        assert_eq!(sanitize("a\u{1b}(Bb\u{1b}#8c\u{1b}%Gd\u{1b} F"), "abcd");
+        // 不完整的序列只去掉 ESC 和中间字符
+        assert_eq!(sanitize("a\u{1b}(你好\u{1b}"), "a你好");
+        assert_eq!(sanitize_line("bob\n[12:00:00] alice: hi"), "bob [12:00:00] alice: hi");
+    }
+
+    #[test]
+    fn test_deserialize() {
+        let msg: HistoryMsg =
+            serde_json::from_str(r#"{"mid":1,"msg":"\u001b[2Jhi","time":"2024-01-01 00:00:00","from_uid":1}"#).unwrap();
+        assert_eq!(msg.msg, "hi");
+    }
+}
@@ -0,0 +1,95 @@
+//! 文本显示宽度相关工具
+use crate::sanitize::sanitize;
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+/// 省略号
+pub const ELLIPSIS: &str = "…";
+/// 多行消息后续行的前缀
+pub const CONTINUATION: &str = "    │ ";
+
+/// 文本在终端中的显示宽度，中文等宽字符占两列
+pub fn display_width(text: &str) -> usize {
+    text.width()
+}
+
+/// 按显示宽度截断文本，超出时以省略号结尾；按字形簇截断，不会拆开国旗、组合表情和组合字符
+pub fn truncate(text: &str, max_width: usize) -> String {
+    if text.width() <= max_width {
+        return text.to_string();
+    }
+    let Some(limit) = max_width.checked_sub(ELLIPSIS.width()) else {
+        return String::new();
+    };
+    let mut width = 0;
+    let mut truncated = String::new();
+    for grapheme in text.graphemes(true) {
+        let grapheme_width = grapheme.width();
+        if width + grapheme_width > limit {
+            break;
+        }
+        width += grapheme_width;
+        truncated.push_str(grapheme);
+    }
+    truncated.push_str(ELLIPSIS);
+    truncated
+}
+
+/// 文字在 `columns` 列宽的终端中占的行数，自动换行的部分也计算在内，控制序列不占宽度
+pub fn rows(text: &str, columns: usize) -> usize {
+    let columns = columns.max(1);
+    text.lines().map(|line| display_width(&sanitize(line)).div_ceil(columns).max(1)).sum::<usize>().max(1)
+}
+
+/// 多行消息除第一行外都加上前缀，只有真正的消息开头顶格显示，
+/// 对方不能在消息中换行后伪造一条带时间和发送人的消息
+pub fn indent_continuation(text: &str) -> String {
+    text.replace('\n', &format!("\n{CONTINUATION}"))
+}
+
+/// 将换行等空白合并为单个空格，用于单行预览
+pub fn single_line(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod test {
+    use super::{display_width, indent_continuation, rows, single_line, truncate};
+
+    #[test]
+    fn test_truncate() {
+        assert_eq!(truncate("hello", 5), "hello");
+        assert_eq!(truncate("hello world", 6), "hello…");
+        // 中文占两列，截断时不能把宽字符劈成两半
+        assert_eq!(truncate("你好世界", 5), "你好…");
+        assert_eq!(truncate("你好世界", 6), "你好…");
+        assert_eq!(display_width(&truncate("你好世界", 6)), 5);
+        assert_eq!(truncate("你好", 0), "");
+        // 组合表情、国旗和组合字符不会被拆开
+        assert_eq!(truncate("👨‍👩‍👧好的呀", 4), "👨‍👩‍👧…");
+        assert_eq!(truncate("🇨🇳🇺🇸ab", 4), "🇨🇳…");
+        assert_eq!(truncate("e\u{301}e\u{301}e\u{301}", 2), "e\u{301}…");
+    }
+
+    #[test]
+    fn test_indent_continuation() {
+        assert_eq!(indent_continuation("ok"), "ok");
+        let forged = indent_continuation("ok\n[2026-10-19 12:00:00] boss: wire the money");
+        assert_eq!(forged, "ok\n    │ [2026-10-19 12:00:00] boss: wire the money");
+        assert!(forged.lines().skip(1).all(|line| line.starts_with("    │ ")));
+    }
+
+    #[test]
+    fn test_rows() {
+        assert_eq!(rows("", 10), 1);
+        assert_eq!(rows("hello", 5), 1);
+        assert_eq!(rows("hello!", 5), 2);
+        // 样式不占宽度，中文占两列
+        assert_eq!(rows("\u{1b}[1mhi\u{1b}[0m\n你好世界", 4), 3);
+    }
+
+    #[test]
+    fn test_single_line() {
+        assert_eq!(single_line("a\nb\r\n  c"), "a b c");
+    }
+}
@@ -0,0 +1,91 @@
+//! 正在输入提示：发送端限制通知频率，接收端超时后自动清除
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// 两次输入通知的最小间隔
+pub const TYPING_INTERVAL: Duration = Duration::from_secs(3);
+/// 超过这个时间没有收到通知，认为对方已停止输入
+pub const TYPING_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// 发送端：输入过程中每隔 `TYPING_INTERVAL` 最多通知一次
+#[derive(Debug, Default)]
+pub struct TypingThrottle {
+    last_sent: Option<Instant>,
+}
+
+impl TypingThrottle {
+    /// 是否需要发送输入通知，需要时记录发送时间
+    pub fn should_send(&mut self, now: Instant) -> bool {
+        if self.last_sent.is_some_and(|last| now.duration_since(last) < TYPING_INTERVAL) {
+            return false;
+        }
+        self.last_sent = Some(now);
+        true
+    }
+
+    /// 消息发出后重置，下次输入立即通知
+    pub fn reset(&mut self) {
+        self.last_sent = None;
+    }
+}
+
+/// 接收端：正在输入的用户和最后一次收到通知的时间
+#[derive(Debug, Default)]
+pub struct TypingIndicator {
+    typing: HashMap<i32, Instant>,
+}
+
+impl TypingIndicator {
+    pub fn on_typing(&mut self, uid: i32, now: Instant) {
+        self.typing.insert(uid, now);
+    }
+
+    /// 收到对方的消息后不再提示
+    pub fn clear(&mut self, uid: i32) {
+        self.typing.remove(&uid);
+    }
+
+    /// 提示文字，如 `bob 正在输入…`，没有人在输入时返回 None
+    pub fn status(&self, now: Instant, name: impl Fn(i32) -> String) -> Option<String> {
+        let mut uids = self
+            .typing
+            .iter()
+            .filter(|(_, last)| now.duration_since(**last) < TYPING_TIMEOUT)
+            .map(|(uid, _)| *uid)
+            .collect::<Vec<_>>();
+        uids.sort();
+        match uids.as_slice() {
+            [] => None,
+            [uid] => Some(format!("{} 正在输入…", name(*uid))),
+            [first, second] => Some(format!("{}、{} 正在输入…", name(*first), name(*second))),
+            [first, ..] => Some(format!("{} 等{}人正在输入…", name(*first), uids.len())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{TypingIndicator, TypingThrottle, TYPING_INTERVAL, TYPING_TIMEOUT};
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_typing() {
+        let now = Instant::now();
+        let mut throttle = TypingThrottle::default();
+        assert!(throttle.should_send(now));
+        assert!(!throttle.should_send(now + Duration::from_secs(1)));
+        assert!(throttle.should_send(now + TYPING_INTERVAL));
+        throttle.reset();
+        assert!(throttle.should_send(now + TYPING_INTERVAL));
+
+        let name = |uid: i32| format!("u{uid}");
+        let mut indicator = TypingIndicator::default();
+        assert_eq!(indicator.status(now, name), None);
+        indicator.on_typing(2, now);
+        indicator.on_typing(1, now + Duration::from_secs(2));
+        assert_eq!(indicator.status(now, name).unwrap(), "u1、u2 正在输入…");
+        assert_eq!(indicator.status(now + TYPING_TIMEOUT, name).unwrap(), "u1 正在输入…");
+        indicator.clear(1);
+        assert_eq!(indicator.status(now + TYPING_TIMEOUT, name), None);
+    }
+}
@@ -0,0 +1,76 @@
+//! 未读消息数
+
+/// 自定义 Option<u32> 未读数序列化，兼容服务端返回字符串或数字
+pub mod unread_format {
+    use serde::{self, Deserialize, Deserializer, Serializer};
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum RawUnread {
+        Number(u32),
+        Text(String),
+    }
+
+    pub fn serialize<S>(unread: &Option<u32>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match unread {
+            None => serializer.serialize_none(),
+            Some(unread) => serializer.serialize_u32(*unread),
+        }
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<u32>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        match Option::<RawUnread>::deserialize(deserializer)? {
+            None => Ok(None),
+            Some(RawUnread::Number(unread)) => Ok(Some(unread)),
+            Some(RawUnread::Text(unread)) if unread.trim().is_empty() => Ok(None),
+            Some(RawUnread::Text(unread)) => unread
+                .trim()
+                .parse::<u32>()
+                .map(Some)
+                .map_err(serde::de::Error::custom),
+        }
+    }
+}
+
+/// 未读数徽标文字，超过99显示为99+
+pub fn badge(unread: u32) -> String {
+    if unread > 99 {
+        "99+".to_string()
+    } else {
+        unread.to_string()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use serde::Deserialize;
+    use serde_json::json;
+
+    #[derive(Deserialize)]
+    struct Chat {
+        #[serde(default, with = "super::unread_format")]
+        unread: Option<u32>,
+    }
+
+    #[test]
+    fn test_parse_unread() {
+        let parse = |value| serde_json::from_value::<Chat>(value).unwrap().unread;
+        assert_eq!(parse(json!({"unread": "12"})), Some(12));
+        assert_eq!(parse(json!({"unread": 3})), Some(3));
+        assert_eq!(parse(json!({"unread": null})), None);
+        assert_eq!(parse(json!({})), None);
+        assert!(serde_json::from_value::<Chat>(json!({"unread": "abc"})).is_err());
+    }
+
+    #[test]
+    fn test_badge() {
+        assert_eq!(super::badge(7), "7");
+        assert_eq!(super::badge(100), "99+");
+    }
+}
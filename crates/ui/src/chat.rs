@@ -0,0 +1,813 @@
+use crate::groups::{authorized, fetch_groups, fetch_members, request_friends, send};
+use crate::e2e;
+use crate::markdown;
+use crate::theme::theme;
+use crate::token::current_user;
+use crate::user_input::Input;
+use crate::{host, http_client, stream_client};
+use color_eyre::eyre::format_err;
+use chrono::{Local, TimeZone};
+use color_eyre::Result;
+use common::attachment::{content_type, download_dir, expand_home, save_path, Attachment};
+use common::command::{help, parse, ChatInput, Command};
+use common::config::settings;
+use common::contact::{ContactBook, BLOCKED_MESSAGE};
+use common::e2e::{fingerprint, KeyCheck, KeyStore};
+use common::edit::{amend, check_recall, own_message};
+use common::emoji;
+use common::group::GROUP_GONE;
+use common::mention::{expand, mentions};
+use common::message::{ChatMessage, HistoryMsg, MessageTarget, MessageTargetGroup, MessageTargetUser, ReactionMessage, TypingMessage};
+use common::outbox::{Outbox, Queued};
+use common::sanitize::sanitize_line;
+use common::reaction::{react, reacted, resolve, summary, PICKER};
+use common::receipt::Receipts;
+use common::text::{single_line, truncate};
+use common::typing::{TypingIndicator, TypingThrottle, TYPING_INTERVAL};
+use crossterm::event::{self, KeyCode, KeyModifiers};
+use ratatui::buffer::Buffer;
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::Modifier;
+use ratatui::symbols;
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, Padding, Paragraph, Widget};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Instant;
+
+/// 表情选择器：回应的消息id和选中的表情
+struct Picker {
+    mid: i64,
+    selected: usize,
+}
+
+/// 打开的会话：聊天记录和输入框，输入 `@` 或 `/` 后按 Tab 补全名称或命令
+pub(crate) struct Chat {
+    pub(crate) target: MessageTarget,
+    name: String,
+    messages: Vec<HistoryMsg>,
+    /// `/clear` 之后从这条消息开始显示
+    start: usize,
+    /// 向上翻过的消息条数，`/more` 每次多翻一页
+    scroll: usize,
+    /// 命令的输出，下次输入时清空
+    output: Vec<String>,
+    /// 发送者id到显示名称
+    names: HashMap<i32, String>,
+    /// @提及的候选：群成员和好友的用户名
+    mention_names: Vec<String>,
+    /// 群公告
+    announcement: Option<String>,
+    input: Input,
+    /// 正在输入的成员
+    typing: TypingIndicator,
+    /// 自己输入时限制通知频率
+    throttle: TypingThrottle,
+    /// 自己发出的消息的状态
+    receipts: Receipts,
+    /// 打开表情选择器时不为空，按键由选择器处理
+    picker: Option<Picker>,
+    /// 上下键选中的附件消息id，输入框为空时按 Enter 下载
+    selected: Option<i64>,
+    /// 单聊是否开启了端到端加密
+    encrypted: bool,
+    /// 对方变化后的公钥，再次输入 `/encrypt on` 时信任
+    pending_key: Option<String>,
+}
+
+impl Chat {
+    /// 打开会话，加载聊天记录和@提及的候选，解密单聊中的加密消息
+    pub(crate) fn open(target: MessageTarget, name: String, contacts: &ContactBook, keys: &KeyStore) -> Result<Self> {
+        let mut messages = fetch_history(target)?;
+        for msg in &mut messages {
+            reveal(keys, target, msg);
+        }
+        let (names, mut mention_names) = sender_names(target, &name, contacts)?;
+        mention_names.extend(request_friends()?.into_iter().map(|friend| friend.name));
+        let announcement = match target {
+            MessageTarget::Group(MessageTargetGroup { gid }) => fetch_groups()?
+                .into_iter()
+                .find(|group| group.gid == gid)
+                .and_then(|group| group.announcement)
+                .filter(|content| !content.is_empty()),
+            MessageTarget::User(_) => None,
+        };
+        Ok(Self {
+            target,
+            name,
+            messages,
+            start: 0,
+            scroll: 0,
+            output: vec![],
+            names,
+            mention_names,
+            announcement,
+            input: Input::new(),
+            typing: TypingIndicator::default(),
+            throttle: TypingThrottle::default(),
+            receipts: Receipts::default(),
+            picker: None,
+            selected: None,
+            encrypted: matches!(target, MessageTarget::User(MessageTargetUser { uid }) if keys.is_enabled(uid)),
+            pending_key: None,
+        })
+    }
+
+    /// 处理输入，返回需要会话列表处理的操作；发送的消息先保存到 `outbox`，失败时自动重发，
+    /// 开启了加密的单聊用 `keys` 加密
+    pub(crate) fn handle_key(&mut self, key: event::KeyEvent, outbox: &mut Outbox, keys: &mut KeyStore) -> Result<ChatAction> {
+        if self.picker.is_some() {
+            self.handle_picker_key(key)?;
+            return Ok(ChatAction::None);
+        }
+        match key.code {
+            KeyCode::Esc if self.selected.is_some() => self.selected = None,
+            KeyCode::Esc => return Ok(ChatAction::Close),
+            KeyCode::Up => self.select_attachment(true),
+            KeyCode::Down => self.select_attachment(false),
+            KeyCode::Enter if self.input.input.is_empty() && self.selected.is_some() => {
+                let mid = self.selected.take().unwrap_or_default();
+                self.download(mid, None)?;
+            }
+            // 回应最新的一条消息
+            KeyCode::Char('e') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                if let Some(msg) = self.messages.iter().rev().find(|msg| !msg.recalled) {
+                    self.picker = Some(Picker { mid: msg.mid, selected: 0 });
+                }
+            }
+            KeyCode::Enter => return self.submit(outbox, keys),
+            KeyCode::Tab => self.input.complete(&self.mention_names),
+            KeyCode::Char(c) => {
+                self.selected = None;
+                self.input.enter_char(c);
+                if c == ':' {
+                    self.input.expand_shortcode();
+                }
+                self.notify_typing();
+            }
+            KeyCode::Backspace => {
+                self.input.delete_char();
+                self.notify_typing();
+            }
+            KeyCode::Left => self.input.move_cursor_left(),
+            KeyCode::Right => self.input.move_cursor_right(),
+            _ => {}
+        }
+        Ok(ChatAction::None)
+    }
+
+    /// 粘贴的文字整体插入，国旗等由多个字符组成的表情不会被拆开；输入框只有一行，换行替换为空格
+    pub(crate) fn paste(&mut self, text: &str) {
+        self.selected = None;
+        self.input.insert_str(&sanitize_line(text));
+        self.notify_typing();
+    }
+
+    /// 在附件之间移动选中，`older` 为 true 时选中更早的附件，移出最新的附件后取消选中
+    fn select_attachment(&mut self, older: bool) {
+        let attachments = self
+            .messages
+            .iter()
+            .filter(|msg| msg.attachment.is_some() && !msg.recalled)
+            .map(|msg| msg.mid)
+            .collect::<Vec<_>>();
+        let position = self.selected.and_then(|mid| attachments.iter().position(|id| *id == mid));
+        self.selected = match (position, older) {
+            (None, true) => attachments.last().copied(),
+            (Some(position), true) => attachments.get(position.saturating_sub(1)).copied(),
+            (Some(position), false) => attachments.get(position + 1).copied(),
+            (None, false) => None,
+        };
+    }
+
+    /// 下载附件，未指定目录时保存到下载目录
+    fn download(&mut self, mid: i64, dir: Option<&str>) -> Result<()> {
+        let attachment = self
+            .messages
+            .iter()
+            .find(|msg| msg.mid == mid && !msg.recalled)
+            .and_then(|msg| msg.attachment.clone())
+            .ok_or_else(|| format_err!("消息{mid}不是附件"))?;
+        let dir = dir.map_or_else(download_dir, expand_home);
+        let path = download_file(&attachment, &dir)?;
+        self.output = vec![format!("已保存到 {}", path.display())];
+        Ok(())
+    }
+
+    /// 左右选择表情，输入编号直接选择，Enter 回应，Esc 取消
+    fn handle_picker_key(&mut self, key: event::KeyEvent) -> Result<()> {
+        let Some(picker) = &mut self.picker else {
+            return Ok(());
+        };
+        let selected = match key.code {
+            KeyCode::Esc => None,
+            KeyCode::Left => {
+                picker.selected = (picker.selected + PICKER.len() - 1) % PICKER.len();
+                return Ok(());
+            }
+            KeyCode::Right => {
+                picker.selected = (picker.selected + 1) % PICKER.len();
+                return Ok(());
+            }
+            KeyCode::Enter => Some(PICKER[picker.selected].to_string()),
+            KeyCode::Char(c) => match c.to_digit(10).and_then(|digit| PICKER.get((digit as usize).checked_sub(1)?)) {
+                Some(emoji) => Some(emoji.to_string()),
+                None => return Ok(()),
+            },
+            _ => return Ok(()),
+        };
+        let mid = picker.mid;
+        self.picker = None;
+        match selected {
+            Some(emoji) => self.react(mid, &emoji),
+            None => Ok(()),
+        }
+    }
+
+    /// 用表情回应消息，已经回应过相同的表情时取消回应
+    fn react(&self, mid: i64, emoji: &str) -> Result<()> {
+        let removed = self
+            .messages
+            .iter()
+            .find(|msg| msg.mid == mid)
+            .is_some_and(|msg| reacted(&msg.reactions, my_user().0, emoji));
+        post_message(self.target, serde_json::json!({ "react": mid, "emoji": emoji, "remove": removed }))
+    }
+
+    /// 收到表情回应
+    pub(crate) fn on_reaction(&mut self, message: &ReactionMessage) {
+        react(&mut self.messages, message);
+    }
+
+    /// 输入消息时通知对方，输入命令时不通知
+    fn notify_typing(&mut self) {
+        if !self.input.input.is_empty() && !self.input.input.starts_with('/') && self.throttle.should_send(Instant::now()) {
+            send_typing(self.target);
+        }
+    }
+
+    /// 其他人的已读位置更新
+    pub(crate) fn on_read(&mut self, mid: i64) {
+        self.receipts.read(mid);
+    }
+
+    /// 收到正在输入的通知
+    pub(crate) fn on_typing(&mut self, message: &TypingMessage) {
+        self.typing.on_typing(message.from_uid, Instant::now());
+    }
+
+    /// 执行命令或发送消息，输入有误时保留输入并提示
+    fn submit(&mut self, outbox: &mut Outbox, keys: &mut KeyStore) -> Result<ChatAction> {
+        self.output.clear();
+        let command = match parse(&self.input.input).map_err(|err| format_err!(err))? {
+            ChatInput::Text(text) if text.is_empty() => return Ok(ChatAction::None),
+            ChatInput::Text(text) => {
+                self.send(&text, None, outbox, keys)?;
+                return Ok(ChatAction::None);
+            }
+            ChatInput::Command(command) => command,
+        };
+        let mut action = ChatAction::None;
+        match command {
+            Command::Exit => action = ChatAction::Close,
+            Command::Help(name) => self.output = help(name.as_deref()).map_err(|err| format_err!(err))?,
+            Command::History(count) => {
+                self.start = count.map_or(0, |count| self.messages.len().saturating_sub(count));
+                self.scroll = 0;
+            }
+            Command::More => self.scroll += settings().page_size.history,
+            Command::Reply { index, text } => {
+                let mid = self
+                    .messages
+                    .len()
+                    .checked_sub(index)
+                    .map(|position| self.messages[position].mid)
+                    .ok_or_else(|| format_err!("没有第{index}条消息"))?;
+                self.send(&text, Some(mid), outbox, keys)?;
+            }
+            Command::Edit { .. } if self.encrypted => return Err(format_err!("加密会话暂不支持编辑")),
+            Command::Edit { index, text } => {
+                let mid = own_message(&self.messages.iter().collect::<Vec<_>>(), index, my_user().0).map_err(|err| format_err!(err))?.mid;
+                let msg = expand(&emoji::expand(&text), &self.mention_names).map_err(|err| format_err!("{err}，请补全后重新发送"))?;
+                post_message(self.target, serde_json::json!({ "edit": mid, "msg": msg }))?;
+            }
+            Command::Recall(index) => {
+                let messages = self.messages.iter().collect::<Vec<_>>();
+                let msg = own_message(&messages, index, my_user().0).map_err(|err| format_err!(err))?;
+                check_recall(msg, Local::now(), settings().chat.recall_limit).map_err(|err| format_err!(err))?;
+                post_message(self.target, serde_json::json!({ "recall": msg.mid }))?;
+            }
+            Command::React { index, emoji } => {
+                let mid = self
+                    .messages
+                    .len()
+                    .checked_sub(index)
+                    .map(|position| self.messages[position].mid)
+                    .ok_or_else(|| format_err!("没有第{index}条消息"))?;
+                match emoji {
+                    Some(emoji) => self.react(mid, &resolve(&emoji))?,
+                    None => self.picker = Some(Picker { mid, selected: 0 }),
+                }
+            }
+            // 附件不加密，加密会话中不发送
+            Command::SendFile(_) if self.encrypted => return Err(format_err!("加密会话暂不支持发送附件")),
+            Command::SendFile(path) => {
+                let content = upload_file(&expand_home(&path))?.to_content();
+                let body = serde_json::json!({
+                    "msg": content.content,
+                    "content_type": content.content_type,
+                    "properties": content.properties,
+                });
+                post_message(self.target, body)?;
+                self.scroll = 0;
+            }
+            Command::Download { mid, dir } => self.download(mid, dir.as_deref())?,
+            Command::Search(keyword) => self.output = self.search(&keyword),
+            Command::Clear => {
+                self.start = self.messages.len();
+                self.scroll = 0;
+            }
+            Command::Me(action) => self.send(&format!("* {} {action}", my_user().1), None, outbox, keys)?,
+            Command::Who => {
+                let mut names = self.names.values().cloned().collect::<Vec<_>>();
+                names.sort();
+                self.output = vec![format!("成员（{}）：You、{}", names.len() + 1, names.join("、"))];
+            }
+            Command::Mute => action = ChatAction::Mute,
+            Command::Retry => action = ChatAction::Retry,
+            Command::Encrypt(enabled) => self.set_encrypted(enabled, keys)?,
+            Command::Fingerprint => self.output = self.fingerprints(keys)?,
+        }
+        self.input.submit_message();
+        Ok(action)
+    }
+
+    /// 发送消息，先补全不完整的@提及；发送失败时留在重发队列中，不再提示错误
+    fn send(&mut self, text: &str, reply: Option<i64>, outbox: &mut Outbox, keys: &KeyStore) -> Result<()> {
+        let msg = expand(&emoji::expand(text), &self.mention_names).map_err(|err| format_err!("{err}，请补全后重新发送"))?;
+        // 加密会话先加密，重发时发送同一份密文
+        let encrypted = keys.encrypt_for(self.target, &msg).map_err(|err| format_err!(err))?;
+        let now = Local::now().timestamp_millis();
+        let queued = outbox.push(self.target, &msg, encrypted, reply, now);
+        let client_id = queued.client_id.clone();
+        outbox.save().map_err(|err| format_err!(err))?;
+        let id = self.receipts.send(&msg, &client_id);
+        self.throttle.reset();
+        self.input.submit_message();
+        self.scroll = 0;
+        let result = match send_message(&queued) {
+            Ok(_) => {
+                outbox.sent(&client_id);
+                self.receipts.sent(id);
+                Ok(())
+            }
+            Err(err) if err.to_string() == GROUP_GONE => {
+                outbox.sent(&client_id);
+                self.receipts.failed(id);
+                Err(err)
+            }
+            Err(_) => {
+                outbox.attempt_failed(&client_id, now);
+                self.receipts.failed(id);
+                Ok(())
+            }
+        };
+        outbox.save().map_err(|err| format_err!(err))?;
+        result
+    }
+
+    /// 开启或关闭加密，`enabled` 为空时切换。开启时发布自己的公钥并获取对方的公钥，
+    /// 对方的公钥变化时先提示核对指纹，再次开启才信任新公钥
+    fn set_encrypted(&mut self, enabled: Option<bool>, keys: &mut KeyStore) -> Result<()> {
+        let MessageTarget::User(MessageTargetUser { uid }) = self.target else {
+            return Err(format_err!("只有单聊支持端到端加密"));
+        };
+        if !enabled.unwrap_or(!self.encrypted) {
+            keys.set_enabled(uid, false);
+            keys.save().map_err(|err| format_err!(err))?;
+            self.encrypted = false;
+            self.output = vec!["已关闭端到端加密".to_string()];
+            return Ok(());
+        }
+        e2e::publish(keys)?;
+        let key = e2e::fetch(uid)?;
+        if keys.check(uid, &key) == KeyCheck::Changed {
+            if self.pending_key.as_deref() != Some(key.as_str()) {
+                self.output = vec![
+                    "对方的密钥已变化，可能是对方换了设备，也可能是有人冒充".to_string(),
+                    format!("原指纹：{}", keys.peer_key(uid).map(fingerprint).unwrap_or_default()),
+                    format!("新指纹：{}", fingerprint(&key)),
+                    "请通过其他渠道核对新指纹，确认后再次输入 /encrypt on 信任新密钥".to_string(),
+                ];
+                self.pending_key = Some(key);
+                return Ok(());
+            }
+            keys.trust(uid, &key);
+        }
+        self.pending_key = None;
+        keys.set_enabled(uid, true);
+        keys.save().map_err(|err| format_err!(err))?;
+        self.encrypted = true;
+        self.output = ["🔒 已开启端到端加密".to_string()].into_iter().chain(self.fingerprints(keys)?).collect();
+        Ok(())
+    }
+
+    /// 双方的指纹，双方看到的应该一致
+    fn fingerprints(&self, keys: &KeyStore) -> Result<Vec<String>> {
+        let MessageTarget::User(MessageTargetUser { uid }) = self.target else {
+            return Err(format_err!("只有单聊支持端到端加密"));
+        };
+        let mine = keys.public_key().map_err(|err| format_err!(err))?;
+        Ok(vec![
+            format!("我的指纹：{}", fingerprint(&mine)),
+            match keys.peer_key(uid) {
+                Some(key) => format!("{}的指纹：{}", self.name, fingerprint(key)),
+                None => "还没有对方的公钥，输入 /encrypt on 获取".to_string(),
+            },
+        ])
+    }
+
+    fn search(&self, keyword: &str) -> Vec<String> {
+        let keyword = keyword.to_lowercase();
+        let matched = self
+            .messages
+            .iter()
+            .filter(|msg| !msg.recalled && msg.msg.to_lowercase().contains(&keyword))
+            .map(|msg| format!("[{}] {}: {}", msg.time.format("%m-%d %H:%M"), self.sender_name(msg.from_uid, my_user().0), msg.display()))
+            .collect::<Vec<_>>();
+        if matched.is_empty() {
+            vec![format!("没有找到包含{keyword}的消息")]
+        } else {
+            matched
+        }
+    }
+
+    /// 命令输出的提示，如免打扰的切换结果
+    pub(crate) fn notify(&mut self, output: String) {
+        self.output = vec![output];
+    }
+
+    fn sender_name(&self, uid: i32, my_uid: i32) -> String {
+        if uid == my_uid {
+            "You".to_string()
+        } else {
+            self.names.get(&uid).cloned().unwrap_or_else(|| uid.to_string())
+        }
+    }
+
+    /// 收到当前会话的新消息，自己发送的消息也从消息流中返回；编辑和撤回原地更新聊天记录。
+    /// 对方用新的公钥发来的消息无法解密，提示核对指纹
+    pub(crate) fn on_message(&mut self, message: &ChatMessage, contacts: &ContactBook, keys: &KeyStore) -> Result<()> {
+        let from_uid = message.payload.from_uid;
+        if message.payload.detail.amended().is_some() {
+            amend(&mut self.messages, &message.payload);
+            return Ok(());
+        }
+        if from_uid != my_user().0 && !self.names.contains_key(&from_uid) {
+            // 新成员入群
+            (self.names, _) = sender_names(self.target, &self.name, contacts)?;
+        }
+        self.typing.clear(from_uid);
+        let mut msg = HistoryMsg::from(message);
+        if let Some(envelope) = msg.encrypted.as_ref().filter(|_| from_uid != my_user().0) {
+            if keys.peer_key(from_uid).is_some_and(|key| key != envelope.sender_key) {
+                self.output = vec![
+                    format!("对方的密钥已变化，新指纹：{}", fingerprint(&envelope.sender_key)),
+                    "请通过其他渠道核对指纹，确认后输入 /encrypt on 信任新密钥".to_string(),
+                ];
+            }
+        }
+        reveal(keys, self.target, &mut msg);
+        if from_uid == my_user().0 {
+            self.receipts.delivered(message.payload.client_id.as_deref());
+        }
+        self.messages.push(msg);
+        Ok(())
+    }
+
+    /// 最近的消息，提到我的消息高亮显示，自己的消息后面显示状态，还没有送达和发送失败的消息排在最后
+    fn lines(&self, contacts: &ContactBook, outbox: &Outbox) -> Vec<Line<'static>> {
+        let (my_uid, my_name) = my_user();
+        let mut lines = vec![];
+        if let Some(content) = &self.announcement {
+            lines.push(Line::styled(format!("[公告] {content}"), theme().badge));
+        }
+        for msg in &self.messages[self.start.min(self.messages.len())..] {
+            let sender = self.sender_name(msg.from_uid, my_uid);
+            let attachment = msg.attachment.as_ref().filter(|_| !msg.recalled && !contacts.is_blocked(msg.from_uid));
+            let content = match attachment {
+                _ if contacts.is_blocked(msg.from_uid) => BLOCKED_MESSAGE.to_string(),
+                Some(attachment) => format!("📎 {}", attachment.text()),
+                None if msg.encrypted.is_some() && !msg.recalled => format!("🔒 {}", msg.display()),
+                None => msg.display(),
+            };
+            // 附件单独一种样式，上下键选中时反色
+            let style = if attachment.is_some() && self.selected == Some(msg.mid) {
+                theme().key.add_modifier(Modifier::REVERSED)
+            } else if attachment.is_some() {
+                theme().key.add_modifier(Modifier::UNDERLINED)
+            } else if msg.from_uid != my_uid && !msg.recalled && mentions(&content, &my_name) {
+                theme().mention
+            } else if msg.recalled {
+                theme().secondary
+            } else {
+                theme().text
+            };
+            // 文字消息按 Markdown 显示，可能有多行
+            let mut content = if attachment.is_none() && !msg.recalled && !contacts.is_blocked(msg.from_uid) {
+                markdown::lines(&content, style)
+            } else {
+                vec![Line::styled(content, style)]
+            };
+            let mut line = Line::from(vec![
+                Span::styled(format!("[{}] ", msg.time.format("%H:%M:%S")), theme().secondary),
+                Span::styled(format!("{sender}: "), theme().title),
+            ]);
+            // 多行的消息从发送人的下一行开始显示
+            if content.len() == 1 {
+                line.spans.append(&mut content.remove(0).spans);
+            }
+            let mut message = vec![line];
+            message.append(&mut content);
+            if msg.from_uid == my_uid && !msg.recalled {
+                if let Some(last) = message.last_mut() {
+                    last.push_span(Span::styled(format!(" {}", self.receipts.state(msg.mid)), theme().secondary));
+                }
+            }
+            lines.append(&mut message);
+            if !msg.reactions.is_empty() && !msg.recalled {
+                lines.push(Line::styled(format!("    {}", summary(&msg.reactions)), theme().secondary));
+            }
+        }
+        for outgoing in self.receipts.outgoing() {
+            lines.push(Line::from(vec![
+                Span::styled("[--:--:--] ", theme().secondary),
+                Span::styled("You: ", theme().title),
+                Span::styled(outgoing.content.clone(), theme().text),
+                Span::styled(format!(" {}", outgoing.state), theme().secondary),
+            ]));
+        }
+        for queued in outbox.queued(self.target) {
+            let time = Local.timestamp_millis_opt(queued.created_at).single().unwrap_or_else(Local::now);
+            let status = if queued.failed() {
+                " 发送失败，输入 /retry 或在列表中按 r 重试".to_string()
+            } else {
+                format!(" 等待重发（已失败{}次）", queued.attempts)
+            };
+            lines.push(Line::from(vec![
+                Span::styled(format!("[{}] ", time.format("%H:%M:%S")), theme().secondary),
+                Span::styled("You: ", theme().title),
+                Span::styled(queued.msg.clone(), theme().text),
+                Span::styled(status, theme().error),
+            ]));
+        }
+        lines
+    }
+
+    pub(crate) fn render(&self, contacts: &ContactBook, outbox: &Outbox, area: Rect, buf: &mut Buffer) {
+        let output_height = u16::try_from(self.output.len()).unwrap_or(u16::MAX).min(area.height / 2);
+        let my_uid = my_user().0;
+        let typing = self.typing.status(Instant::now(), |uid| self.sender_name(uid, my_uid));
+        let shortcodes = self.input.shortcode_candidates();
+        let [messages_area, typing_area, output_area, picker_area, shortcode_area, input_area] = Layout::vertical([
+            Constraint::Fill(1),
+            Constraint::Length(u16::from(typing.is_some())),
+            Constraint::Length(output_height),
+            Constraint::Length(u16::from(self.picker.is_some())),
+            Constraint::Length(u16::from(!shortcodes.is_empty())),
+            Constraint::Length(3),
+        ])
+        .areas(area);
+        let block = Block::new()
+            .title(Line::raw(format!("{}Chat with {}", if self.encrypted { "🔒 " } else { "" }, self.name)).centered())
+            .borders(Borders::LEFT | Borders::TOP)
+            .border_set(symbols::border::EMPTY)
+            .border_style(theme().header)
+            .style(theme().row)
+            .padding(Padding::horizontal(1));
+        // 显示最新的消息，`/more` 向上翻页
+        let lines = self.lines(contacts, outbox);
+        let height = usize::from(block.inner(messages_area).height);
+        let end = lines.len().saturating_sub(self.scroll).max(height.min(lines.len()));
+        let skip = end.saturating_sub(height);
+        Paragraph::new(lines.into_iter().skip(skip).take(height).collect::<Vec<_>>())
+            .block(block)
+            .render(messages_area, buf);
+        if let Some(typing) = typing {
+            Paragraph::new(typing)
+                .style(theme().secondary.add_modifier(Modifier::ITALIC))
+                .block(Block::new().borders(Borders::LEFT).border_set(symbols::border::EMPTY).padding(Padding::horizontal(1)))
+                .render(typing_area, buf);
+        }
+        Paragraph::new(self.output.iter().map(|line| Line::raw(line.clone())).collect::<Vec<_>>())
+            .style(theme().secondary)
+            .block(Block::new().borders(Borders::LEFT).border_set(symbols::border::EMPTY).padding(Padding::horizontal(1)))
+            .render(output_area, buf);
+
+        if let Some(picker) = &self.picker {
+            self.render_picker(picker, picker_area, buf);
+        }
+        if !shortcodes.is_empty() {
+            render_shortcodes(&shortcodes, shortcode_area, buf);
+        }
+
+        Paragraph::new(self.input.line())
+            .style(theme().editing)
+            .block(Block::bordered().title(if self.selected.is_some() {
+                "Enter 下载附件, ↑↓ 选择附件, Esc 取消选择"
+            } else {
+                "Enter 发送, Tab 补全, ↑↓ 选择附件, Ctrl-E 回应, /help 查看命令, Esc 关闭"
+            }))
+            .render(input_area, buf);
+    }
+
+    /// 表情选择器，显示在输入框上方
+    fn render_picker(&self, picker: &Picker, area: Rect, buf: &mut Buffer) {
+        let preview = self
+            .messages
+            .iter()
+            .find(|msg| msg.mid == picker.mid)
+            .map(|msg| truncate(&single_line(&msg.display()), 20))
+            .unwrap_or_default();
+        let mut spans = vec![Span::styled(format!("回应「{preview}」："), theme().secondary)];
+        for (index, emoji) in PICKER.iter().enumerate() {
+            let style = if index == picker.selected { theme().editing.add_modifier(Modifier::REVERSED) } else { theme().text };
+            spans.push(Span::styled(format!(" {}.{emoji} ", index + 1), style));
+        }
+        spans.push(Span::styled("  ←→ 选择, Enter 确定, Esc 取消", theme().secondary));
+        Paragraph::new(Line::from(spans))
+            .block(Block::new().borders(Borders::LEFT).border_set(symbols::border::EMPTY).padding(Padding::horizontal(1)))
+            .render(area, buf);
+    }
+}
+
+/// 正在输入的表情短代码的候选，Tab 依次替换为其中的表情
+fn render_shortcodes(shortcodes: &[(&str, &str)], area: Rect, buf: &mut Buffer) {
+    let mut spans = vec![Span::styled("Tab 补全表情：", theme().secondary)];
+    for (code, emoji) in shortcodes.iter().take(8) {
+        spans.push(Span::styled(format!(" {emoji} "), theme().text));
+        spans.push(Span::styled(format!(":{code}: "), theme().secondary));
+    }
+    Paragraph::new(Line::from(spans))
+        .block(Block::new().borders(Borders::LEFT).border_set(symbols::border::EMPTY).padding(Padding::horizontal(1)))
+        .render(area, buf);
+}
+
+/// 聊天输入框需要会话列表处理的操作
+pub(crate) enum ChatAction {
+    None,
+    Close,
+    /// 切换当前会话的免打扰
+    Mute,
+    /// 重发当前会话中发送失败的消息
+    Retry,
+}
+
+/// 当前账号的id和用户名
+pub(crate) fn my_user() -> (i32, String) {
+    current_user()
+        .lock()
+        .unwrap()
+        .user
+        .as_ref()
+        .map_or((0, String::new()), |user| (user.id, user.name.clone()))
+}
+
+/// 发送者id到显示名称的映射，以及@提及的候选用户名
+pub(crate) fn sender_names(target: MessageTarget, name: &str, contacts: &ContactBook) -> Result<(HashMap<i32, String>, Vec<String>)> {
+    match target {
+        MessageTarget::User(MessageTargetUser { uid }) => Ok((HashMap::from([(uid, name.to_string())]), vec![])),
+        MessageTarget::Group(MessageTargetGroup { gid }) => {
+            let members = fetch_members(gid)?;
+            let names = members
+                .iter()
+                .map(|member| (member.uid, contacts.display_name(member.uid, &member.name)))
+                .collect();
+            Ok((names, members.into_iter().map(|member| member.name).collect()))
+        }
+    }
+}
+
+/// 单聊或群聊的聊天记录，群已解散时返回 `GROUP_GONE`
+pub(crate) fn fetch_history(target: MessageTarget) -> Result<Vec<HistoryMsg>> {
+    let url = match target {
+        MessageTarget::User(MessageTargetUser { uid }) => format!("{}/user/{uid}/history", host()),
+        MessageTarget::Group(MessageTargetGroup { gid }) => {
+            return send(http_client().get(format!("{}/group/{gid}/history", host())))?
+                .json::<Vec<HistoryMsg>>()
+                .map_err(|err| format_err!("Fail to Parse History: {}", err));
+        }
+    };
+    let res = authorized(http_client().get(url))
+        .send()
+        .map_err(|err| format_err!("Fail to Get History: {}", err))?;
+    if !res.status().is_success() {
+        return Err(format_err!("Fail to Get History: HTTP {}", res.status()));
+    }
+    res.json::<Vec<HistoryMsg>>()
+        .map_err(|err| format_err!("Fail to Parse History: {}", err))
+}
+
+#[derive(Deserialize)]
+struct Uploaded {
+    url: String,
+}
+
+/// 上传文件，返回发送附件消息需要的文件信息
+fn upload_file(path: &Path) -> Result<Attachment> {
+    let name = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .ok_or_else(|| format_err!("{} 不是文件", path.display()))?
+        .to_string();
+    let file = File::open(path).map_err(|err| format_err!("打开{}失败: {}", path.display(), err))?;
+    let size = file.metadata()?.len();
+    let content_type = content_type(&name).to_string();
+    let res = authorized(stream_client().post(format!("{}/file", host())))
+        .query(&[("name", name.as_str())])
+        .header("Content-Type", &content_type)
+        .body(reqwest::blocking::Body::sized(file, size))
+        .send()
+        .map_err(|err| format_err!("上传失败: {}", err))?;
+    if !res.status().is_success() {
+        return Err(format_err!("上传失败: HTTP {}", res.status()));
+    }
+    let uploaded = res.json::<Uploaded>().map_err(|err| format_err!("上传失败: {}", err))?;
+    Ok(Attachment { name, size, url: uploaded.url, content_type })
+}
+
+/// 下载附件到 `dir`，返回保存的路径；下载失败时删除不完整的文件
+fn download_file(attachment: &Attachment, dir: &Path) -> Result<PathBuf> {
+    // 只给自己的服务端带上登录信息
+    let (url, own_server) = attachment.download_url(&host()).map_err(|err| format_err!("下载失败: {}", err))?;
+    let request = if own_server { authorized(stream_client().get(url)) } else { stream_client().get(url) };
+    let mut res = request.send().map_err(|err| format_err!("下载失败: {}", err))?;
+    if !res.status().is_success() {
+        return Err(format_err!("下载失败: HTTP {}", res.status()));
+    }
+    std::fs::create_dir_all(dir).map_err(|err| format_err!("创建{}失败: {}", dir.display(), err))?;
+    let path = save_path(dir, &attachment.name);
+    let mut file = File::create(&path).map_err(|err| format_err!("创建{}失败: {}", path.display(), err))?;
+    if let Err(err) = res.copy_to(&mut file) {
+        let _ = std::fs::remove_file(&path);
+        return Err(format_err!("下载失败: {}", err));
+    }
+    Ok(path)
+}
+
+/// 通知对方正在输入，在后台线程发送，服务端响应慢时不卡住输入。
+/// 超过发送间隔的通知已经没有意义，旧版本服务端没有这个接口，失败时忽略
+fn send_typing(target: MessageTarget) {
+    let request = authorized(http_client().post(format!("{}/typing", host())).json(&serde_json::json!({ "target": target })))
+        .timeout(TYPING_INTERVAL);
+    thread::spawn(move || request.send());
+}
+
+/// 单聊中的加密消息替换为原文，对方的公钥只在 `/encrypt on` 时获取并记住
+fn reveal(keys: &KeyStore, target: MessageTarget, msg: &mut HistoryMsg) {
+    if let MessageTarget::User(MessageTargetUser { uid }) = target {
+        keys.reveal(msg, uid, my_user().0);
+    }
+}
+
+/// 发送队列中的消息，客户端id用于服务端识别重发的消息。
+/// 加密消息发送密文，服务端只能看到占位文字
+pub(crate) fn send_message(queued: &Queued) -> Result<()> {
+    let mut body = match &queued.encrypted {
+        Some(envelope) => {
+            let content = envelope.to_content();
+            serde_json::json!({
+                "msg": content.content,
+                "content_type": content.content_type,
+                "properties": content.properties,
+                "client_id": queued.client_id,
+            })
+        }
+        None => serde_json::json!({ "msg": queued.msg, "client_id": queued.client_id }),
+    };
+    if let Some(mid) = queued.reply {
+        body["reply"] = mid.into();
+    }
+    post_message(queued.target, body)
+}
+
+/// 向会话发送消息、编辑、撤回或表情回应，群已解散时返回 `GROUP_GONE`
+fn post_message(target: MessageTarget, body: serde_json::Value) -> Result<()> {
+    match target {
+        MessageTarget::User(MessageTargetUser { uid }) => {
+            let res = authorized(http_client().post(format!("{}/user/{uid}/send", host())).json(&body))
+                .send()
+                .map_err(|err| format_err!("Send message failed: {}", err))?;
+            if !res.status().is_success() {
+                return Err(format_err!("Send message failed: HTTP {}", res.status()));
+            }
+            Ok(())
+        }
+        MessageTarget::Group(MessageTargetGroup { gid }) => {
+            send(http_client().post(format!("{}/group/{gid}/send", host())).json(&body)).map(|_| ())
+        }
+    }
+}
@@ -1,14 +1,220 @@
+use crate::keymap::{keymap, Action, Screen};
+use crate::theme::theme;
+use crate::token::current_user;
+use crate::{host, http_client};
+use chrono::Local;
+use color_eyre::eyre::format_err;
+use color_eyre::Result;
+use common::datetime::relative_time;
+use common::friend::{FriendReqVo, FriendRequestStatus, ReviewSummary};
+use crossterm::event;
 use ratatui::buffer::Buffer;
-use ratatui::layout::Rect;
-use ratatui::widgets::Widget;
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::symbols;
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, HighlightSpacing, List, ListItem, ListState, Padding, Paragraph, StatefulWidget, Widget};
+use std::collections::HashSet;
 
-pub(crate) struct Contacts {}
+/// 好友申请列表，勾选后批量同意或拒绝
+pub(crate) struct Contacts {
+    requests: Vec<FriendReqVo>,
+    /// 勾选的申请id
+    checked: HashSet<i32>,
+    state: ListState,
+    /// 当前筛选项在 `FriendRequestStatus::FILTERS` 中的位置
+    filter: usize,
+    /// 上一次批量处理的结果
+    summary: Option<String>,
+}
+
+impl Contacts {
+    pub(crate) fn new() -> Result<Self> {
+        Ok(Self {
+            requests: friend_requests()?,
+            checked: HashSet::new(),
+            state: ListState::default(),
+            filter: 0,
+            summary: None,
+        })
+    }
+
+    pub(crate) fn handle_key(&mut self, key: event::KeyEvent) -> Result<()> {
+        match keymap().action(Screen::Contacts, &key) {
+            Some(Action::Down) => self.state.select_next(),
+            Some(Action::Up) => self.state.select_previous(),
+            Some(Action::Toggle) => self.toggle(),
+            Some(Action::ToggleAll) => self.toggle_all(),
+            Some(Action::Approve) => self.review(FriendRequestStatus::APPROVE)?,
+            Some(Action::Reject) => self.review(FriendRequestStatus::REJECT)?,
+            Some(Action::Filter) => {
+                self.filter = (self.filter + 1) % FriendRequestStatus::FILTERS.len();
+                self.checked.clear();
+                self.state.select(None);
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn filter(&self) -> Option<FriendRequestStatus> {
+        FriendRequestStatus::FILTERS[self.filter]
+    }
+
+    /// 当前筛选项下的申请
+    fn visible(&self) -> Vec<&FriendReqVo> {
+        self.requests
+            .iter()
+            .filter(|req| FriendRequestStatus::matches(self.filter(), req.status))
+            .collect()
+    }
+
+    fn selected(&self) -> Option<&FriendReqVo> {
+        self.state.selected().and_then(|index| self.visible().get(index).copied())
+    }
+
+    /// 只有待处理的申请可以勾选
+    fn toggle(&mut self) {
+        let Some(req) = self.selected().filter(|req| req.status == FriendRequestStatus::WAIT) else {
+            return;
+        };
+        let id = req.id;
+        if !self.checked.remove(&id) {
+            self.checked.insert(id);
+        }
+    }
+
+    /// 全部勾选，已全部勾选时取消勾选
+    fn toggle_all(&mut self) {
+        let pending = self
+            .visible()
+            .iter()
+            .filter(|req| req.status == FriendRequestStatus::WAIT)
+            .map(|req| req.id)
+            .collect::<HashSet<_>>();
+        if self.checked == pending {
+            self.checked.clear();
+        } else {
+            self.checked = pending;
+        }
+    }
+
+    /// 处理勾选的申请，没有勾选时处理当前选中的申请
+    fn review(&mut self, status: FriendRequestStatus) -> Result<()> {
+        let targets = if self.checked.is_empty() {
+            self.selected()
+                .filter(|req| req.status == FriendRequestStatus::WAIT)
+                .map(|req| vec![(req.id, req.request_name.clone())])
+                .unwrap_or_default()
+        } else {
+            self.visible()
+                .iter()
+                .filter(|req| self.checked.contains(&req.id))
+                .map(|req| (req.id, req.request_name.clone()))
+                .collect()
+        };
+        if targets.is_empty() {
+            return Ok(());
+        }
+        let mut summary = ReviewSummary::default();
+        for (id, name) in targets {
+            summary.record(&name, status, review(id, status));
+        }
+        self.summary = Some(summary.to_string());
+        self.checked.clear();
+        self.requests = friend_requests()?;
+        Ok(())
+    }
+
+    fn render_list(&mut self, area: Rect, buf: &mut Buffer) {
+        let visible = self.visible();
+        let title = format!("Friend Requests | {} ({})", FriendRequestStatus::filter_name(self.filter()), visible.len());
+        let block = Block::new()
+            .title(Line::raw(title).centered())
+            .borders(Borders::TOP)
+            .border_set(symbols::border::EMPTY)
+            .border_style(theme().header)
+            .style(theme().row)
+            .padding(Padding::horizontal(1));
+        let items: Vec<ListItem> = visible
+            .iter()
+            .map(|req| {
+                let checkbox = match req.status {
+                    FriendRequestStatus::WAIT if self.checked.contains(&req.id) => "[x] ",
+                    FriendRequestStatus::WAIT => "[ ] ",
+                    _ => "    ",
+                };
+                ListItem::new(Line::from(vec![
+                    Span::raw(checkbox),
+                    Span::styled(req.request_name.clone(), theme().title),
+                    Span::styled(format!("  {}", req.reason()), theme().text),
+                    Span::styled(
+                        format!("  {} · {}", req.status, relative_time(req.create_time, Local::now())),
+                        theme().secondary,
+                    ),
+                ]))
+            })
+            .collect();
+        let list = List::new(items)
+            .block(block)
+            .highlight_style(theme().selected)
+            .highlight_symbol(">")
+            .highlight_spacing(HighlightSpacing::Always);
+        StatefulWidget::render(list, area, buf, &mut self.state);
+    }
+}
 
 impl Widget for &mut Contacts {
     fn render(self, area: Rect, buf: &mut Buffer)
     where
-        Self: Sized
+        Self: Sized,
     {
-        todo!()
+        let [list_area, summary_area, footer_area] = Layout::vertical([
+            Constraint::Fill(1),
+            Constraint::Length(1),
+            Constraint::Length(1),
+        ])
+            .areas(area);
+        self.render_list(list_area, buf);
+        if let Some(summary) = &self.summary {
+            Paragraph::new(summary.as_str())
+                .style(theme().secondary)
+                .centered()
+                .render(summary_area, buf);
+        }
+        Paragraph::new(keymap().footer(Screen::Contacts))
+            .centered()
+            .render(footer_area, buf);
+    }
+}
+
+fn friend_requests() -> Result<Vec<FriendReqVo>> {
+    let token = current_user().lock().unwrap().token.clone().unwrap();
+    let res = http_client()
+        .get(format!("{}/friend/req", host()))
+        .header("Authorization", format!("Bearer {}", token))
+        .send();
+    match res {
+        Ok(res) if res.status().is_success() => res
+            .json::<Vec<FriendReqVo>>()
+            .map_err(|err| format_err!("Fail to Parse Friend Request: {}", err)),
+        Ok(res) => Err(format_err!("Fail to Get Friend Request: HTTP {}", res.status())),
+        Err(err) => Err(format_err!("Fail to Get Friend Request: {}", err)),
+    }
+}
+
+fn review(id: i32, status: FriendRequestStatus) -> Result<(), String> {
+    let token = current_user().lock().unwrap().token.clone().unwrap();
+    let res = http_client()
+        .post(format!("{}/friend/req", host()))
+        .header("Authorization", format!("Bearer {}", token))
+        .json(&serde_json::json!({
+            "id": id,
+            "status": status,
+        }))
+        .send();
+    match res {
+        Ok(res) if res.status().is_success() => Ok(()),
+        Ok(res) => Err(format!("HTTP {}", res.status())),
+        Err(err) => Err(err.to_string()),
     }
-}
\ No newline at end of file
+}
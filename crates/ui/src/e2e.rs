@@ -0,0 +1,41 @@
+use crate::groups::authorized;
+use crate::{host, http_client};
+use color_eyre::eyre::format_err;
+use color_eyre::Result;
+use common::e2e::KeyStore;
+use common::sanitize::sanitize;
+use reqwest::StatusCode;
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+struct PublishedKey {
+    public_key: String,
+}
+
+/// 发布自己的公钥，对方开启加密时从服务端获取
+pub(crate) fn publish(keys: &KeyStore) -> Result<()> {
+    let public_key = keys.public_key().map_err(|err| format_err!(err))?;
+    let res = authorized(http_client().put(format!("{}/key", host())).json(&serde_json::json!({ "public_key": public_key })))
+        .send()
+        .map_err(|err| format_err!("发布公钥失败: {}", err))?;
+    if !res.status().is_success() {
+        return Err(format_err!("发布公钥失败: HTTP {}: {}", res.status(), sanitize(&res.text().unwrap_or_default())));
+    }
+    Ok(())
+}
+
+/// 获取对方发布的公钥，对方还没有发布时返回错误
+pub(crate) fn fetch(uid: i32) -> Result<String> {
+    let res = authorized(http_client().get(format!("{}/user/{uid}/key", host())))
+        .send()
+        .map_err(|err| format_err!("获取对方公钥失败: {}", err))?;
+    if res.status() == StatusCode::NOT_FOUND {
+        return Err(format_err!("对方还没有发布公钥，需要对方也开启加密"));
+    }
+    if !res.status().is_success() {
+        return Err(format_err!("获取对方公钥失败: HTTP {}: {}", res.status(), sanitize(&res.text().unwrap_or_default())));
+    }
+    res.json::<PublishedKey>()
+        .map(|key| key.public_key)
+        .map_err(|err| format_err!("获取对方公钥失败: {}", err))
+}
@@ -0,0 +1,66 @@
+use crate::stream_client;
+use crate::token::current_user;
+use common::message::Message;
+use std::io::{BufRead, BufReader};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::thread;
+use std::thread::sleep;
+use std::time::Duration;
+
+/// 断线重连间隔
+const RECONNECT_PERIOD: Duration = Duration::from_secs(3);
+
+/// 订阅当前账号的消息流，后台线程读取SSE并通过channel推送给界面
+pub(crate) fn subscribe() -> Receiver<Message> {
+    let (tx, rx) = channel();
+    let session = current_user();
+    thread::spawn(move || {
+        loop {
+            let (server, token) = {
+                let guard = session.lock().unwrap();
+                match &guard.token {
+                    None => break,
+                    Some(token) => (guard.server.clone(), token.clone()),
+                }
+            };
+            // 接收端已关闭（退出登陆或切换账号）时结束线程
+            if !read_stream(&server, &token, &tx) {
+                break;
+            }
+            sleep(RECONNECT_PERIOD);
+        }
+    });
+    rx
+}
+
+/// 读取消息流直到断开，返回是否需要重连
+fn read_stream(server: &str, token: &str, tx: &Sender<Message>) -> bool {
+    let response = stream_client()
+        .get(format!("{server}/event/stream"))
+        .header("Authorization", format!("Bearer {token}"))
+        .header("User-Agent", "Chat-Cli/1.0")
+        .send();
+    let response = match response {
+        Ok(response) if response.status().is_success() => response,
+        _ => return true,
+    };
+    let mut sse_message = String::new();
+    for line in BufReader::new(response).lines() {
+        let Ok(line) = line else {
+            return true;
+        };
+        // 空行表示一个事件结束
+        if !line.is_empty() {
+            sse_message.push_str(&line);
+            sse_message.push('\n');
+            continue;
+        }
+        if let Some(Ok(message)) = Message::from_sse(&sse_message) {
+            if tx.send(message).is_err() {
+                return false;
+            }
+        }
+        sse_message.clear();
+    }
+    true
+}
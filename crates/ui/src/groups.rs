@@ -0,0 +1,578 @@
+use crate::keymap::{keymap, Action, Screen};
+use crate::theme::theme;
+use crate::token::current_user;
+use crate::user_input::Input;
+use crate::{host, http_client};
+use color_eyre::eyre::format_err;
+use color_eyre::Result;
+use common::contact::ContactBook;
+use common::group::{is_gone, role_of, sort_members, GroupMember, GroupRole, GroupVo, GROUP_GONE};
+use common::sanitize::sanitize;
+use crossterm::event::{self, KeyCode};
+use ratatui::buffer::Buffer;
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::symbols;
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, HighlightSpacing, List, ListItem, ListState, Padding, Paragraph, StatefulWidget, Widget};
+use reqwest::blocking::{RequestBuilder, Response};
+use serde::Deserialize;
+
+/// 群聊列表和群成员，群主和管理员可以管理群聊
+pub(crate) struct Groups {
+    groups: Vec<GroupVo>,
+    state: ListState,
+    /// 打开的群的成员，未打开时为空
+    members: Option<Vec<GroupMember>>,
+    member_state: ListState,
+    /// 正在输入的内容：创建群聊、改名、邀请好友或发布公告
+    editing: Option<(Editing, Input)>,
+    /// 再按一次退出键才真正退出群聊
+    confirm_leave: bool,
+    /// 上一次操作的结果
+    status: Option<String>,
+    contacts: ContactBook,
+}
+
+#[derive(Clone, Copy, Eq, PartialEq)]
+enum Editing {
+    Create,
+    Rename,
+    Invite,
+    Announce,
+}
+
+impl Editing {
+    fn title(&self) -> &'static str {
+        match self {
+            Editing::Create => "群名称",
+            Editing::Rename => "新的群名称",
+            Editing::Invite => "邀请好友，多个名称用空格分隔",
+            Editing::Announce => "群公告，留空撤下公告",
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub(crate) struct Friend {
+    pub(crate) id: i32,
+    #[serde(deserialize_with = "common::sanitize::line")]
+    pub(crate) name: String,
+}
+
+impl Groups {
+    pub(crate) fn new() -> Result<Self> {
+        let uid = my_uid();
+        let contacts = ContactBook::load(&host(), uid).map_err(|err| format_err!(err))?;
+        Ok(Self {
+            groups: fetch_groups()?,
+            state: ListState::default(),
+            members: None,
+            member_state: ListState::default(),
+            editing: None,
+            confirm_leave: false,
+            status: None,
+            contacts,
+        })
+    }
+
+    /// 输入时按键全部交给输入框
+    pub(crate) fn is_editing(&self) -> bool {
+        self.editing.is_some()
+    }
+
+    pub(crate) fn handle_key(&mut self, key: event::KeyEvent) -> Result<()> {
+        if self.is_editing() {
+            return self.handle_input(key);
+        }
+        let action = keymap().action(Screen::Groups, &key);
+        if action != Some(Action::Leave) {
+            self.confirm_leave = false;
+        }
+        let result = match action {
+            Some(Action::Down) => {
+                self.select(1);
+                Ok(())
+            }
+            Some(Action::Up) => {
+                self.select(-1);
+                Ok(())
+            }
+            Some(Action::Members) => self.open_members(),
+            Some(Action::Close) => {
+                self.members = None;
+                Ok(())
+            }
+            Some(Action::Create) => {
+                self.editing = Some((Editing::Create, Input::new()));
+                Ok(())
+            }
+            Some(Action::Invite) if self.selected().is_some() => {
+                self.editing = Some((Editing::Invite, Input::new()));
+                Ok(())
+            }
+            Some(Action::Rename) => self.start_editing(Editing::Rename),
+            Some(Action::Announce) => self.start_editing(Editing::Announce),
+            Some(Action::Admin) => self.toggle_admin(),
+            Some(Action::Remove) => self.remove_member(),
+            Some(Action::Leave) => self.leave(),
+            _ => Ok(()),
+        };
+        self.handle_gone(result)
+    }
+
+    fn handle_input(&mut self, key: event::KeyEvent) -> Result<()> {
+        let Some((editing, input)) = &mut self.editing else {
+            return Ok(());
+        };
+        match key.code {
+            KeyCode::Esc => self.editing = None,
+            KeyCode::Enter => {
+                let (editing, text) = (*editing, input.input.trim().to_string());
+                self.editing = None;
+                // 公告可以为空，表示撤下公告
+                if !text.is_empty() || editing == Editing::Announce {
+                    let result = self.submit(editing, &text);
+                    return self.handle_gone(result);
+                }
+            }
+            KeyCode::Char(c) => input.enter_char(c),
+            KeyCode::Backspace => input.delete_char(),
+            KeyCode::Left => input.move_cursor_left(),
+            KeyCode::Right => input.move_cursor_right(),
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn submit(&mut self, editing: Editing, text: &str) -> Result<()> {
+        match editing {
+            Editing::Create => {
+                let group = create_group(text)?;
+                self.status = Some(format!("已创建群聊{}", group.name));
+                self.groups.push(group);
+                self.state.select(Some(self.groups.len() - 1));
+                self.members = None;
+            }
+            Editing::Rename => {
+                let Some(gid) = self.selected().map(|group| group.gid) else {
+                    return Ok(());
+                };
+                rename(gid, text)?;
+                self.status = Some(format!("已修改群名称为{text}"));
+                self.refresh()?;
+            }
+            Editing::Invite => {
+                let Some(gid) = self.selected().map(|group| group.gid) else {
+                    return Ok(());
+                };
+                let friends = fetch_friends(&self.contacts)?;
+                let mut uids = vec![];
+                for name in text.split_whitespace() {
+                    match friends.iter().find(|friend| friend.name == name) {
+                        Some(friend) => uids.push(friend.id),
+                        None => {
+                            self.status = Some(format!("好友{name}不存在"));
+                            return Ok(());
+                        }
+                    }
+                }
+                invite(gid, &uids)?;
+                self.status = Some(format!("已邀请 {} 位好友", uids.len()));
+                if self.members.is_some() {
+                    self.open_members()?;
+                }
+            }
+            Editing::Announce => {
+                let Some(gid) = self.selected().map(|group| group.gid) else {
+                    return Ok(());
+                };
+                announce(gid, text)?;
+                self.status = Some(if text.is_empty() { "已撤下公告" } else { "公告已更新" }.to_string());
+                self.refresh()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// 改名仅群主可用，发布公告群主和管理员可用
+    fn start_editing(&mut self, editing: Editing) -> Result<()> {
+        let Some(role) = self.my_role()? else {
+            return Ok(());
+        };
+        let Some(group) = self.selected() else {
+            return Ok(());
+        };
+        let (allowed, text) = match editing {
+            Editing::Rename => (role == GroupRole::Owner, group.name.clone()),
+            _ => (role.can_manage(), group.announcement.clone().unwrap_or_default()),
+        };
+        if allowed {
+            self.editing = Some((editing, Input::with_text(text)));
+        } else {
+            self.status = Some(format!("你是{role}，没有权限"));
+        }
+        Ok(())
+    }
+
+    /// 当前用户在选中群里的角色，需要时加载成员列表
+    fn my_role(&mut self) -> Result<Option<GroupRole>> {
+        if self.selected().is_none() {
+            return Ok(None);
+        }
+        if self.members.is_none() {
+            self.open_members()?;
+        }
+        Ok(self.members.as_ref().map(|members| role_of(members, my_uid())))
+    }
+
+    fn selected_member(&self) -> Option<GroupMember> {
+        self.member_state
+            .selected()
+            .and_then(|index| self.members.as_ref()?.get(index))
+            .cloned()
+    }
+
+    /// 群主设置或取消选中成员的管理员
+    fn toggle_admin(&mut self) -> Result<()> {
+        let Some(gid) = self.selected().map(|group| group.gid) else {
+            return Ok(());
+        };
+        if self.my_role()? != Some(GroupRole::Owner) {
+            self.status = Some("只有群主可以设置管理员".to_string());
+            return Ok(());
+        }
+        let Some(member) = self.selected_member().filter(|member| member.role != GroupRole::Owner) else {
+            return Ok(());
+        };
+        let role = match member.role {
+            GroupRole::Admin => GroupRole::Member,
+            _ => GroupRole::Admin,
+        };
+        set_role(gid, member.uid, role)?;
+        self.status = Some(format!("已将{}设为{role}", member.name));
+        self.open_members_keep_cursor()
+    }
+
+    /// 群已解散或已被移出群聊时，从列表中移除该群
+    fn handle_gone(&mut self, result: Result<()>) -> Result<()> {
+        match result {
+            Err(err) if err.to_string() == GROUP_GONE => {
+                if let Some(index) = self.state.selected().filter(|index| *index < self.groups.len()) {
+                    self.groups.remove(index);
+                }
+                self.members = None;
+                self.state.select(None);
+                self.status = Some(GROUP_GONE.to_string());
+                Ok(())
+            }
+            result => result,
+        }
+    }
+
+    fn selected(&self) -> Option<&GroupVo> {
+        self.state.selected().and_then(|index| self.groups.get(index))
+    }
+
+    /// 打开成员列表时移动成员光标，否则移动群聊光标
+    fn select(&mut self, step: i32) {
+        let state = if self.members.is_some() {
+            &mut self.member_state
+        } else {
+            &mut self.state
+        };
+        if step > 0 {
+            state.select_next();
+        } else {
+            state.select_previous();
+        }
+    }
+
+    fn open_members(&mut self) -> Result<()> {
+        let Some(gid) = self.selected().map(|group| group.gid) else {
+            return Ok(());
+        };
+        let mut members = fetch_members(gid)?;
+        for member in &mut members {
+            member.name = self.contacts.display_name(member.uid, &member.name);
+        }
+        sort_members(&mut members);
+        self.members = Some(members);
+        self.member_state = ListState::default();
+        Ok(())
+    }
+
+    /// 重新加载成员列表，光标位置不变
+    fn open_members_keep_cursor(&mut self) -> Result<()> {
+        let selected = self.member_state.selected();
+        self.open_members()?;
+        self.member_state.select(selected);
+        Ok(())
+    }
+
+    /// 群主可以移除任何人，管理员只能移除普通成员
+    fn remove_member(&mut self) -> Result<()> {
+        let Some(gid) = self.selected().map(|group| group.gid) else {
+            return Ok(());
+        };
+        let Some(role) = self.my_role()? else {
+            return Ok(());
+        };
+        let Some(member) = self.selected_member() else {
+            return Ok(());
+        };
+        if !role.can_remove(member.role) {
+            self.status = Some(format!("你是{role}，不能移除{}", member.role));
+            return Ok(());
+        }
+        remove_member(gid, member.uid)?;
+        self.status = Some(format!("已将{}移出群聊", member.name));
+        self.open_members()
+    }
+
+    fn leave(&mut self) -> Result<()> {
+        let Some((gid, name)) = self.selected().map(|group| (group.gid, group.name.clone())) else {
+            return Ok(());
+        };
+        if !self.confirm_leave {
+            self.confirm_leave = true;
+            self.status = Some(format!("再按一次 {} 退出{name}", keymap().keys(Screen::Groups, Action::Leave)));
+            return Ok(());
+        }
+        self.confirm_leave = false;
+        leave(gid)?;
+        self.status = Some(format!("已退出{name}"));
+        self.refresh()
+    }
+
+    /// 重新拉取群聊列表，保持选中的群不变
+    fn refresh(&mut self) -> Result<()> {
+        let selected = self.selected().map(|group| group.gid);
+        self.groups = fetch_groups()?;
+        let index = selected.and_then(|gid| self.groups.iter().position(|group| group.gid == gid));
+        self.state.select(index);
+        if index.is_none() {
+            self.members = None;
+        }
+        Ok(())
+    }
+
+    fn render_groups(&mut self, area: Rect, buf: &mut Buffer) {
+        let block = Block::new()
+            .title(Line::raw(format!("Groups ({})", self.groups.len())).centered())
+            .borders(Borders::TOP)
+            .border_set(symbols::border::EMPTY)
+            .border_style(theme().header)
+            .style(theme().row)
+            .padding(Padding::horizontal(1));
+        let uid = my_uid();
+        let items: Vec<ListItem> = self
+            .groups
+            .iter()
+            .map(|group| {
+                let mut spans = vec![Span::styled(group.name.clone(), theme().title)];
+                if group.is_owner(uid) {
+                    spans.push(Span::styled("  群主", theme().secondary));
+                }
+                ListItem::new(Line::from(spans))
+            })
+            .collect();
+        let list = List::new(items)
+            .block(block)
+            .highlight_style(theme().selected)
+            .highlight_symbol(">")
+            .highlight_spacing(HighlightSpacing::Always);
+        StatefulWidget::render(list, area, buf, &mut self.state);
+    }
+
+    fn render_members(&mut self, area: Rect, buf: &mut Buffer) {
+        let Some(members) = &self.members else {
+            return;
+        };
+        let announcement = self.selected().and_then(|group| group.announcement.clone()).filter(|content| !content.is_empty());
+        let block = Block::new()
+            .title(Line::raw(format!("Members ({})", members.len())).centered())
+            .borders(Borders::LEFT | Borders::TOP)
+            .border_set(symbols::border::EMPTY)
+            .border_style(theme().header)
+            .style(theme().row)
+            .padding(Padding::horizontal(1));
+        let items: Vec<ListItem> = members
+            .iter()
+            .map(|member| {
+                let mut spans = vec![Span::styled(member.name.clone(), theme().text)];
+                if member.role != GroupRole::Member {
+                    spans.push(Span::styled(format!("  {}", member.role), theme().secondary));
+                }
+                ListItem::new(Line::from(spans))
+            })
+            .collect();
+        let list = List::new(items)
+            .block(block)
+            .highlight_style(theme().selected)
+            .highlight_symbol(">")
+            .highlight_spacing(HighlightSpacing::Always);
+        // 公告置顶显示在成员列表上方
+        let area = match announcement {
+            Some(content) => {
+                let [announcement_area, list_area] =
+                    Layout::vertical([Constraint::Length(2), Constraint::Fill(1)]).areas(area);
+                Paragraph::new(Line::styled(format!("[公告] {content}"), theme().badge))
+                    .block(Block::new().borders(Borders::LEFT).border_set(symbols::border::EMPTY).padding(Padding::new(1, 1, 1, 0)))
+                    .render(announcement_area, buf);
+                list_area
+            }
+            None => area,
+        };
+        StatefulWidget::render(list, area, buf, &mut self.member_state);
+    }
+
+    /// 输入框，光标所在字符反色显示
+    fn render_input(&self, area: Rect, buf: &mut Buffer) {
+        let Some((editing, input)) = &self.editing else {
+            return;
+        };
+        Paragraph::new(input.line())
+            .style(theme().editing)
+            .block(Block::bordered().title(format!("{} | Enter 确认, Esc 取消", editing.title())))
+            .render(area, buf);
+    }
+}
+
+impl Widget for &mut Groups {
+    fn render(self, area: Rect, buf: &mut Buffer)
+    where
+        Self: Sized,
+    {
+        let input_height = if self.is_editing() { 3 } else { 0 };
+        let [main_area, input_area, status_area, footer_area] = Layout::vertical([
+            Constraint::Fill(1),
+            Constraint::Length(input_height),
+            Constraint::Length(1),
+            Constraint::Length(1),
+        ])
+            .areas(area);
+        if self.members.is_some() {
+            let [groups_area, members_area] =
+                Layout::horizontal([Constraint::Fill(1), Constraint::Fill(1)]).areas(main_area);
+            self.render_groups(groups_area, buf);
+            self.render_members(members_area, buf);
+        } else {
+            self.render_groups(main_area, buf);
+        }
+        self.render_input(input_area, buf);
+        if let Some(status) = &self.status {
+            Paragraph::new(status.as_str())
+                .style(theme().secondary)
+                .centered()
+                .render(status_area, buf);
+        }
+        Paragraph::new(keymap().footer(Screen::Groups))
+            .centered()
+            .render(footer_area, buf);
+    }
+}
+
+fn my_uid() -> i32 {
+    current_user().lock().unwrap().user.as_ref().map_or(0, |user| user.id)
+}
+
+pub(crate) fn authorized(request: RequestBuilder) -> RequestBuilder {
+    let token = current_user().lock().unwrap().token.clone().unwrap_or_default();
+    request.header("Authorization", format!("Bearer {}", token))
+}
+
+/// 发送请求，群已解散或已被移出群聊时返回 `GROUP_GONE`
+pub(crate) fn send(request: RequestBuilder) -> Result<Response> {
+    let res = authorized(request).send().map_err(|err| format_err!("Fail to Send Request: {}", err))?;
+    let status = res.status();
+    if status.is_success() {
+        Ok(res)
+    } else if is_gone(status.as_u16()) {
+        Err(format_err!(GROUP_GONE))
+    } else {
+        Err(format_err!("HTTP {}: {}", status, sanitize(&res.text().unwrap_or_default())))
+    }
+}
+
+pub(crate) fn fetch_groups() -> Result<Vec<GroupVo>> {
+    send(http_client().get(format!("{}/group", host())))?
+        .json::<Vec<GroupVo>>()
+        .map_err(|err| format_err!("Fail to Parse Groups: {}", err))
+}
+
+pub(crate) fn fetch_members(gid: i32) -> Result<Vec<GroupMember>> {
+    send(http_client().get(format!("{}/group/{gid}/member", host())))?
+        .json::<Vec<GroupMember>>()
+        .map_err(|err| format_err!("Fail to Parse Group Members: {}", err))
+}
+
+/// 好友列表，名称为用户名
+pub(crate) fn request_friends() -> Result<Vec<Friend>> {
+    let res = authorized(http_client().get(format!("{}/friend", host())))
+        .send()
+        .map_err(|err| format_err!("Fail to Get Friends: {}", err))?;
+    if !res.status().is_success() {
+        return Err(format_err!("Fail to Get Friends: HTTP {}", res.status()));
+    }
+    res.json::<Vec<Friend>>()
+        .map_err(|err| format_err!("Fail to Parse Friends: {}", err))
+}
+
+/// 好友列表，名称已替换为备注
+fn fetch_friends(contacts: &ContactBook) -> Result<Vec<Friend>> {
+    let mut friends = request_friends()?;
+    for friend in &mut friends {
+        friend.name = contacts.display_name(friend.id, &friend.name);
+    }
+    Ok(friends)
+}
+
+fn create_group(name: &str) -> Result<GroupVo> {
+    send(
+        http_client()
+            .post(format!("{}/group", host()))
+            .json(&serde_json::json!({ "name": name, "members": [] })),
+    )?
+    .json::<GroupVo>()
+    .map_err(|err| format_err!("Fail to Parse Group: {}", err))
+}
+
+fn rename(gid: i32, name: &str) -> Result<()> {
+    send(http_client().put(format!("{}/group/{gid}", host())).json(&serde_json::json!({ "name": name }))).map(|_| ())
+}
+
+fn invite(gid: i32, members: &[i32]) -> Result<()> {
+    send(
+        http_client()
+            .post(format!("{}/group/{gid}/member", host()))
+            .json(&serde_json::json!({ "members": members })),
+    )
+    .map(|_| ())
+}
+
+fn remove_member(gid: i32, uid: i32) -> Result<()> {
+    send(http_client().delete(format!("{}/group/{gid}/member/{uid}", host()))).map(|_| ())
+}
+
+/// 发布公告，内容为空时撤下公告
+fn announce(gid: i32, content: &str) -> Result<()> {
+    send(
+        http_client()
+            .put(format!("{}/group/{gid}/announcement", host()))
+            .json(&serde_json::json!({ "content": content })),
+    )
+    .map(|_| ())
+}
+
+fn set_role(gid: i32, uid: i32, role: GroupRole) -> Result<()> {
+    send(
+        http_client()
+            .put(format!("{}/group/{gid}/member/{uid}/role", host()))
+            .json(&serde_json::json!({ "role": role })),
+    )
+    .map(|_| ())
+}
+
+fn leave(gid: i32) -> Result<()> {
+    send(http_client().post(format!("{}/group/{gid}/leave", host()))).map(|_| ())
+}
@@ -0,0 +1,47 @@
+use crate::keymap::{keymap, Screen};
+use ratatui::buffer::Buffer;
+use ratatui::layout::Rect;
+use crate::theme::theme;
+use ratatui::style::{Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, Clear, Paragraph, Widget, Wrap};
+
+/// 快捷键帮助弹窗，内容由当前生效的快捷键配置生成
+pub(crate) struct Help {
+    pub(crate) screens: Vec<Screen>,
+}
+
+impl Widget for &Help {
+    fn render(self, area: Rect, buf: &mut Buffer)
+    where
+        Self: Sized,
+    {
+        let keymap = keymap();
+        let sections: Vec<_> = self.screens.iter().map(|screen| (screen, keymap.help(*screen))).collect();
+        let keys_width = sections
+            .iter()
+            .flat_map(|(_, help)| help.iter().map(|(keys, _)| keys.chars().count()))
+            .max()
+            .unwrap_or(0);
+
+        let mut lines = Vec::new();
+        for (screen, help) in sections {
+            if !lines.is_empty() {
+                lines.push(Line::raw(""));
+            }
+            lines.push(Line::styled(screen.title(), Style::default().add_modifier(Modifier::BOLD)));
+            for (keys, description) in help {
+                lines.push(Line::from(vec![
+                    Span::styled(format!("  {keys:<keys_width$}  "), theme().key),
+                    Span::raw(description),
+                ]));
+            }
+        }
+
+        Clear.render(area, buf);
+        Paragraph::new(lines)
+            .block(Block::default().title("Help | ? or Esc to close").borders(Borders::ALL))
+            .wrap(Wrap { trim: false })
+            .render(area, buf);
+    }
+}
@@ -1,28 +1,83 @@
 use crate::contacts::Contacts;
+use crate::groups::Groups;
+use crate::help::Help;
+use crate::keymap::{keymap, Action, Screen};
 use crate::me::Me;
-use crate::recent_chat::RecentChat;
-use crate::token::CURRENT_USER;
-use crate::{centered_rect, ui};
-use color_eyre::owo_colors::OwoColorize;
+use crate::recent_chat::{unread_badge, RecentChat};
+use crate::theme::theme;
+use crate::login::{resume, save_session};
+use crate::token::{current_user, switch_profile};
+use crate::{centered_rect, event_stream, ui};
 use color_eyre::Result;
-use crossterm::event;
+use common::message::Message;
+use common::notify::Notifier;
+use common::profile::Profile;
+use common::unread::badge;
 use crossterm::event::{Event, KeyCode, KeyEventKind};
+use crossterm::terminal::SetTitle;
+use crossterm::{event, execute};
 use ratatui::layout::{Constraint, Direction, Layout, Rect};
-use ratatui::style::{Color, Modifier, Style};
+use ratatui::style::Style;
+use ratatui::text::{Line, Span};
 use ratatui::widgets::{Block, Borders, Paragraph};
 use ratatui::{DefaultTerminal, Frame};
+use std::io::stdout;
+use std::time::Duration;
+
+/// 终端标题
+const TITLE: &str = "chat-cli";
+/// 等待键盘事件的超时时间，超时后处理消息流
+const POLL_PERIOD: Duration = Duration::from_millis(200);
 
 #[derive(Eq, PartialEq, Clone)]
 enum Menu {
     RecentChat,
     Contacts,
+    Groups,
     Me,
 }
 
+impl Menu {
+    fn next(&self) -> Self {
+        match self {
+            Menu::RecentChat => Menu::Contacts,
+            Menu::Contacts => Menu::Groups,
+            Menu::Groups | Menu::Me => Menu::Me,
+        }
+    }
+
+    fn prev(&self) -> Self {
+        match self {
+            Menu::RecentChat | Menu::Contacts => Menu::RecentChat,
+            Menu::Groups => Menu::Contacts,
+            Menu::Me => Menu::Groups,
+        }
+    }
+
+    /// 帮助弹窗中展示的快捷键页面
+    fn screens(&self) -> Vec<Screen> {
+        match self {
+            Menu::RecentChat => vec![Screen::Home, Screen::RecentChat],
+            Menu::Contacts => vec![Screen::Home, Screen::Contacts],
+            Menu::Groups => vec![Screen::Home, Screen::Groups],
+            Menu::Me => vec![Screen::Home, Screen::Me],
+        }
+    }
+}
+
 pub struct Home {
     selected_menu: Menu,
     error_message: Option<String>,
-    current_mode: CurrentMode
+    current_mode: CurrentMode,
+    recent_chat: RecentChat,
+    contacts: Contacts,
+    groups: Groups,
+    me: Me,
+    /// 终端标题中显示的未读数，以及是否有人@我
+    title_unread: Option<(u32, bool)>,
+    show_help: bool,
+    /// 其他会话的新消息通知和状态栏
+    notifier: Notifier,
 }
 
 pub(crate) enum CurrentMode {
@@ -31,66 +86,175 @@ pub(crate) enum CurrentMode {
 }
 
 impl Home {
-    pub(crate) fn new() -> Self {
-        Self { selected_menu: Menu::RecentChat, error_message: None, current_mode: CurrentMode::Normal }
+    pub(crate) fn new() -> Result<Self> {
+        Ok(Self {
+            selected_menu: Menu::RecentChat,
+            error_message: None,
+            current_mode: CurrentMode::Normal,
+            recent_chat: RecentChat::new()?,
+            contacts: Contacts::new()?,
+            groups: Groups::new()?,
+            me: Me::new()?,
+            title_unread: None,
+            show_help: false,
+            notifier: Notifier::default(),
+        })
     }
-    // TODO 最近聊天页面
+
     pub fn run(&mut self, terminal: &mut DefaultTerminal) -> Result<()> {
+        let mut messages = event_stream::subscribe();
         loop {
             terminal.draw(|f| self.draw(f))?;
-            if let Event::Key(key) = event::read()? {
+            self.update_title()?;
+            // 处理消息流推送的新消息
+            while let Ok(message) = messages.try_recv() {
+                match message {
+                    Message::ChatMessage(chat_message) => match self.recent_chat.on_message(&chat_message) {
+                        // 免打扰的会话不通知
+                        Ok(Some(notification)) => self.notifier.notify(&notification),
+                        Ok(None) => {}
+                        Err(err) => self.error_message = Some(err.to_string()),
+                    },
+                    Message::Typing(typing) => self.recent_chat.on_typing(&typing),
+                    Message::ReadIndex(read) => self.recent_chat.on_read(&read),
+                    Message::Reaction(reaction) => self.recent_chat.on_reaction(&reaction),
+                    Message::Heartbeat(_) => {}
+                }
+            }
+            // 重发发送失败的消息
+            if let Err(err) = self.recent_chat.flush_outbox() {
+                self.error_message = Some(err.to_string());
+            }
+            if !event::poll(POLL_PERIOD)? {
+                continue;
+            }
+            let event = event::read()?;
+            // 粘贴和输入法一次提交的文字
+            if let Event::Paste(text) = &event {
+                if self.selected_menu == Menu::RecentChat && !self.show_help {
+                    self.recent_chat.paste(text);
+                }
+                continue;
+            }
+            if let Event::Key(key) = event {
                 if key.kind != KeyEventKind::Press {
                     continue;
                 }
-                match self.selected_menu {
-                    Menu::RecentChat => match key.code {
-                        KeyCode::Char('q') => {
-                            let mut mutex_guard = CURRENT_USER.lock().unwrap();
-                            mutex_guard.user = None;
-                            mutex_guard.token = None;
-                            return Ok(());
-                        }
-                        KeyCode::Right => {
-                            self.selected_menu = Menu::Contacts;
-                        }
-                        _ => {}
-                    },
-                    Menu::Contacts => match key.code {
-                        KeyCode::Char('q') => {
-                            let mut mutex_guard = CURRENT_USER.lock().unwrap();
-                            mutex_guard.user = None;
-                            mutex_guard.token = None;
-                            return Ok(());
-                        }
-                        KeyCode::Left => {
-                            self.selected_menu = Menu::RecentChat;
+                if self.show_help {
+                    if key.code == KeyCode::Esc || keymap().action(Screen::Home, &key) == Some(Action::Help) {
+                        self.show_help = false;
+                    }
+                    continue;
+                }
+                if let CurrentMode::Alerting = self.current_mode {
+                    if key.code == KeyCode::Esc {
+                        self.error_message = None;
+                        self.current_mode = CurrentMode::Normal;
+                    }
+                    continue;
+                }
+                // 输入群名称、聊天内容时不响应页面快捷键
+                if self.selected_menu == Menu::Groups && self.groups.is_editing() {
+                    if let Err(err) = self.groups.handle_key(key) {
+                        self.error_message = Some(err.to_string());
+                    }
+                    continue;
+                }
+                if self.selected_menu == Menu::RecentChat && self.recent_chat.is_chatting() {
+                    if let Err(err) = self.recent_chat.handle_key(key) {
+                        self.error_message = Some(err.to_string());
+                    }
+                    continue;
+                }
+                match keymap().action(Screen::Home, &key) {
+                    Some(Action::Quit) => {
+                        let session = current_user();
+                        let mut mutex_guard = session.lock().unwrap();
+                        mutex_guard.user = None;
+                        mutex_guard.token = None;
+                        save_session(&mutex_guard);
+                        execute!(stdout(), SetTitle(TITLE))?;
+                        return Ok(());
+                    }
+                    Some(Action::Help) => self.show_help = true,
+                    Some(Action::NextTab) => self.selected_menu = self.selected_menu.next(),
+                    Some(Action::PrevTab) => self.selected_menu = self.selected_menu.prev(),
+                    _ => match self.selected_menu {
+                        Menu::RecentChat => {
+                            if let Err(err) = self.recent_chat.handle_key(key) {
+                                self.error_message = Some(err.to_string());
+                            }
                         }
-                        KeyCode::Right => {
-                            self.selected_menu = Menu::Me;
+                        Menu::Me => {
+                            if let Some(profile) = self.me.handle_key(key) {
+                                if !self.switch(&profile) {
+                                    // 目标账号未登陆，回到登陆页
+                                    execute!(stdout(), SetTitle(TITLE))?;
+                                    return Ok(());
+                                }
+                                messages = event_stream::subscribe();
+                            }
                         }
-                        _ => {}
-                    }
-                    Menu::Me => match key.code {
-                        KeyCode::Char('q') => {
-                            let mut mutex_guard = CURRENT_USER.lock().unwrap();
-                            mutex_guard.user = None;
-                            mutex_guard.token = None;
-                            return Ok(());
+                        Menu::Contacts => {
+                            if let Err(err) = self.contacts.handle_key(key) {
+                                self.error_message = Some(err.to_string());
+                            }
                         }
-                        KeyCode::Left => {
-                            self.selected_menu = Menu::Contacts;
+                        Menu::Groups => {
+                            if let Err(err) = self.groups.handle_key(key) {
+                                self.error_message = Some(err.to_string());
+                            }
                         }
-                        _ => {}
-                    }
+                    },
                 }
             }
         }
     }
 
+    /// 切换账号，返回目标账号是否已登陆
+    fn switch(&mut self, profile: &Profile) -> bool {
+        switch_profile(profile);
+        if !resume() {
+            return false;
+        }
+        // 重新加载目标账号的数据，其他账号的会话保持不变
+        match RecentChat::new().and_then(|recent_chat| Ok((recent_chat, Contacts::new()?, Groups::new()?, Me::new()?))) {
+            Ok((recent_chat, contacts, groups, me)) => {
+                self.recent_chat = recent_chat;
+                self.contacts = contacts;
+                self.groups = groups;
+                self.me = me;
+            }
+            Err(err) => self.error_message = Some(err.to_string()),
+        }
+        self.title_unread = None;
+        true
+    }
+
+    /// 在终端标题上显示未读总数，有人@我时优先提示
+    fn update_title(&mut self) -> Result<()> {
+        let unread = self.recent_chat.total_unread();
+        let mentioned = self.recent_chat.has_mentions();
+        self.notifier.update_status(unread, self.recent_chat.mention_count());
+        if self.title_unread != Some((unread, mentioned)) {
+            let mut title = if unread > 0 {
+                format!("({}) {TITLE}", badge(unread))
+            } else {
+                TITLE.to_string()
+            };
+            if mentioned {
+                title = format!("[有人@我] {title}");
+            }
+            execute!(stdout(), SetTitle(title))?;
+            self.title_unread = Some((unread, mentioned));
+        }
+        Ok(())
+    }
+
     fn draw(&mut self, frame: &mut Frame) {
         let block = Block::default()
             .borders(Borders::NONE)
-            .style(Style::default().bg(Color::DarkGray));
+            .style(theme().background);
         let area = ui::total_area(frame);
         frame.render_widget(block, area);
 
@@ -103,21 +267,16 @@ impl Home {
             .areas(area);
         match self.selected_menu {
             Menu::RecentChat => {
-                let res = RecentChat::new();
-                match res {
-                    Ok(mut chat) => {
-                        frame.render_widget(&mut chat, content_area)
-                    }
-                    Err(err) => { self.error_message = Some(err.to_string()); }
-                }
+                frame.render_widget(&mut self.recent_chat, content_area);
             }
             Menu::Contacts => {
-                let mut contacts = Contacts {};
-                frame.render_widget(&mut contacts, content_area);
+                frame.render_widget(&mut self.contacts, content_area);
+            }
+            Menu::Groups => {
+                frame.render_widget(&mut self.groups, content_area);
             }
             Menu::Me => {
-                let mut me = Me {};
-                frame.render_widget(&mut me, content_area);
+                frame.render_widget(&mut self.me, content_area);
             }
         }
         self.menu_render(frame, manu_area);
@@ -126,23 +285,37 @@ impl Home {
         // 绘制错误消息
         if let Some(message) = &self.error_message {
             let error_paragraph = Paragraph::new(message.as_str())
-                .style(Style::default().fg(Color::Red))
+                .style(theme().error)
                 .block(Block::default().title("Error | Esc to close this msg").borders(Borders::ALL));
             frame.render_widget(error_paragraph, error_area); // 选择合适的区域
             self.current_mode = CurrentMode::Alerting;
         }
+
+        if self.show_help {
+            let help = Help { screens: self.selected_menu.screens() };
+            frame.render_widget(&help, centered_rect(60, 60, area));
+        }
     }
 
     fn menu_render(&mut self, frame: &mut Frame, manu_area: Rect) {
-        let manu_border = Block::default().borders(Borders::NONE).style(Style::default().bg(Color::Gray));
+        let manu_border = Block::default().borders(Borders::NONE).style(theme().menu);
         frame.render_widget(manu_border, manu_area);
 
-        let [recent_chat_area, contacts_area, me_area] = Layout::default()
+        let [recent_chat_area, contacts_area, groups_area, me_area] = Layout::default()
             .direction(Direction::Horizontal)
-            .constraints([Constraint::Ratio(1, 3); 3])
+            .constraints([Constraint::Ratio(1, 4); 4])
             .areas(manu_area);
 
-        let recent_chat_text = Paragraph::new("RecentChat")
+        let unread = self.recent_chat.total_unread();
+        let mut recent_chat_title = if unread > 0 {
+            Line::from(vec![Span::raw("RecentChat "), unread_badge(Some(unread))])
+        } else {
+            Line::from("RecentChat")
+        };
+        if self.recent_chat.has_mentions() {
+            recent_chat_title.push_span(Span::styled(" @", theme().mention));
+        }
+        let recent_chat_text = Paragraph::new(recent_chat_title)
             .style(self.chose_manu_paragraph(Menu::RecentChat))
             .block(self.chose_manu_block(Menu::RecentChat))
             .centered();
@@ -150,6 +323,10 @@ impl Home {
             .style(self.chose_manu_paragraph(Menu::Contacts))
             .block(self.chose_manu_block(Menu::Contacts))
             .centered();
+        let groups_text = Paragraph::new("Groups")
+            .style(self.chose_manu_paragraph(Menu::Groups))
+            .block(self.chose_manu_block(Menu::Groups))
+            .centered();
         let me_text = Paragraph::new("Me")
             .style(self.chose_manu_paragraph(Menu::Me))
             .block(self.chose_manu_block(Menu::Me))
@@ -157,22 +334,30 @@ impl Home {
 
         frame.render_widget(recent_chat_text, centered_rect(80, 90, recent_chat_area));
         frame.render_widget(contacts_text, centered_rect(80, 90, contacts_area));
+        frame.render_widget(groups_text, centered_rect(80, 90, groups_area));
         frame.render_widget(me_text, centered_rect(80, 90, me_area));
     }
 
     fn chose_manu_paragraph(&self, current_menu: Menu) -> Style {
         if self.selected_menu == current_menu {
-            Style::default().fg(Color::Black).add_modifier(Modifier::BOLD | Modifier::ITALIC)
+            theme().menu_selected
         } else {
-            Style::default().fg(Color::White)
+            theme().menu
         }
     }
-    fn chose_manu_block(&self, current_menu: Menu) -> Block {
+    fn chose_manu_block(&self, current_menu: Menu) -> Block<'_> {
         if self.selected_menu == current_menu {
-            Block::new().borders(Borders::ALL).style(Style::default().fg(Color::LightGreen).bg(Color::LightGreen))
+            Block::new().borders(Borders::ALL).style(hidden_border(theme().menu_selected))
         } else {
-            Block::new().borders(Borders::ALL).style(Style::default().fg(Color::Gray).bg(Color::Gray))
+            Block::new().borders(Borders::ALL).style(hidden_border(theme().menu))
         }
     }
 }
 
+/// 边框与背景同色，只显示色块
+fn hidden_border(style: Style) -> Style {
+    match style.bg {
+        Some(bg) => Style::new().fg(bg).bg(bg),
+        None => Style::new(),
+    }
+}
@@ -0,0 +1,584 @@
+use color_eyre::eyre::format_err;
+use color_eyre::Result;
+use common::config::{config_dir, settings};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use indexmap::IndexMap;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+/// 当前生效的快捷键配置
+static KEYMAP: OnceLock<Keymap> = OnceLock::new();
+
+/// 加载快捷键配置，程序启动时调用一次
+pub(crate) fn init() -> Result<()> {
+    let keymap = Keymap::load()?;
+    KEYMAP
+        .set(keymap)
+        .map_err(|_| format_err!("keymap already initialized"))
+}
+
+pub(crate) fn keymap() -> &'static Keymap {
+    KEYMAP.get_or_init(|| Keymap::preset(Preset::Default))
+}
+
+/// 快捷键所属页面
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub(crate) enum Screen {
+    Login,
+    Home,
+    RecentChat,
+    Contacts,
+    Groups,
+    Me,
+}
+
+impl Screen {
+    pub(crate) fn title(&self) -> &'static str {
+        match self {
+            Screen::Login => "Login",
+            Screen::Home => "Home",
+            Screen::RecentChat => "RecentChat",
+            Screen::Contacts => "Contacts",
+            Screen::Groups => "Groups",
+            Screen::Me => "Me",
+        }
+    }
+}
+
+/// 可绑定快捷键的操作
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum Action {
+    Quit,
+    Help,
+    Edit,
+    Submit,
+    NextTab,
+    PrevTab,
+    Up,
+    Down,
+    Top,
+    Bottom,
+    Open,
+    Close,
+    Switch,
+    Toggle,
+    ToggleAll,
+    Approve,
+    Reject,
+    Filter,
+    Members,
+    Create,
+    Invite,
+    Rename,
+    Remove,
+    Leave,
+    Announce,
+    Admin,
+    Mute,
+    Mentions,
+    Retry,
+}
+
+impl Action {
+    fn description(&self) -> &'static str {
+        match self {
+            Action::Quit => "quit",
+            Action::Help => "show help",
+            Action::Edit => "start editing",
+            Action::Submit => "login",
+            Action::NextTab => "next tab",
+            Action::PrevTab => "previous tab",
+            Action::Up => "move up",
+            Action::Down => "move down",
+            Action::Top => "go top",
+            Action::Bottom => "go bottom",
+            Action::Open => "open chat",
+            Action::Close => "close chat",
+            Action::Switch => "switch account",
+            Action::Toggle => "check",
+            Action::ToggleAll => "check all",
+            Action::Approve => "approve",
+            Action::Reject => "reject",
+            Action::Filter => "filter",
+            Action::Members => "show members",
+            Action::Create => "create group",
+            Action::Invite => "invite friends",
+            Action::Rename => "rename group",
+            Action::Remove => "remove member",
+            Action::Leave => "leave group",
+            Action::Announce => "edit announcement",
+            Action::Admin => "toggle admin",
+            Action::Mute => "mute/unmute",
+            Action::Mentions => "show mentions",
+            Action::Retry => "retry failed",
+        }
+    }
+}
+
+/// 内置的快捷键方案
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Preset {
+    Default,
+    Vim,
+    Emacs,
+}
+
+impl FromStr for Preset {
+    type Err = color_eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "default" => Ok(Preset::Default),
+            "vim" => Ok(Preset::Vim),
+            "emacs" => Ok(Preset::Emacs),
+            _ => Err(format_err!("unknown keymap `{s}`, expected default, vim or emacs")),
+        }
+    }
+}
+
+/// 单个按键，如 `q`、`Ctrl-n`、`Enter`
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub(crate) struct KeyBinding {
+    code: KeyCode,
+    modifiers: KeyModifiers,
+}
+
+impl KeyBinding {
+    fn parse(s: &str) -> Result<Self> {
+        let mut modifiers = KeyModifiers::NONE;
+        let mut rest = s;
+        // 单个 `-` 本身也是一个按键
+        while let Some((modifier, key)) = rest.split_once('-').filter(|(_, key)| !key.is_empty()) {
+            modifiers |= match modifier.to_ascii_lowercase().as_str() {
+                "ctrl" | "c" => KeyModifiers::CONTROL,
+                "alt" | "m" => KeyModifiers::ALT,
+                "shift" | "s" => KeyModifiers::SHIFT,
+                _ => return Err(format_err!("unknown modifier `{modifier}` in key `{s}`")),
+            };
+            rest = key;
+        }
+        let code = match rest.to_ascii_lowercase().as_str() {
+            "enter" => KeyCode::Enter,
+            "esc" => KeyCode::Esc,
+            "tab" => KeyCode::Tab,
+            "backtab" => KeyCode::BackTab,
+            "backspace" => KeyCode::Backspace,
+            "delete" => KeyCode::Delete,
+            "insert" => KeyCode::Insert,
+            "space" => KeyCode::Char(' '),
+            "up" => KeyCode::Up,
+            "down" => KeyCode::Down,
+            "left" => KeyCode::Left,
+            "right" => KeyCode::Right,
+            "home" => KeyCode::Home,
+            "end" => KeyCode::End,
+            "pageup" => KeyCode::PageUp,
+            "pagedown" => KeyCode::PageDown,
+            key if key.len() > 1 && key.starts_with('f') => {
+                let n = key[1..].parse::<u8>().map_err(|_| format_err!("unknown key `{s}`"))?;
+                KeyCode::F(n)
+            }
+            _ => {
+                let mut chars = rest.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => KeyCode::Char(c),
+                    _ => return Err(format_err!("unknown key `{s}`")),
+                }
+            }
+        };
+        Ok(Self { code, modifiers })
+    }
+
+    fn matches(&self, key: &KeyEvent) -> bool {
+        // 大写字母、符号等字符本身已包含Shift，忽略Shift修饰键
+        let modifiers = match key.code {
+            KeyCode::Char(_) => key.modifiers - KeyModifiers::SHIFT,
+            _ => key.modifiers,
+        };
+        let expected = match self.code {
+            KeyCode::Char(_) => self.modifiers - KeyModifiers::SHIFT,
+            _ => self.modifiers,
+        };
+        self.code == key.code && expected == modifiers
+    }
+}
+
+impl Display for KeyBinding {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.modifiers.contains(KeyModifiers::CONTROL) {
+            write!(f, "Ctrl-")?;
+        }
+        if self.modifiers.contains(KeyModifiers::ALT) {
+            write!(f, "Alt-")?;
+        }
+        if self.modifiers.contains(KeyModifiers::SHIFT) {
+            write!(f, "Shift-")?;
+        }
+        match self.code {
+            KeyCode::Up => write!(f, "↑"),
+            KeyCode::Down => write!(f, "↓"),
+            KeyCode::Left => write!(f, "←"),
+            KeyCode::Right => write!(f, "→"),
+            KeyCode::Char(' ') => write!(f, "Space"),
+            KeyCode::Char(c) => write!(f, "{c}"),
+            KeyCode::F(n) => write!(f, "F{n}"),
+            code => write!(f, "{code:?}"),
+        }
+    }
+}
+
+/// 快捷键配置文件，在 config.toml 中 `ui.keymap` 指定的方案基础上覆盖按键
+#[derive(Deserialize, Default)]
+struct KeymapFile {
+    #[serde(default)]
+    login: HashMap<Action, Vec<String>>,
+    #[serde(default)]
+    home: HashMap<Action, Vec<String>>,
+    #[serde(default)]
+    recent_chat: HashMap<Action, Vec<String>>,
+    #[serde(default)]
+    contacts: HashMap<Action, Vec<String>>,
+    #[serde(default)]
+    groups: HashMap<Action, Vec<String>>,
+    #[serde(default)]
+    me: HashMap<Action, Vec<String>>,
+}
+
+/// 各页面操作与按键的映射
+pub(crate) struct Keymap {
+    screens: IndexMap<Screen, IndexMap<Action, Vec<KeyBinding>>>,
+}
+
+type Bindings = &'static [(Action, &'static [&'static str])];
+
+const DEFAULT_LOGIN: Bindings = &[
+    (Action::Quit, &["q", "Esc"]),
+    (Action::Edit, &["e"]),
+    (Action::Submit, &["Enter"]),
+    (Action::Help, &["?"]),
+];
+const DEFAULT_HOME: Bindings = &[
+    (Action::Quit, &["q"]),
+    (Action::NextTab, &["Right"]),
+    (Action::PrevTab, &["Left"]),
+    (Action::Help, &["?"]),
+];
+const DEFAULT_RECENT_CHAT: Bindings = &[
+    (Action::Down, &["Down"]),
+    (Action::Up, &["Up"]),
+    (Action::Top, &["g", "Home"]),
+    (Action::Bottom, &["G", "End"]),
+    (Action::Open, &["Enter"]),
+    (Action::Close, &["Esc"]),
+    (Action::Mute, &["m"]),
+    (Action::Mentions, &["@"]),
+    (Action::Retry, &["r"]),
+];
+const DEFAULT_CONTACTS: Bindings = &[
+    (Action::Down, &["Down"]),
+    (Action::Up, &["Up"]),
+    (Action::Toggle, &["Space"]),
+    (Action::ToggleAll, &["a"]),
+    (Action::Approve, &["y"]),
+    (Action::Reject, &["n"]),
+    (Action::Filter, &["f"]),
+];
+const DEFAULT_GROUPS: Bindings = &[
+    (Action::Down, &["Down"]),
+    (Action::Up, &["Up"]),
+    (Action::Members, &["Enter"]),
+    (Action::Close, &["Esc"]),
+    (Action::Create, &["c"]),
+    (Action::Invite, &["i"]),
+    (Action::Rename, &["r"]),
+    (Action::Remove, &["d"]),
+    (Action::Leave, &["x"]),
+    (Action::Announce, &["a"]),
+    (Action::Admin, &["A"]),
+];
+const DEFAULT_ME: Bindings = &[
+    (Action::Down, &["Down"]),
+    (Action::Up, &["Up"]),
+    (Action::Switch, &["Enter"]),
+];
+
+const VIM_HOME: Bindings = &[
+    (Action::Quit, &["q"]),
+    (Action::NextTab, &["Tab", "Right"]),
+    (Action::PrevTab, &["BackTab", "Left"]),
+    (Action::Help, &["?"]),
+];
+const VIM_RECENT_CHAT: Bindings = &[
+    (Action::Down, &["j", "Down"]),
+    (Action::Up, &["k", "Up"]),
+    (Action::Top, &["g", "Home"]),
+    (Action::Bottom, &["G", "End"]),
+    (Action::Open, &["l", "Enter"]),
+    (Action::Close, &["h", "Esc"]),
+    (Action::Mute, &["m"]),
+    (Action::Mentions, &["@"]),
+    (Action::Retry, &["r"]),
+];
+const VIM_CONTACTS: Bindings = &[
+    (Action::Down, &["j", "Down"]),
+    (Action::Up, &["k", "Up"]),
+    (Action::Toggle, &["Space", "x"]),
+    (Action::ToggleAll, &["a"]),
+    (Action::Approve, &["y"]),
+    (Action::Reject, &["n"]),
+    (Action::Filter, &["f"]),
+];
+const VIM_GROUPS: Bindings = &[
+    (Action::Down, &["j", "Down"]),
+    (Action::Up, &["k", "Up"]),
+    (Action::Members, &["l", "Enter"]),
+    (Action::Close, &["h", "Esc"]),
+    (Action::Create, &["c"]),
+    (Action::Invite, &["i"]),
+    (Action::Rename, &["r"]),
+    (Action::Remove, &["d"]),
+    (Action::Leave, &["x"]),
+    (Action::Announce, &["a"]),
+    (Action::Admin, &["A"]),
+];
+const VIM_ME: Bindings = &[
+    (Action::Down, &["j", "Down"]),
+    (Action::Up, &["k", "Up"]),
+    (Action::Switch, &["l", "Enter"]),
+];
+
+const EMACS_LOGIN: Bindings = &[
+    (Action::Quit, &["Ctrl-q", "Esc"]),
+    (Action::Edit, &["e"]),
+    (Action::Submit, &["Enter"]),
+    (Action::Help, &["?"]),
+];
+const EMACS_HOME: Bindings = &[
+    (Action::Quit, &["Ctrl-q"]),
+    (Action::NextTab, &["Ctrl-f", "Right"]),
+    (Action::PrevTab, &["Ctrl-b", "Left"]),
+    (Action::Help, &["?"]),
+];
+const EMACS_RECENT_CHAT: Bindings = &[
+    (Action::Down, &["Ctrl-n", "Down"]),
+    (Action::Up, &["Ctrl-p", "Up"]),
+    (Action::Top, &["Alt-<", "Home"]),
+    (Action::Bottom, &["Alt->", "End"]),
+    (Action::Open, &["Enter"]),
+    (Action::Close, &["Ctrl-g", "Esc"]),
+    (Action::Mute, &["Alt-m"]),
+    (Action::Mentions, &["@"]),
+    (Action::Retry, &["Alt-r"]),
+];
+const EMACS_CONTACTS: Bindings = &[
+    (Action::Down, &["Ctrl-n", "Down"]),
+    (Action::Up, &["Ctrl-p", "Up"]),
+    (Action::Toggle, &["Space"]),
+    (Action::ToggleAll, &["Alt-a"]),
+    (Action::Approve, &["y"]),
+    (Action::Reject, &["n"]),
+    (Action::Filter, &["Alt-f"]),
+];
+const EMACS_GROUPS: Bindings = &[
+    (Action::Down, &["Ctrl-n", "Down"]),
+    (Action::Up, &["Ctrl-p", "Up"]),
+    (Action::Members, &["Enter"]),
+    (Action::Close, &["Ctrl-g", "Esc"]),
+    (Action::Create, &["Alt-c"]),
+    (Action::Invite, &["Alt-i"]),
+    (Action::Rename, &["Alt-r"]),
+    (Action::Remove, &["Alt-d"]),
+    (Action::Leave, &["Alt-x"]),
+    (Action::Announce, &["Alt-a"]),
+    (Action::Admin, &["Alt-A"]),
+];
+const EMACS_ME: Bindings = &[
+    (Action::Down, &["Ctrl-n", "Down"]),
+    (Action::Up, &["Ctrl-p", "Up"]),
+    (Action::Switch, &["Enter"]),
+];
+
+impl Keymap {
+    /// 快捷键配置文件路径：~/.config/chat-cli/keymap.toml
+    fn path() -> Option<PathBuf> {
+        config_dir().map(|dir| dir.join("keymap.toml"))
+    }
+
+    fn load() -> Result<Self> {
+        let preset = settings().ui.keymap.parse::<Preset>()?;
+        let file = match Self::path().filter(|path| path.exists()) {
+            None => KeymapFile::default(),
+            Some(path) => config::Config::builder()
+                .add_source(config::File::from(path.clone()))
+                .build()
+                .and_then(|config| config.try_deserialize::<KeymapFile>())
+                .map_err(|err| format_err!("invalid keymap {}: {}", path.display(), err))?,
+        };
+        let mut keymap = Self::preset(preset);
+        for (screen, overrides) in [
+            (Screen::Login, file.login),
+            (Screen::Home, file.home),
+            (Screen::RecentChat, file.recent_chat),
+            (Screen::Contacts, file.contacts),
+            (Screen::Groups, file.groups),
+            (Screen::Me, file.me),
+        ] {
+            let bindings = keymap.screens.entry(screen).or_default();
+            for (action, keys) in overrides {
+                let keys = keys.iter().map(|key| KeyBinding::parse(key)).collect::<Result<Vec<_>>>()?;
+                bindings.insert(action, keys);
+            }
+        }
+        keymap.check_conflicts()?;
+        Ok(keymap)
+    }
+
+    fn preset(preset: Preset) -> Self {
+        let (login, home, recent_chat, contacts, groups, me) = match preset {
+            Preset::Default => (DEFAULT_LOGIN, DEFAULT_HOME, DEFAULT_RECENT_CHAT, DEFAULT_CONTACTS, DEFAULT_GROUPS, DEFAULT_ME),
+            Preset::Vim => (DEFAULT_LOGIN, VIM_HOME, VIM_RECENT_CHAT, VIM_CONTACTS, VIM_GROUPS, VIM_ME),
+            Preset::Emacs => (EMACS_LOGIN, EMACS_HOME, EMACS_RECENT_CHAT, EMACS_CONTACTS, EMACS_GROUPS, EMACS_ME),
+        };
+        let to_bindings = |bindings: Bindings| {
+            bindings
+                .iter()
+                .map(|(action, keys)| {
+                    let keys = keys.iter().map(|key| KeyBinding::parse(key).expect("invalid preset key")).collect();
+                    (*action, keys)
+                })
+                .collect()
+        };
+        Self {
+            screens: IndexMap::from([
+                (Screen::Login, to_bindings(login)),
+                (Screen::Home, to_bindings(home)),
+                (Screen::RecentChat, to_bindings(recent_chat)),
+                (Screen::Contacts, to_bindings(contacts)),
+                (Screen::Groups, to_bindings(groups)),
+                (Screen::Me, to_bindings(me)),
+            ]),
+        }
+    }
+
+    /// 同一页面内一个按键只能绑定一个操作。主页的按键先于各个标签页处理，标签页中不能再绑定
+    fn check_conflicts(&self) -> Result<()> {
+        let home = self.screens.get(&Screen::Home);
+        for (screen, bindings) in &self.screens {
+            let mut seen: Vec<(KeyBinding, Action)> = Vec::new();
+            for (action, keys) in bindings {
+                for key in keys {
+                    if let Some((_, other)) = seen.iter().find(|(seen_key, _)| seen_key == key) {
+                        return Err(format_err!(
+                            "key `{key}` is bound to both `{other:?}` and `{action:?}` in {}",
+                            screen.title()
+                        ));
+                    }
+                    seen.push((*key, *action));
+                }
+            }
+            if matches!(screen, Screen::Login | Screen::Home) {
+                continue;
+            }
+            for (action, keys) in bindings {
+                for key in keys {
+                    if let Some((other, _)) = home.into_iter().flatten().find(|(_, home_keys)| home_keys.contains(key)) {
+                        return Err(format_err!(
+                            "key `{key}` of `{action:?}` in {} is already bound to `{other:?}` in Home",
+                            screen.title()
+                        ));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// 按键在指定页面对应的操作
+    pub(crate) fn action(&self, screen: Screen, key: &KeyEvent) -> Option<Action> {
+        self.screens.get(&screen)?.iter().find_map(|(action, keys)| {
+            keys.iter().any(|binding| binding.matches(key)).then_some(*action)
+        })
+    }
+
+    /// 操作绑定的按键，如 `g/Home`
+    pub(crate) fn keys(&self, screen: Screen, action: Action) -> String {
+        self.screens
+            .get(&screen)
+            .and_then(|bindings| bindings.get(&action))
+            .map(|keys| keys.iter().map(ToString::to_string).collect::<Vec<_>>().join("/"))
+            .unwrap_or_default()
+    }
+
+    /// 页面底部的帮助提示
+    pub(crate) fn footer(&self, screen: Screen) -> String {
+        self.help(screen)
+            .into_iter()
+            .map(|(keys, description)| format!("{keys} {description}"))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// 页面的全部快捷键及说明，按配置顺序排列
+    pub(crate) fn help(&self, screen: Screen) -> Vec<(String, &'static str)> {
+        self.screens
+            .get(&screen)
+            .map(|bindings| {
+                bindings
+                    .iter()
+                    .filter(|(_, keys)| !keys.is_empty())
+                    .map(|(action, _)| (self.keys(screen, *action), action.description()))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Action, KeyBinding, Keymap, Preset, Screen};
+    use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+    #[test]
+    fn test_parse_key() {
+        let key = KeyBinding::parse("Ctrl-n").unwrap();
+        assert!(key.matches(&KeyEvent::new(KeyCode::Char('n'), KeyModifiers::CONTROL)));
+        assert!(!key.matches(&KeyEvent::new(KeyCode::Char('n'), KeyModifiers::NONE)));
+        // 终端上报大写字母时可能带有Shift
+        let key = KeyBinding::parse("G").unwrap();
+        assert!(key.matches(&KeyEvent::new(KeyCode::Char('G'), KeyModifiers::SHIFT)));
+        assert_eq!(KeyBinding::parse("-").unwrap().code, KeyCode::Char('-'));
+        assert_eq!(KeyBinding::parse("Alt->").unwrap().to_string(), "Alt->");
+        assert!(KeyBinding::parse("Hyper-x").is_err());
+        assert!(KeyBinding::parse("foo").is_err());
+    }
+
+    #[test]
+    fn test_presets() {
+        for preset in [Preset::Default, Preset::Vim, Preset::Emacs] {
+            Keymap::preset(preset).check_conflicts().unwrap();
+        }
+        let vim = Keymap::preset(Preset::Vim);
+        let j = KeyEvent::new(KeyCode::Char('j'), KeyModifiers::NONE);
+        assert_eq!(vim.action(Screen::RecentChat, &j), Some(Action::Down));
+        assert_eq!(vim.keys(Screen::RecentChat, Action::Down), "j/↓");
+        assert_eq!(Keymap::preset(Preset::Default).action(Screen::RecentChat, &j), None);
+    }
+
+    #[test]
+    fn test_home_conflicts() {
+        // 主页先处理按键，标签页中绑定主页的按键永远不会触发
+        let mut keymap = Keymap::preset(Preset::Default);
+        keymap.screens[&Screen::RecentChat].insert(Action::Retry, vec![KeyBinding::parse("q").unwrap()]);
+        let err = keymap.check_conflicts().unwrap_err().to_string();
+        assert_eq!(err, "key `q` of `Retry` in RecentChat is already bound to `Quit` in Home");
+        // 登录页不经过主页
+        let mut keymap = Keymap::preset(Preset::Default);
+        keymap.screens[&Screen::Login].insert(Action::Help, vec![KeyBinding::parse("Left").unwrap()]);
+        keymap.check_conflicts().unwrap();
+    }
+}
@@ -1,19 +1,23 @@
-use crate::recent_chat::RecentChat;
-use crate::token::CURRENT_USER;
+use crate::help::Help;
+use crate::home::Home;
+use crate::keymap::{keymap, Action, Screen};
+use crate::theme::theme;
+use crate::token::{current_user, CurrentUser};
 use crate::user_input::Input;
 use crate::{centered_rect, token};
-use crate::{ui, HOST};
+use crate::{host, http_client, ui};
 use color_eyre::eyre::format_err;
 use color_eyre::Result;
 use crossterm::event;
 use crossterm::event::{Event, KeyCode, KeyEventKind};
 use ratatui::layout::{Constraint, Direction, Layout, Position, Rect};
-use ratatui::prelude::{Color, Line, Modifier, Style, Stylize, Text};
+use ratatui::prelude::{Line, Modifier, Style, Stylize, Text};
 use ratatui::widgets::{Block, Borders, Paragraph};
 use ratatui::{DefaultTerminal, Frame};
-use reqwest::blocking::Client;
 use reqwest::StatusCode;
+use common::profile::Profiles;
 use serde::Deserialize;
+use std::sync::{Arc, Mutex};
 use std::thread;
 use std::thread::sleep;
 use std::time::Duration;
@@ -24,6 +28,7 @@ pub struct Login {
     current_mode: CurrentMode,
     currently_editing: Option<CurrentlyEditing>,
     error_message: Option<String>, // 添加错误消息字段
+    show_help: bool,
 }
 
 enum CurrentMode {
@@ -34,35 +39,42 @@ enum CurrentMode {
 
 impl Login {
     pub fn run(mut self, mut terminal: DefaultTerminal) -> Result<()> {
+        // profile 中保存的 token 仍有效时直接进入首页
+        if resume() {
+            self.enter_home(&mut terminal)?;
+        }
         loop {
             terminal.draw(|f| self.draw(f))?;
             if let Event::Key(key) = event::read()? {
                 if key.kind != KeyEventKind::Press {
                     continue;
                 }
+                if self.show_help {
+                    if key.code == KeyCode::Esc || keymap().action(Screen::Login, &key) == Some(Action::Help) {
+                        self.show_help = false;
+                    }
+                    continue;
+                }
                 match self.current_mode {
-                    CurrentMode::Normal => match key.code {
-                        KeyCode::Char('q') | KeyCode::Esc => {
+                    CurrentMode::Normal => match keymap().action(Screen::Login, &key) {
+                        Some(Action::Quit) => {
                             return Ok(());
                         }
-                        KeyCode::Enter => {
+                        Some(Action::Submit) => {
                             match login(&self) {
-                                Ok(_) => {
-                                    // 登陆后进入最近聊天页面
-                                    // TODO home页面修改为tab 支持切换manu
-                                    let mut recent_chat = RecentChat::new()?;
-                                    recent_chat.run(&mut terminal)?;
-                                }
+                                // 登陆后进入首页
+                                Ok(_) => self.enter_home(&mut terminal)?,
                                 Err(err) => {
                                     self.error_message = Some(err.to_string());
                                     self.current_mode = CurrentMode::Alerting;
                                 }
                             }
                         }
-                        KeyCode::Char('e') => {
+                        Some(Action::Edit) => {
                             self.current_mode = CurrentMode::Editing;
                             self.currently_editing = Some(CurrentlyEditing::Username);
                         }
+                        Some(Action::Help) => self.show_help = true,
                         _ => {}
                     }
                     CurrentMode::Editing => {
@@ -107,20 +119,37 @@ impl Login {
         }
     }
 
+    /// 进入首页，退出登陆或切换到未登陆的账号后回到登陆页
+    fn enter_home(&mut self, terminal: &mut DefaultTerminal) -> Result<()> {
+        match Home::new() {
+            Ok(mut home) => home.run(terminal)?,
+            Err(err) => {
+                self.error_message = Some(err.to_string());
+                self.current_mode = CurrentMode::Alerting;
+            }
+        }
+        *self = Self {
+            error_message: self.error_message.take(),
+            ..Self::new()
+        };
+        Ok(())
+    }
+
     pub(crate) fn new() -> Self {
         Self {
-            username: Input::new(),
+            username: Input::with_text(profile_username().unwrap_or_default()),
             password: Input::new(),
             current_mode: CurrentMode::Normal,
             currently_editing: None,
             error_message: None, // 初始化错误消息
+            show_help: false,
         }
     }
 
     fn draw(&mut self, frame: &mut Frame) {
         let bg_block = Block::default()
             .borders(Borders::ALL)
-            .style(Style::default().fg(Color::Green));
+            .style(theme().border);
 
         let area = ui::total_area(frame);
         frame.render_widget(bg_block, area);
@@ -157,12 +186,14 @@ impl Login {
             CurrentMode::Normal => (
                 vec![
                     "Press ".into(),
-                    "q".bold(),
+                    keymap().keys(Screen::Login, Action::Quit).bold(),
                     " to exit, ".into(),
-                    "e".bold(),
+                    keymap().keys(Screen::Login, Action::Edit).bold(),
                     " to start editing, ".bold(),
-                    "Enter".bold(),
-                    " to Login.".bold(),
+                    keymap().keys(Screen::Login, Action::Submit).bold(),
+                    " to Login, ".bold(),
+                    keymap().keys(Screen::Login, Action::Help).bold(),
+                    " for help.".bold(),
                 ],
                 Style::default().add_modifier(Modifier::RAPID_BLINK),
             ),
@@ -191,16 +222,16 @@ impl Login {
 
         let user_name = Paragraph::new(self.username.input.as_str())
             .style(match self.current_mode {
-                CurrentMode::Editing if self.currently_editing == Some(CurrentlyEditing::Username) => Style::default().fg(Color::Yellow),
+                CurrentMode::Editing if self.currently_editing == Some(CurrentlyEditing::Username) => theme().editing,
                 _ => Style::default(),
             })
-            .block(Block::bordered().title("Username"));
+            .block(Block::bordered().title(username_title()));
         user_name_area = centered_rect(70, 100, user_name_area);
         frame.render_widget(user_name, user_name_area);
 
         let password = Paragraph::new(self.password.input.as_str())
             .style(match self.current_mode {
-                CurrentMode::Editing if self.currently_editing == Some(CurrentlyEditing::Password) => Style::default().fg(Color::Yellow),
+                CurrentMode::Editing if self.currently_editing == Some(CurrentlyEditing::Password) => theme().editing,
                 _ => Style::default(),
             })
             .block(Block::bordered().title("Password"));
@@ -233,11 +264,16 @@ impl Login {
         // 绘制错误消息
         if let Some(message) = &self.error_message {
             let error_paragraph = Paragraph::new(message.as_str())
-                .style(Style::default().fg(Color::Red))
+                .style(theme().error)
                 .block(Block::default().title("Error | Esc to close this msg").borders(Borders::ALL));
             frame.render_widget(error_paragraph, error_area); // 选择合适的区域
             self.current_mode = CurrentMode::Alerting;
         }
+
+        if self.show_help {
+            let help = Help { screens: vec![Screen::Login] };
+            frame.render_widget(&help, centered_rect(60, 60, area));
+        }
     }
 
     pub fn toggle_editing(&mut self) {
@@ -253,26 +289,73 @@ impl Login {
 }
 
 fn login(login: &Login) -> Result<()> {
-    match do_login(login) {
-        Ok(token) => {
-            let user = token::parse_token(token.as_str()).unwrap().claims;
-            {
-                let mut guard = CURRENT_USER.lock().unwrap();
-                guard.user = Some(user); // Create a longer-lived binding
-                guard.token = Option::from(token); // Create a longer-lived binding
-            }
-            renew();
-            Ok(())
-        }
-        Err(err) => {
-            Err(err)
+    let token = do_login(login)?;
+    let user = token::parse_token(token.as_str()).unwrap().claims;
+    let session = current_user();
+    {
+        let mut guard = session.lock().unwrap();
+        guard.user = Some(user); // Create a longer-lived binding
+        guard.token = Option::from(token); // Create a longer-lived binding
+        save_session(&guard);
+    }
+    renew(session);
+    Ok(())
+}
+
+/// 使用 profile 中保存的 token 恢复当前账号的会话，返回是否已登陆
+pub(crate) fn resume() -> bool {
+    let session = current_user();
+    let token = {
+        let guard = session.lock().unwrap();
+        if guard.token.is_some() {
+            return true;
         }
+        let Some(name) = guard.profile.clone() else {
+            return false;
+        };
+        let Some(token) = Profiles::load().ok().and_then(|profiles| profiles.get(&name)?.token.clone()) else {
+            return false;
+        };
+        token
+    };
+    let Ok(token_data) = token::parse_token(&token) else {
+        return false;
+    };
+    {
+        let mut guard = session.lock().unwrap();
+        guard.user = Some(token_data.claims);
+        guard.token = Some(token);
+    }
+    renew(session);
+    true
+}
+
+/// 登陆、刷新 token 或退出登陆后写回 profile，下次启动或切换账号时恢复
+pub(crate) fn save_session(session: &CurrentUser) {
+    if let Some(name) = &session.profile {
+        let username = session.user.as_ref().map(|user| user.name.clone());
+        let _ = Profiles::save_session(name, username, session.token.clone());
+    }
+}
+
+/// 当前 profile 的用户名，用于预填登陆框
+fn profile_username() -> Option<String> {
+    let name = current_user().lock().unwrap().profile.clone()?;
+    Profiles::load().ok()?.get(&name)?.username.clone()
+}
+
+fn username_title() -> String {
+    let session = current_user();
+    let guard = session.lock().unwrap();
+    match &guard.profile {
+        Some(name) => format!("Username | {name} {}", guard.server),
+        None => format!("Username | {}", guard.server),
     }
 }
 
 fn do_login(login: &Login) -> Result<String> {
-    let login_url = format!("{HOST}/token/login");
-    let client = Client::new();
+    let login_url = format!("{}/token/login", host());
+    let client = http_client();
     let response = client
         .post(&login_url)
         .json(&serde_json::json!({
@@ -303,22 +386,24 @@ fn do_login(login: &Login) -> Result<String> {
     }
 }
 
-fn renew() {
-    // 启动异步线程，定时刷新token过期时间
+fn renew(session: Arc<Mutex<CurrentUser>>) {
+    // 启动异步线程，定时刷新token过期时间，只刷新登陆时的账号
     thread::spawn(move || {
         loop {
-            let token = match CURRENT_USER.lock().unwrap().token.clone() {
-                None => { break; }
-                Some(token) => token
-            };
-            let token = format!("Bearer {token}");
             let renew_token_period = Duration::from_secs(60);
             sleep(renew_token_period);
-            let renew_url = format!("{HOST}/token/renew");
-            let client = Client::new();
+            let (server, token) = {
+                let guard = session.lock().unwrap();
+                match &guard.token {
+                    None => { break; }
+                    Some(token) => (guard.server.clone(), format!("Bearer {token}")),
+                }
+            };
+            let renew_url = format!("{server}/token/renew");
+            let client = http_client();
             let response = client
                 .patch(renew_url)
-                .header("Authorization", token.clone())
+                .header("Authorization", token)
                 .send();
             match response {
                 Ok(res) => {
@@ -326,9 +411,14 @@ fn renew() {
                         match res.text() {
                             Ok(t) => {
                                 let token_data = token::parse_token(t.as_str()).unwrap();
-                                let mut guard = CURRENT_USER.lock().unwrap();
+                                let mut guard = session.lock().unwrap();
+                                // 等待响应期间已退出登陆
+                                if guard.token.is_none() {
+                                    break;
+                                }
                                 guard.user = Some(token_data.claims);
                                 guard.token = Some(t);
+                                save_session(&guard);
                             }
                             Err(e) => {
                                 eprintln!("Failed to parse response: {}", e);
@@ -359,7 +449,7 @@ enum CurrentlyEditing {
 
 fn set_cursor(frame: &mut Frame, area: Rect, input: &Input) {
     frame.set_cursor_position(Position::new(
-        area.x + input.character_index as u16 + 1,
+        area.x + input.cursor_column() + 1,
         area.y + 1,
     ))
 }
@@ -3,25 +3,76 @@ mod user_input;
 mod token;
 mod home;
 mod ui;
-mod datetime;
 mod recent_chat;
+mod chat;
 mod contacts;
+mod groups;
 mod me;
+mod event_stream;
+mod keymap;
+mod help;
+mod theme;
+mod markdown;
+mod e2e;
 
 use crate::login::Login;
+use clap::Parser;
+use common::config;
+use common::config::settings;
 use color_eyre::owo_colors::OwoColorize;
 use color_eyre::{eyre::Context, Result};
+use crossterm::event::{DisableBracketedPaste, EnableBracketedPaste};
+use crossterm::execute;
 use ratatui::layout::{Constraint, Direction, Layout, Rect};
+use reqwest::blocking::Client;
+use std::io::stdout;
 
-#[cfg(feature = "release")]
-static HOST: &str = include_str!("../config/release");
-#[cfg(not(feature = "release"))]
-static HOST: &str = "http://localhost:3000";
+/// 当前账号所在的服务端地址
+pub(crate) fn host() -> String {
+    token::current_user().lock().unwrap().server.clone()
+}
+
+/// 按配置的超时时间创建HTTP客户端
+pub(crate) fn http_client() -> Client {
+    let timeout = &settings().timeout;
+    Client::builder()
+        .connect_timeout(timeout.connect())
+        .timeout(timeout.request())
+        .build()
+        .expect("unable to build http client")
+}
+
+/// 消息流是长连接，只限制连接超时
+pub(crate) fn stream_client() -> Client {
+    Client::builder()
+        .connect_timeout(settings().timeout.connect())
+        .timeout(None)
+        .build()
+        .expect("unable to build http client")
+}
+
+#[derive(Parser)]
+#[command(version = "0.1", about = "A Chat Client TUI", long_about = None)]
+struct Args {
+    /// 服务端地址，覆盖配置文件、环境变量和 profile
+    #[arg(long)]
+    server: Option<String>,
+    /// 使用的 profile，见 `chat-cli profile list`
+    #[arg(long)]
+    profile: Option<String>,
+}
 
 fn main() -> Result<()> {
+    let args = Args::parse();
     color_eyre::install()?;
+    config::init(args.server, args.profile).context("invalid config")?;
+    keymap::init()?;
+    theme::init()?;
     let terminal = ratatui::init();
+    // 粘贴的文字作为一个整体收到，不会被当作按键逐个处理
+    execute!(stdout(), EnableBracketedPaste)?;
     let app_result = Login::new().run(terminal).context("app loop failed");
+    execute!(stdout(), DisableBracketedPaste)?;
     ratatui::restore();
     app_result
 }
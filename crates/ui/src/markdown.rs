@@ -0,0 +1,58 @@
+use crate::theme::theme;
+use common::markdown::{code_box, parse, Block, Span as MdSpan};
+use ratatui::style::{Modifier, Style};
+use ratatui::text::{Line, Span};
+
+/// 按 Markdown 子集显示消息，`style` 为正文的样式；代码块加边框，引用和链接使用主题中的颜色
+pub(crate) fn lines(text: &str, style: Style) -> Vec<Line<'static>> {
+    let blocks = parse(text);
+    let mut lines = vec![];
+    let mut index = 0;
+    while index < blocks.len() {
+        match &blocks[index] {
+            Block::Text(spans) => lines.push(Line::from(styled(spans, style))),
+            Block::Quote(spans) => {
+                let mut quote = vec![Span::styled("│ ", theme().secondary)];
+                quote.extend(styled(spans, style.patch(theme().secondary)));
+                lines.push(Line::from(quote));
+            }
+            Block::Code(_) => {
+                // 连续的代码行放在同一个框里
+                let code = blocks[index..]
+                    .iter()
+                    .map_while(|block| match block {
+                        Block::Code(line) => Some(line.as_str()),
+                        _ => None,
+                    })
+                    .collect::<Vec<_>>();
+                index += code.len() - 1;
+                lines.extend(code_box(&code).into_iter().map(|line| Line::styled(line, theme().editing)));
+            }
+        }
+        index += 1;
+    }
+    lines
+}
+
+/// 终端界面的缓冲区不能写入 OSC 8 超链接，链接只加下划线，多数终端会自动识别其中的地址
+fn styled(spans: &[MdSpan], style: Style) -> Vec<Span<'static>> {
+    spans
+        .iter()
+        .map(|span| {
+            let mut span_style = if span.style.code { style.patch(theme().editing) } else { style };
+            if span.style.bold {
+                span_style = span_style.add_modifier(Modifier::BOLD);
+            }
+            if span.style.italic {
+                span_style = span_style.add_modifier(Modifier::ITALIC);
+            }
+            match &span.link {
+                Some(url) if *url != span.text => {
+                    Span::styled(format!("{} ({url})", span.text), span_style.patch(theme().key).add_modifier(Modifier::UNDERLINED))
+                }
+                Some(_) => Span::styled(span.text.clone(), span_style.patch(theme().key).add_modifier(Modifier::UNDERLINED)),
+                None => Span::styled(span.text.clone(), span_style),
+            }
+        })
+        .collect()
+}
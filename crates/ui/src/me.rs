@@ -1,14 +1,126 @@
+use crate::keymap::{keymap, Action, Screen};
+use crate::theme::theme;
+use crate::token::{current_user, is_logged_in};
+use color_eyre::eyre::format_err;
+use color_eyre::Result;
+use common::profile::{Profile, Profiles};
+use crossterm::event;
 use ratatui::buffer::Buffer;
-use ratatui::layout::Rect;
-use ratatui::widgets::Widget;
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::symbols;
+use ratatui::text::{Line, Span, Text};
+use ratatui::widgets::{Block, Borders, HighlightSpacing, List, ListItem, ListState, Padding, Paragraph, StatefulWidget, Widget};
 
-pub(crate) struct Me {}
+/// 当前账号信息和账号切换
+pub(crate) struct Me {
+    profiles: Vec<Profile>,
+    state: ListState,
+}
 
-impl Widget for &mut Me{
+impl Me {
+    pub(crate) fn new() -> Result<Self> {
+        let profiles = Profiles::load().map_err(|err| format_err!(err))?.profiles;
+        // 默认选中当前账号
+        let active = current_user().lock().unwrap().profile.clone();
+        let selected = profiles.iter().position(|profile| Some(&profile.name) == active.as_ref());
+        Ok(Self {
+            profiles,
+            state: ListState::default().with_selected(selected),
+        })
+    }
+
+    /// 处理按键，选择切换账号时返回目标 profile
+    pub(crate) fn handle_key(&mut self, key: event::KeyEvent) -> Option<Profile> {
+        match keymap().action(Screen::Me, &key) {
+            Some(Action::Down) => self.state.select_next(),
+            Some(Action::Up) => self.state.select_previous(),
+            Some(Action::Switch) => {
+                return self.state.selected().and_then(|i| self.profiles.get(i)).cloned();
+            }
+            _ => {}
+        }
+        None
+    }
+
+    fn render_account(area: Rect, buf: &mut Buffer) {
+        let session = current_user();
+        let guard = session.lock().unwrap();
+        let mut lines = vec![];
+        if let Some(user) = &guard.user {
+            lines.push(Line::from(format!("用户名: {}", user.name)));
+            lines.push(Line::from(format!("邮箱: {}", user.email.as_deref().unwrap_or("-"))));
+            lines.push(Line::from(format!("手机: {}", user.phone.as_deref().unwrap_or("-"))));
+        }
+        lines.push(Line::from(format!("服务端: {}", guard.server)));
+        let block = Block::new()
+            .title(Line::raw("Account").centered())
+            .borders(Borders::TOP)
+            .border_set(symbols::border::EMPTY)
+            .border_style(theme().header)
+            .style(theme().row)
+            .padding(Padding::horizontal(1));
+        Paragraph::new(Text::from(lines)).block(block).render(area, buf);
+    }
+
+    fn render_profiles(&mut self, area: Rect, buf: &mut Buffer) {
+        let block = Block::new()
+            .title(Line::raw("Profiles").centered())
+            .borders(Borders::TOP)
+            .border_set(symbols::border::EMPTY)
+            .border_style(theme().header)
+            .style(theme().row)
+            .padding(Padding::horizontal(1));
+        if self.profiles.is_empty() {
+            Paragraph::new("还没有 profile，使用 `chat-cli profile add` 添加")
+                .block(block)
+                .style(theme().secondary)
+                .render(area, buf);
+            return;
+        }
+        let active = current_user().lock().unwrap().profile.clone();
+        let items: Vec<ListItem> = self
+            .profiles
+            .iter()
+            .map(|profile| {
+                let marker = if Some(&profile.name) == active.as_ref() { "● " } else { "  " };
+                let mut spans = vec![
+                    Span::raw(marker),
+                    Span::styled(profile.name.clone(), theme().title),
+                    Span::styled(format!("  {}", profile.server), theme().secondary),
+                ];
+                if let Some(username) = &profile.username {
+                    spans.push(Span::raw(format!("  {username}")));
+                }
+                if is_logged_in(&profile.name) {
+                    spans.push(Span::styled("  已登陆", theme().secondary));
+                }
+                ListItem::new(Line::from(spans))
+            })
+            .collect();
+        let list = List::new(items)
+            .block(block)
+            .highlight_style(theme().selected)
+            .highlight_symbol(">")
+            .highlight_spacing(HighlightSpacing::Always);
+        StatefulWidget::render(list, area, buf, &mut self.state);
+    }
+}
+
+impl Widget for &mut Me {
     fn render(self, area: Rect, buf: &mut Buffer)
     where
-        Self: Sized
+        Self: Sized,
     {
-        todo!()
+        let [account_area, profiles_area, footer_area] = Layout::vertical([
+            Constraint::Length(6),
+            Constraint::Fill(1),
+            Constraint::Length(1),
+        ])
+            .areas(area);
+        Me::render_account(account_area, buf);
+        self.render_profiles(profiles_area, buf);
+        Paragraph::new(keymap().footer(Screen::Me))
+            .centered()
+            .render(footer_area, buf);
     }
-}
\ No newline at end of file
+}
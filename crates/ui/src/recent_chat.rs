@@ -1,88 +1,233 @@
-use crate::datetime::datetime_format;
-use crate::token::CURRENT_USER;
-use crate::ui::total_area;
-use crate::HOST;
+use crate::chat::{fetch_history, my_user, send_message, sender_names, Chat, ChatAction};
+use crate::keymap::{keymap, Action, Screen};
+use crate::theme::theme;
+use crate::token::current_user;
+use crate::{host, http_client};
 use chrono::{DateTime, Local};
 use color_eyre::eyre::format_err;
 use color_eyre::Result;
+use common::config::settings;
+use common::contact::{ContactBook, BLOCKED_MESSAGE};
+use common::datetime::{datetime_format, relative_time};
+use common::e2e::KeyStore;
+use common::group::{is_gone, GROUP_GONE};
+use common::mention::mentions;
+use common::message::{
+    ChatMessage, MessageTarget, MessageTargetGroup, MessageTargetUser, ReactionMessage, ReadIndexMessage, TypingMessage, UpdateReadIndex,
+};
+use common::notify::{should_notify, Notification};
+use common::outbox::Outbox;
+use common::text::{display_width, single_line, truncate};
+use common::unread::{badge, unread_format};
 use crossterm::event;
-use crossterm::event::{Event, KeyCode};
 use ratatui::buffer::Buffer;
 use ratatui::layout::{Constraint, Layout, Rect};
-use ratatui::prelude::{Color, Style};
-use ratatui::style::palette::material::{BLUE, GREEN};
-use ratatui::style::palette::tailwind::SLATE;
-use ratatui::style::{Modifier, Stylize};
-use ratatui::text::{Line, Span};
+use ratatui::prelude::Style;
+use ratatui::symbols;
+use ratatui::text::{Line, Span, Text};
 use ratatui::widgets::{Block, Borders, HighlightSpacing, List, ListItem, ListState, Padding, Paragraph, StatefulWidget, Widget, Wrap};
-use ratatui::{symbols, DefaultTerminal};
-use reqwest::blocking::Client;
 use serde::{Deserialize, Serialize};
-
-const TODO_HEADER_STYLE: Style = Style::new().fg(SLATE.c100).bg(BLUE.c800);
-const NORMAL_ROW_BG: Color = SLATE.c950;
-const ALT_ROW_BG_COLOR: Color = SLATE.c900;
-const SELECTED_STYLE: Style = Style::new().bg(SLATE.c800).add_modifier(Modifier::BOLD);
-const TEXT_FG_COLOR: Color = SLATE.c200;
-const COMPLETED_TEXT_FG_COLOR: Color = GREEN.c500;
+use std::cmp::Reverse;
+use std::collections::HashSet;
 
 pub(crate) struct RecentChat {
     error_message: Option<String>, // 添加错误消息字段
-    should_exit: bool,
     chat_list: ChatList,
+    /// 当前打开的会话，打开的会话收到消息时直接标记为已读
+    chat: Option<Chat>,
+    /// 当前账号的好友备注、黑名单和免打扰设置
+    contacts: ContactBook,
+    /// 有人@我且还没有打开的会话
+    mentioned: HashSet<MessageTarget>,
+    /// 提到我的消息，打开提及列表时不为空
+    mentions: Option<(Vec<Mention>, ListState)>,
+    /// 发送失败等待重发的消息
+    outbox: Outbox,
+    /// 端到端加密的密钥
+    keys: KeyStore,
+}
+
+/// 提到我的消息
+struct Mention {
+    target: MessageTarget,
+    /// 会话名称
+    conversation: String,
+    sender: String,
+    msg: String,
+    time: DateTime<Local>,
 }
 
 impl RecentChat {
     fn render_footer(area: Rect, buf: &mut Buffer) {
-        Paragraph::new("Use ↓↑ to move, →/Enter to change status, g/G or Home/End to go top/bottom.")
+        Paragraph::new(keymap().footer(Screen::RecentChat))
             .centered()
             .render(area, buf);
     }
 }
 
 struct ChatList {
-    items: Vec<ChatVo>, // TODO 需要异步刷新来获取最新消息
+    items: Vec<ChatVo>,
     state: ListState,
 }
 
 impl RecentChat {
     pub(crate) fn new() -> Result<Self> {
+        let uid = current_user().lock().unwrap().user.as_ref().map_or(0, |user| user.id);
+        let contacts = ContactBook::load(&host(), uid).map_err(|err| format_err!(err))?;
+        let mut outbox = Outbox::load(&host(), uid).map_err(|err| format_err!(err))?;
+        let keys = KeyStore::load(&host(), uid).map_err(|err| format_err!(err))?;
+        outbox.reveal(&keys);
         let chat_list = ChatList {
-            items: recent_chat()?,
+            items: recent_chat(&contacts)?,
             state: ListState::default(),
         };
+        let my_name = my_user().1;
+        let mentioned = chat_list
+            .items
+            .iter()
+            .filter(|chat_vo| chat_vo.unread() > 0 && mentions(chat_vo.msg(), &my_name))
+            .map(ChatVo::target)
+            .collect();
         Ok(Self {
             error_message: None,
-            should_exit: false,
             chat_list,
+            chat: None,
+            contacts,
+            mentioned,
+            mentions: None,
+            outbox,
+            keys,
         })
     }
 
-    pub fn run(mut self, terminal: &mut DefaultTerminal) -> Result<()> {
-        while !self.should_exit {
-            terminal.draw(|frame| {
-                let rect = total_area(frame);
-                frame.render_widget(&mut self, rect)
-            })?;
-            if let Event::Key(key) = event::read()? {
-                self.handle_key(key);
-            };
+    /// 打开会话后按键全部交给输入框
+    pub(crate) fn is_chatting(&self) -> bool {
+        self.chat.is_some()
+    }
+
+    /// 是否有人@我
+    pub(crate) fn has_mentions(&self) -> bool {
+        !self.mentioned.is_empty()
+    }
+
+    /// 有人@我的会话数
+    pub(crate) fn mention_count(&self) -> usize {
+        self.mentioned.len()
+    }
+
+    /// 粘贴的文字插入聊天输入框
+    pub(crate) fn paste(&mut self, text: &str) {
+        if let Some(chat) = &mut self.chat {
+            chat.paste(text);
+        }
+    }
+
+    pub(crate) fn handle_key(&mut self, key: event::KeyEvent) -> Result<()> {
+        if let Some(chat) = &mut self.chat {
+            match chat.handle_key(key, &mut self.outbox, &mut self.keys)? {
+                ChatAction::None => {}
+                ChatAction::Close => self.chat = None,
+                ChatAction::Mute => {
+                    let target = chat.target;
+                    let muted = !self.contacts.is_muted(target);
+                    self.contacts.set_muted(target, muted);
+                    self.contacts.save().map_err(|err| format_err!(err))?;
+                    chat.notify(if muted { "已开启免打扰" } else { "已关闭免打扰" }.to_string());
+                }
+                ChatAction::Retry => {
+                    let target = chat.target;
+                    match self.outbox.retry(target, Local::now().timestamp_millis()) {
+                        0 => chat.notify("没有发送失败的消息".to_string()),
+                        count => {
+                            chat.notify(format!("正在重发{count}条消息"));
+                            self.flush_outbox()?;
+                        }
+                    }
+                }
+            }
+            return Ok(());
+        }
+        if self.mentions.is_some() {
+            return self.handle_mentions_key(key);
+        }
+        match keymap().action(Screen::RecentChat, &key) {
+            Some(Action::Close) => self.chat_list.state.select(None),
+            Some(Action::Down) => self.select_next(),
+            Some(Action::Up) => self.select_previous(),
+            Some(Action::Top) => self.select_first(),
+            Some(Action::Bottom) => self.select_last(),
+            Some(Action::Open) => {
+                self.open_chat()?;
+            }
+            Some(Action::Mute) => self.toggle_mute()?,
+            Some(Action::Mentions) => self.open_mentions()?,
+            Some(Action::Retry) => self.retry()?,
+            _ => {}
         }
         Ok(())
     }
 
-    fn handle_key(&mut self, key: event::KeyEvent) {
-        match key.code {
-            KeyCode::Char('q') | KeyCode::Esc => self.should_exit = true,
-            KeyCode::Down => self.select_next(),
-            KeyCode::Up => self.select_previous(),
-            KeyCode::Char('g') | KeyCode::Home => self.select_first(),
-            KeyCode::Char('G') | KeyCode::End => self.select_last(),
-            KeyCode::Right | KeyCode::Enter => {
-                self.to_chat();
+    /// 提及列表中的按键：打开选中消息所在的会话
+    fn handle_mentions_key(&mut self, key: event::KeyEvent) -> Result<()> {
+        let Some((mentions, state)) = &mut self.mentions else {
+            return Ok(());
+        };
+        match keymap().action(Screen::RecentChat, &key) {
+            Some(Action::Down) => state.select_next(),
+            Some(Action::Up) => state.select_previous(),
+            Some(Action::Top) => state.select_first(),
+            Some(Action::Bottom) => state.select_last(),
+            Some(Action::Close) | Some(Action::Mentions) => self.mentions = None,
+            Some(Action::Open) => {
+                let Some(target) = state.selected().and_then(|index| mentions.get(index)).map(|mention| mention.target) else {
+                    return Ok(());
+                };
+                self.mentions = None;
+                let index = self.chat_list.items.iter().position(|chat_vo| chat_vo.target() == target);
+                self.chat_list.state.select(index);
+                self.open_chat()?;
             }
             _ => {}
         }
+        Ok(())
+    }
+
+    /// 汇总最近会话中提到我的消息，按时间倒序排列
+    fn open_mentions(&mut self) -> Result<()> {
+        let (my_uid, my_name) = my_user();
+        let mut found = vec![];
+        for chat_vo in &self.chat_list.items {
+            let target = chat_vo.target();
+            let conversation = chat_vo.get_name();
+            // 群已解散时跳过
+            let (history, (names, _)) = match fetch_history(target)
+                .and_then(|history| Ok((history, sender_names(target, &conversation, &self.contacts)?)))
+            {
+                Err(err) if err.to_string() == GROUP_GONE => continue,
+                result => result?,
+            };
+            found.extend(
+                history
+                    .into_iter()
+                    .filter(|msg| {
+                        msg.from_uid != my_uid && !msg.recalled && !self.contacts.is_blocked(msg.from_uid) && mentions(&msg.msg, &my_name)
+                    })
+                    .map(|msg| Mention {
+                        target,
+                        conversation: conversation.clone(),
+                        sender: names.get(&msg.from_uid).cloned().unwrap_or_else(|| msg.from_uid.to_string()),
+                        msg: msg.display(),
+                        time: msg.time,
+                    }),
+            );
+        }
+        found.sort_by_key(|mention| Reverse(mention.time));
+        let mut state = ListState::default();
+        if !found.is_empty() {
+            state.select_first();
+        }
+        self.mentions = Some((found, state));
+        Ok(())
     }
     fn select_next(&mut self) {
         self.chat_list.state.select_next()
@@ -96,31 +241,226 @@ impl RecentChat {
     fn select_last(&mut self) {
         self.chat_list.state.select_last()
     }
-    fn to_chat(&self) {
-        let index = self.chat_list.state.selected();
-        match index {
-            Some(index) => {
-                let chat_vo = &self.chat_list.items[index];
-                match chat_vo {
-                    ChatVo::User { uid, .. } => {
-                        // TODO: to chat with user
-                    }
-                    ChatVo::Group { gid, .. } => {
-                        // TODO: to chat with group
-                    }
+    fn open_chat(&mut self) -> Result<()> {
+        let Some(index) = self.chat_list.state.selected() else {
+            return Ok(());
+        };
+        let Some(chat_vo) = self.chat_list.items.get_mut(index) else {
+            return Ok(());
+        };
+        let target = chat_vo.target();
+        // 打开会话即已读，群已解散时从列表中移除
+        let result = set_read_index(chat_vo.read_index()).and_then(|_| Chat::open(target, chat_vo.get_name(), &self.contacts, &self.keys));
+        match result {
+            Err(err) if err.to_string() == GROUP_GONE => {
+                self.chat_list.items.remove(index);
+                self.chat_list.state.select(None);
+                self.mentioned.remove(&target);
+                Err(err)
+            }
+            Err(err) => Err(err),
+            Ok(chat) => {
+                chat_vo.mark_read();
+                self.mentioned.remove(&target);
+                self.chat = Some(chat);
+                Ok(())
+            }
+        }
+    }
+
+    /// 切换选中会话的免打扰
+    fn toggle_mute(&mut self) -> Result<()> {
+        let Some(target) = self.chat_list.state.selected().and_then(|index| self.chat_list.items.get(index)).map(ChatVo::target) else {
+            return Ok(());
+        };
+        let muted = !self.contacts.is_muted(target);
+        self.contacts.set_muted(target, muted);
+        self.contacts.save().map_err(|err| format_err!(err))
+    }
+
+    /// 未读总数，不含免打扰的会话
+    pub(crate) fn total_unread(&self) -> u32 {
+        self.chat_list
+            .items
+            .iter()
+            .filter(|chat_vo| !self.contacts.is_muted(chat_vo.target()))
+            .map(ChatVo::unread)
+            .sum()
+    }
+
+    /// 收到新消息：更新会话预览并移动到最上方，返回需要发出的通知
+    pub(crate) fn on_message(&mut self, message: &ChatMessage) -> Result<Option<Notification>> {
+        let (my_uid, my_name) = match &current_user().lock().unwrap().user {
+            Some(user) => (user.id, user.name.clone()),
+            None => return Ok(None),
+        };
+        let target = message.payload.conversation(my_uid);
+        let from_me = message.payload.from_uid == my_uid;
+        // 请求超时但服务端已收到的消息不再重发
+        if from_me && self.outbox.echoed(&message.payload) {
+            self.outbox.save().map_err(|err| format_err!(err))?;
+        }
+        // 不显示被屏蔽用户的消息
+        if self.contacts.is_blocked(message.payload.from_uid) {
+            return Ok(None);
+        }
+        // 编辑和撤回不提醒，也不改变会话的顺序
+        if let Some(mid) = message.payload.detail.amended() {
+            if let Some(chat) = self.chat.as_mut().filter(|chat| chat.target == target) {
+                chat.on_message(message, &self.contacts, &self.keys)?;
+            }
+            if let Some(chat_vo) = self.chat_list.items.iter_mut().find(|chat_vo| chat_vo.target() == target) {
+                chat_vo.amend(mid, message);
+            }
+            return Ok(None);
+        }
+        let opened = match &mut self.chat {
+            Some(chat) if chat.target == target => {
+                chat.on_message(message, &self.contacts, &self.keys)?;
+                true
+            }
+            _ => false,
+        };
+        let mentioned = !from_me && !opened && mentions(&message.payload.detail.get_content(), &my_name);
+        if mentioned {
+            self.mentioned.insert(target);
+        }
+        // 自己发的和正在看的会话不提醒，免打扰的会话只在有人@我时提醒
+        let alert = !from_me && !opened && should_notify(&settings().notify, self.contacts.is_muted(target), mentioned, Local::now().time());
+        let Some(index) = self.chat_list.items.iter().position(|chat_vo| chat_vo.target() == target) else {
+            // 新的会话，重新拉取最近聊天列表
+            self.refresh()?;
+            return Ok(self.notification(message, target, mentioned).filter(|_| alert));
+        };
+        let sender_name = if from_me {
+            my_name
+        } else {
+            match self.find_name(message.payload.from_uid) {
+                Some(name) => name,
+                None => {
+                    self.refresh()?;
+                    return Ok(self.notification(message, target, mentioned).filter(|_| alert));
                 }
             }
-            None => {}
+        };
+        let notification = alert.then(|| self.notification(message, target, mentioned)).flatten();
+
+        let mut chat_vo = self.chat_list.items.remove(index);
+        chat_vo.update(message, sender_name, !from_me && !opened);
+        if opened && !from_me {
+            set_read_index(chat_vo.read_index())?;
+        }
+        self.chat_list.items.insert(0, chat_vo);
+
+        // 保持选中的会话不变
+        match self.chat_list.state.selected() {
+            Some(selected) if selected == index => self.chat_list.state.select(Some(0)),
+            Some(selected) if selected < index => self.chat_list.state.select(Some(selected + 1)),
+            _ => {}
+        }
+        Ok(notification)
+    }
+
+    /// 新消息的通知，标题为会话名称
+    fn notification(&self, message: &ChatMessage, target: MessageTarget, mentioned: bool) -> Option<Notification> {
+        let chat_vo = self.chat_list.items.iter().find(|chat_vo| chat_vo.target() == target)?;
+        let from_uid = message.payload.from_uid;
+        let sender = self.find_name(from_uid).unwrap_or_else(|| self.contacts.display_name(from_uid, &from_uid.to_string()));
+        Some(Notification::new(&chat_vo.get_name(), &sender, &message.payload.detail.get_content(), mentioned))
+    }
+
+    /// 收到正在输入的通知，只在打开的会话中提示
+    pub(crate) fn on_typing(&mut self, message: &TypingMessage) {
+        if self.contacts.is_blocked(message.from_uid) || message.from_uid == my_user().0 {
+            return;
+        }
+        if let Some(chat) = self.chat.as_mut().filter(|chat| chat.target == message.conversation()) {
+            chat.on_typing(message);
+        }
+    }
+
+    /// 重发到时间的消息，群已解散的消息不再重发
+    pub(crate) fn flush_outbox(&mut self) -> Result<()> {
+        let now = Local::now().timestamp_millis();
+        let due = self.outbox.due(now);
+        if due.is_empty() {
+            return Ok(());
+        }
+        for queued in due {
+            match send_message(&queued) {
+                Ok(_) => self.outbox.sent(&queued.client_id),
+                Err(err) if err.to_string() == GROUP_GONE => self.outbox.sent(&queued.client_id),
+                Err(_) => self.outbox.attempt_failed(&queued.client_id, now),
+            }
+        }
+        self.outbox.save().map_err(|err| format_err!(err))
+    }
+
+    /// 手动重发选中会话中发送失败的消息
+    fn retry(&mut self) -> Result<()> {
+        let Some(target) = self.chat_list.state.selected().and_then(|index| self.chat_list.items.get(index)).map(ChatVo::target) else {
+            return Ok(());
+        };
+        if self.outbox.retry(target, Local::now().timestamp_millis()) > 0 {
+            self.flush_outbox()?;
+        }
+        Ok(())
+    }
+
+    /// 收到表情回应，只在打开的会话中显示
+    pub(crate) fn on_reaction(&mut self, message: &ReactionMessage) {
+        if self.contacts.is_blocked(message.from_uid) {
+            return;
+        }
+        let target = message.conversation(my_user().0);
+        if let Some(chat) = self.chat.as_mut().filter(|chat| chat.target == target) {
+            chat.on_reaction(message);
+        }
+    }
+
+    /// 其他人的已读位置更新，只在打开的会话中显示
+    pub(crate) fn on_read(&mut self, message: &ReadIndexMessage) {
+        if message.uid == my_user().0 {
+            return;
+        }
+        if let Some(chat) = self.chat.as_mut().filter(|chat| chat.target == message.conversation()) {
+            chat.on_read(message.mid);
         }
     }
 
+    /// 重新拉取最近聊天列表，保持选中的会话不变
+    fn refresh(&mut self) -> Result<()> {
+        let selected = self
+            .chat_list
+            .state
+            .selected()
+            .and_then(|index| self.chat_list.items.get(index))
+            .map(ChatVo::target);
+        self.chat_list.items = recent_chat(&self.contacts)?;
+        let index = selected.and_then(|target| self.chat_list.items.iter().position(|chat_vo| chat_vo.target() == target));
+        self.chat_list.state.select(index);
+        Ok(())
+    }
+
+    /// 从已有会话中查找用户名
+    fn find_name(&self, uid: i32) -> Option<String> {
+        self.chat_list.items.iter().find_map(|chat_vo| match chat_vo {
+            ChatVo::User { uid: friend_uid, user_name, .. } | ChatVo::Group { uid: friend_uid, user_name, .. } if *friend_uid == uid => {
+                Some(user_name.clone())
+            }
+            _ => None,
+        })
+    }
+
     fn render_list(&mut self, area: Rect, buf: &mut Buffer) {
         let block = Block::new()
             .borders(Borders::TOP)
             .border_set(symbols::border::EMPTY)
-            .border_style(TODO_HEADER_STYLE)
-            .bg(NORMAL_ROW_BG);
+            .border_style(theme().header)
+            .style(theme().row);
 
+        // 列表左侧留出一列给选中标记
+        let width = usize::from(area.width.saturating_sub(1));
         // Iterate through all elements in the `items` and stylize them.
         let items: Vec<ListItem> = self
             .chat_list
@@ -128,16 +468,17 @@ impl RecentChat {
             .iter()
             .enumerate()
             .map(|(i, chat_vo)| {
-                let color = alternate_colors(i);
-                ListItem::new(Line::from(chat_vo)).bg(color)
+                let target = chat_vo.target();
+                chat_vo
+                    .to_list_item(width, self.contacts.is_muted(target), self.mentioned.contains(&target))
+                    .style(alternate_rows(i))
             })
             .collect();
 
-        // TODO 需要支持自动换行 wrap
         // Create a List from all list items and highlight the currently selected one
         let list = List::new(items)
             .block(block)
-            .highlight_style(SELECTED_STYLE)
+            .highlight_style(theme().selected)
             .highlight_symbol(">")
             .highlight_spacing(HighlightSpacing::Always);
 
@@ -150,9 +491,9 @@ impl RecentChat {
         // We get the info depending on the item's state.
         let (info, title) = if let Some(i) = self.chat_list.state.selected() {
             let chat_vo = &self.chat_list.items[i];
-            (Line::from(chat_vo), format!("Chat with {}", chat_vo.get_name()))
+            (chat_vo.to_text(), format!("Chat with {}", chat_vo.get_name()))
         } else {
-            (Line::from("Nothing selected...".to_string()), "No chat selected".to_string())
+            (Text::from("Nothing selected..."), "No chat selected".to_string())
         };
 
         // We show the list item's info under the list in this paragraph
@@ -160,24 +501,62 @@ impl RecentChat {
             .title(Line::raw(title).centered())
             .borders(Borders::LEFT | Borders::TOP)
             .border_set(symbols::border::EMPTY)
-            .border_style(TODO_HEADER_STYLE)
-            .bg(NORMAL_ROW_BG)
+            .border_style(theme().header)
+            .style(theme().row)
             .padding(Padding::horizontal(1));
 
         // We can now render the item info
         Paragraph::new(info)
             .block(block)
-            .fg(TEXT_FG_COLOR)
             .wrap(Wrap { trim: false })
             .render(area, buf);
     }
+
+    fn render_mentions(&mut self, area: Rect, buf: &mut Buffer) {
+        let Some((mentions, state)) = &mut self.mentions else {
+            return;
+        };
+        let block = Block::new()
+            .title(Line::raw(format!("提到我的 ({})", mentions.len())).centered())
+            .borders(Borders::TOP)
+            .border_set(symbols::border::EMPTY)
+            .border_style(theme().header)
+            .style(theme().row);
+        if mentions.is_empty() {
+            Paragraph::new("最近没有人@你").block(block).centered().render(area, buf);
+            return;
+        }
+        let width = usize::from(area.width.saturating_sub(1));
+        let items: Vec<ListItem> = mentions
+            .iter()
+            .enumerate()
+            .map(|(i, mention)| {
+                let time = relative_time(mention.time, Local::now());
+                let name = truncate(&format!("{} · {}", mention.conversation, mention.sender), width.saturating_sub(display_width(&time) + 1));
+                let padding = width.saturating_sub(display_width(&name) + display_width(&time));
+                let header = Line::from(vec![
+                    Span::styled(name, theme().title),
+                    Span::raw(" ".repeat(padding)),
+                    Span::styled(time, theme().secondary),
+                ]);
+                let msg = Line::styled(truncate(&single_line(&mention.msg), width), theme().mention);
+                ListItem::new(Text::from(vec![header, msg])).style(alternate_rows(i))
+            })
+            .collect();
+        let list = List::new(items)
+            .block(block)
+            .highlight_style(theme().selected)
+            .highlight_symbol(">")
+            .highlight_spacing(HighlightSpacing::Always);
+        StatefulWidget::render(list, area, buf, state);
+    }
 }
 
-const fn alternate_colors(i: usize) -> Color {
+fn alternate_rows(i: usize) -> Style {
     if i % 2 == 0 {
-        NORMAL_ROW_BG
+        theme().row
     } else {
-        ALT_ROW_BG_COLOR
+        theme().alt_row
     }
 }
 
@@ -194,21 +573,44 @@ impl Widget for &mut RecentChat {
 
         RecentChat::render_footer(footer_area, buf);
 
-        if self.chat_list.state.selected().is_some() {
+        if self.mentions.is_some() {
+            self.render_mentions(main_area, buf);
+        } else if self.chat_list.state.selected().is_some() || self.chat.is_some() {
             let [list_area, chat_area] =
                 Layout::horizontal([Constraint::Fill(1), Constraint::Fill(2)]).areas(main_area);
             self.render_list(list_area, buf);
-            self.render_chat(chat_area, buf);
+            match &self.chat {
+                Some(chat) => chat.render(&self.contacts, &self.outbox, chat_area, buf),
+                None => self.render_chat(chat_area, buf),
+            }
         } else {
             self.render_list(main_area, buf);
         }
     }
 }
 
-fn recent_chat() -> Result<Vec<ChatVo>> {
-    let url = format!("{HOST}/user/history/100");
-    let token = CURRENT_USER.lock().unwrap().token.clone().unwrap();
-    let res = Client::new()
+fn set_read_index(ri: UpdateReadIndex) -> Result<()> {
+    let token = current_user().lock().unwrap().token.clone().unwrap();
+    let res = http_client()
+        .put(format!("{}/ri", host()))
+        .header(
+            "Authorization",
+            format!("Bearer {}", token),
+        )
+        .json(&ri)
+        .send();
+    match res {
+        Ok(res) if res.status().is_success() => Ok(()),
+        Ok(res) if matches!(ri, UpdateReadIndex::Group { .. }) && is_gone(res.status().as_u16()) => Err(format_err!(GROUP_GONE)),
+        Ok(res) => Err(format_err!("Fail to Set Read Index: HTTP {}", res.status())),
+        Err(err) => Err(format_err!("Fail to Set Read Index: {}", err)),
+    }
+}
+
+fn recent_chat(contacts: &ContactBook) -> Result<Vec<ChatVo>> {
+    let url = format!("{}/user/history/{}", host(), settings().page_size.recent_chat);
+    let token = current_user().lock().unwrap().token.clone().unwrap();
+    let res = http_client()
         .get(url)
         .header(
             "Authorization",
@@ -217,7 +619,9 @@ fn recent_chat() -> Result<Vec<ChatVo>> {
         .send();
     if let Ok(res) = res {
         if res.status().is_success() {
-            res.json::<Vec<ChatVo>>().map_err(|err| { format_err!("Fail to Parse Recent Chat: {}", err) })
+            res.json::<Vec<ChatVo>>()
+                .map(|chat_vos| chat_vos.into_iter().filter_map(|chat_vo| chat_vo.apply_contacts(contacts)).collect())
+                .map_err(|err| { format_err!("Fail to Parse Recent Chat: {}", err) })
         } else {
             Err(format_err!("Fail to Get Recent Chat"))
         }
@@ -234,36 +638,43 @@ enum ChatVo {
         /// id of friend
         uid: i32,
         /// name of friend
+        #[serde(deserialize_with = "common::sanitize::line")]
         user_name: String,
         /// message id
         mid: i64,
         /// message content
+        #[serde(deserialize_with = "common::sanitize::text")]
         msg: String,
         /// message time
         #[serde(with = "datetime_format")]
         msg_time: DateTime<Local>,
         /// unread message count
-        unread: Option<String>,
+        #[serde(default, with = "unread_format")]
+        unread: Option<u32>,
     },
     /// GroupChat
     Group {
         /// id of group
         gid: i32,
         /// name of group
+        #[serde(deserialize_with = "common::sanitize::line")]
         group_name: String,
         /// id of friend
         uid: i32,
         /// name of friend
+        #[serde(deserialize_with = "common::sanitize::line")]
         user_name: String,
         /// message id
         mid: i64,
         /// message content
+        #[serde(deserialize_with = "common::sanitize::text")]
         msg: String,
         /// message time
         #[serde(with = "datetime_format")]
         msg_time: DateTime<Local>,
         /// unread message count
-        unread: Option<String>,
+        #[serde(default, with = "unread_format")]
+        unread: Option<u32>,
     },
 }
 
@@ -271,52 +682,149 @@ impl ChatVo {
     fn get_name(&self) -> String {
         match self {
             ChatVo::User { user_name, .. } => user_name.clone(),
-            ChatVo::Group { user_name, .. } => user_name.clone(),
+            ChatVo::Group { group_name, .. } => group_name.clone(),
+        }
+    }
+
+    fn target(&self) -> MessageTarget {
+        match self {
+            ChatVo::User { uid, .. } => MessageTarget::User(MessageTargetUser { uid: *uid }),
+            ChatVo::Group { gid, .. } => MessageTarget::Group(MessageTargetGroup { gid: *gid }),
         }
     }
-}
 
-impl From<&ChatVo> for Line<'_> {
-    fn from(value: &ChatVo) -> Self {
-        match value {
-            ChatVo::User {
-                uid: _uid,
-                user_name,
-                msg,
-                msg_time,
-                unread,
-                ..
-            } => {
-                let mut content = vec![
-                    // FIXME 换行不生效
-                    Span::styled(format!("好友: {}\n", user_name), Style::default().fg(Color::LightBlue)),
-                    Span::styled(format!("时间: {}\n", msg_time), Style::default().fg(Color::LightBlue)),
-                    Span::styled(format!("{}\n", msg), Style::default().fg(Color::White)),
-                ];
-                if let Some(unread) = unread {
-                    content.push(Span::styled(format!("未读: {}\n", unread), Style::default().fg(Color::LightBlue)))
+    fn msg(&self) -> &str {
+        match self {
+            ChatVo::User { msg, .. } | ChatVo::Group { msg, .. } => msg,
+        }
+    }
+
+    fn unread(&self) -> u32 {
+        match self {
+            ChatVo::User { unread, .. } | ChatVo::Group { unread, .. } => unread.unwrap_or(0),
+        }
+    }
+
+    fn read_index(&self) -> UpdateReadIndex {
+        match self {
+            ChatVo::User { uid, mid, .. } => UpdateReadIndex::User { target_uid: *uid, mid: *mid },
+            ChatVo::Group { gid, mid, .. } => UpdateReadIndex::Group { target_gid: *gid, mid: *mid },
+        }
+    }
+
+    /// 好友名称替换为备注，隐藏被屏蔽用户的会话和群聊消息
+    fn apply_contacts(mut self, contacts: &ContactBook) -> Option<Self> {
+        match &mut self {
+            ChatVo::User { uid, .. } if contacts.is_blocked(*uid) => return None,
+            ChatVo::User { uid, user_name, .. } => *user_name = contacts.display_name(*uid, user_name),
+            ChatVo::Group { uid, user_name, msg, .. } => {
+                *user_name = contacts.display_name(*uid, user_name);
+                if contacts.is_blocked(*uid) {
+                    *msg = BLOCKED_MESSAGE.to_string();
                 }
-                Line::from(content)
             }
-            ChatVo::Group {
-                gid: _gid,
-                group_name,
-                user_name,
-                msg,
-                msg_time,
-                unread,
-                ..
-            } => {
-                let mut content = vec![
-                    Span::styled(format!("群: {}\n", group_name), Style::default().fg(Color::LightBlue)),
-                    Span::styled(format!("时间: {}\n", msg_time), Style::default().fg(Color::LightBlue)),
-                    Span::styled(format!("{}: {}\n", user_name, msg), Style::default().fg(Color::White)),
-                ];
-                if let Some(unread) = unread {
-                    content.push(Span::styled(format!("未读: {}\n", unread), Style::default().fg(Color::LightBlue)))
-                }
-                Line::from(content)
+        }
+        Some(self)
+    }
+
+    fn mark_read(&mut self) {
+        match self {
+            ChatVo::User { unread, .. } | ChatVo::Group { unread, .. } => *unread = None,
+        }
+    }
+
+    /// 预览的消息被编辑或撤回时更新预览
+    fn amend(&mut self, amended: i64, message: &ChatMessage) {
+        match self {
+            ChatVo::User { mid, msg, .. } | ChatVo::Group { mid, msg, .. } if *mid == amended => {
+                *msg = message.payload.detail.get_content();
+            }
+            _ => {}
+        }
+    }
+
+    /// 用新消息更新会话预览，群聊同时更新最后发言人
+    fn update(&mut self, message: &ChatMessage, sender_name: String, increase_unread: bool) {
+        let unread_count = self.unread() + u32::from(increase_unread);
+        match self {
+            ChatVo::User { mid, msg, msg_time, unread, .. } => {
+                *mid = message.mid;
+                *msg = message.payload.detail.get_content();
+                *msg_time = message.payload.created_at;
+                *unread = Some(unread_count).filter(|count| *count > 0);
+            }
+            ChatVo::Group { uid, user_name, mid, msg, msg_time, unread, .. } => {
+                *uid = message.payload.from_uid;
+                *user_name = sender_name;
+                *mid = message.mid;
+                *msg = message.payload.detail.get_content();
+                *msg_time = message.payload.created_at;
+                *unread = Some(unread_count).filter(|count| *count > 0);
             }
         }
     }
 }
+
+impl ChatVo {
+    /// 会话列表项：第一行为名称、未读数和相对时间，第二行为按宽度截断的消息预览
+    ///
+    /// 免打扰的会话名称后显示 `[免打扰]`，未读数不使用醒目的颜色；有人@我时显示 `[有人@我]`
+    fn to_list_item(&self, width: usize, muted: bool, mentioned: bool) -> ListItem<'static> {
+        let (name, preview, msg_time, unread) = match self {
+            ChatVo::User { user_name, msg, msg_time, unread, .. } => {
+                (format!("好友: {user_name}"), single_line(msg), msg_time, unread)
+            }
+            ChatVo::Group { group_name, user_name, msg, msg_time, unread, .. } => {
+                (format!("群: {group_name}"), format!("{user_name}: {}", single_line(msg)), msg_time, unread)
+            }
+        };
+        let time = relative_time(*msg_time, Local::now());
+        let name = if muted { format!("{name} [免打扰]") } else { name };
+        let mut badge = unread_badge(*unread);
+        if muted {
+            badge = badge.style(theme().secondary);
+        }
+        let mention = if mentioned { Span::styled(" [有人@我]", theme().mention) } else { Span::raw("") };
+
+        // 名称过长时截断，保证时间靠右显示
+        let name_width = width.saturating_sub(display_width(&time) + mention.width() + badge.width() + 1);
+        let name = truncate(&name, name_width);
+        let padding = width.saturating_sub(display_width(&name) + mention.width() + badge.width() + display_width(&time));
+        let header = Line::from(vec![
+            Span::styled(name, theme().title),
+            mention,
+            badge,
+            Span::raw(" ".repeat(padding)),
+            Span::styled(time, theme().secondary),
+        ]);
+        let preview = Line::styled(truncate(&preview, width), theme().text);
+        ListItem::new(Text::from(vec![header, preview]))
+    }
+
+    /// 会话详情，用于右侧面板，消息内容完整显示并自动换行
+    fn to_text(&self) -> Text<'static> {
+        let (name, sender, msg, msg_time) = match self {
+            ChatVo::User { user_name, msg, msg_time, .. } => (format!("好友: {user_name}"), None, msg, msg_time),
+            ChatVo::Group { group_name, user_name, msg, msg_time, .. } => {
+                (format!("群: {group_name}"), Some(user_name), msg, msg_time)
+            }
+        };
+        let mut lines = vec![
+            Line::styled(name, theme().title),
+            Line::styled(format!("时间: {}", msg_time.format("%Y-%m-%d %H:%M:%S")), theme().title),
+        ];
+        if let Some(sender) = sender {
+            lines.push(Line::styled(format!("{sender}:"), theme().title));
+        }
+        lines.extend(msg.lines().map(|line| Line::styled(line.to_string(), theme().text)));
+        Text::from(lines)
+    }
+}
+
+/// 未读数徽标，没有未读时为空
+pub(crate) fn unread_badge(unread: Option<u32>) -> Span<'static> {
+    match unread {
+        Some(unread) if unread > 0 => Span::styled(format!(" {} ", badge(unread)), theme().badge),
+        _ => Span::raw(""),
+    }
+}
@@ -0,0 +1,459 @@
+use color_eyre::eyre::format_err;
+use color_eyre::Result;
+use common::config::{config_dir, settings};
+use ratatui::style::palette::material::BLUE;
+use ratatui::style::palette::tailwind::{BLUE as TW_BLUE, SLATE};
+use ratatui::style::{Color, Modifier, Style};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+/// 当前生效的主题
+static THEME: OnceLock<Theme> = OnceLock::new();
+
+/// 加载主题配置，程序启动时调用一次
+pub(crate) fn init() -> Result<()> {
+    let theme = Theme::load()?;
+    THEME
+        .set(theme)
+        .map_err(|_| format_err!("theme already initialized"))
+}
+
+pub(crate) fn theme() -> &'static Theme {
+    THEME.get_or_init(|| Theme::dark().adapt(ColorSupport::detect()))
+}
+
+/// 界面各部分的样式
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Theme {
+    /// 整体背景
+    pub(crate) background: Style,
+    /// 登陆页边框
+    pub(crate) border: Style,
+    /// 列表和面板的标题栏
+    pub(crate) header: Style,
+    /// 列表行，奇偶行交替
+    pub(crate) row: Style,
+    pub(crate) alt_row: Style,
+    /// 列表选中行
+    pub(crate) selected: Style,
+    /// 正文
+    pub(crate) text: Style,
+    /// 会话名称等标题文字
+    pub(crate) title: Style,
+    /// 时间等次要文字
+    pub(crate) secondary: Style,
+    /// 未读数徽标
+    pub(crate) badge: Style,
+    /// 底部菜单
+    pub(crate) menu: Style,
+    pub(crate) menu_selected: Style,
+    /// 正在编辑的输入框
+    pub(crate) editing: Style,
+    /// 帮助中的按键
+    pub(crate) key: Style,
+    /// 错误提示
+    pub(crate) error: Style,
+    /// 提到我的消息
+    pub(crate) mention: Style,
+}
+
+impl Theme {
+    pub(crate) fn dark() -> Self {
+        Self {
+            background: Style::new().bg(Color::DarkGray),
+            border: Style::new().fg(Color::Green),
+            header: Style::new().fg(SLATE.c100).bg(BLUE.c800),
+            row: Style::new().fg(SLATE.c200).bg(SLATE.c950),
+            alt_row: Style::new().fg(SLATE.c200).bg(SLATE.c900),
+            selected: Style::new().bg(SLATE.c800).add_modifier(Modifier::BOLD),
+            text: Style::new().fg(Color::White),
+            title: Style::new().fg(Color::LightBlue),
+            secondary: Style::new().fg(Color::Gray),
+            badge: Style::new().fg(Color::White).bg(Color::Red).add_modifier(Modifier::BOLD),
+            menu: Style::new().fg(Color::White).bg(Color::Gray),
+            menu_selected: Style::new().fg(Color::Black).bg(Color::LightGreen).add_modifier(Modifier::BOLD | Modifier::ITALIC),
+            editing: Style::new().fg(Color::Yellow),
+            key: Style::new().fg(Color::Yellow),
+            error: Style::new().fg(Color::Red),
+            mention: Style::new().fg(Color::Yellow).add_modifier(Modifier::BOLD),
+        }
+    }
+
+    pub(crate) fn light() -> Self {
+        Self {
+            background: Style::new().bg(SLATE.c100),
+            border: Style::new().fg(Color::Green),
+            header: Style::new().fg(SLATE.c900).bg(TW_BLUE.c200),
+            row: Style::new().fg(SLATE.c800).bg(SLATE.c50),
+            alt_row: Style::new().fg(SLATE.c800).bg(SLATE.c100),
+            selected: Style::new().bg(SLATE.c300).add_modifier(Modifier::BOLD),
+            text: Style::new().fg(SLATE.c900),
+            title: Style::new().fg(TW_BLUE.c700),
+            secondary: Style::new().fg(SLATE.c500),
+            badge: Style::new().fg(Color::White).bg(Color::Red).add_modifier(Modifier::BOLD),
+            menu: Style::new().fg(SLATE.c700).bg(SLATE.c200),
+            menu_selected: Style::new().fg(Color::White).bg(TW_BLUE.c600).add_modifier(Modifier::BOLD | Modifier::ITALIC),
+            editing: Style::new().fg(TW_BLUE.c700),
+            key: Style::new().fg(TW_BLUE.c700).add_modifier(Modifier::BOLD),
+            error: Style::new().fg(Color::Red),
+            mention: Style::new().fg(Color::Magenta).add_modifier(Modifier::BOLD),
+        }
+    }
+
+    pub(crate) fn high_contrast() -> Self {
+        Self {
+            background: Style::new().bg(Color::Black),
+            border: Style::new().fg(Color::White),
+            header: Style::new().fg(Color::Black).bg(Color::White).add_modifier(Modifier::BOLD),
+            row: Style::new().fg(Color::White).bg(Color::Black),
+            alt_row: Style::new().fg(Color::White).bg(Color::Black),
+            selected: Style::new().fg(Color::Black).bg(Color::Yellow).add_modifier(Modifier::BOLD),
+            text: Style::new().fg(Color::White),
+            title: Style::new().fg(Color::Cyan).add_modifier(Modifier::BOLD),
+            secondary: Style::new().fg(Color::White),
+            badge: Style::new().fg(Color::Black).bg(Color::Yellow).add_modifier(Modifier::BOLD),
+            menu: Style::new().fg(Color::White).bg(Color::Black),
+            menu_selected: Style::new().fg(Color::Black).bg(Color::Yellow).add_modifier(Modifier::BOLD),
+            editing: Style::new().fg(Color::Yellow).add_modifier(Modifier::BOLD),
+            key: Style::new().fg(Color::Yellow).add_modifier(Modifier::BOLD),
+            error: Style::new().fg(Color::LightRed).add_modifier(Modifier::BOLD),
+            mention: Style::new().fg(Color::Black).bg(Color::Cyan).add_modifier(Modifier::BOLD),
+        }
+    }
+
+    /// 不使用颜色，仅靠粗体、反色等区分
+    fn monochrome() -> Self {
+        let plain = Style::new();
+        Self {
+            background: plain,
+            border: plain,
+            header: plain.add_modifier(Modifier::BOLD),
+            row: plain,
+            alt_row: plain,
+            selected: plain.add_modifier(Modifier::REVERSED),
+            text: plain,
+            title: plain.add_modifier(Modifier::BOLD),
+            secondary: plain.add_modifier(Modifier::DIM),
+            badge: plain.add_modifier(Modifier::REVERSED | Modifier::BOLD),
+            menu: plain,
+            menu_selected: plain.add_modifier(Modifier::REVERSED | Modifier::BOLD),
+            editing: plain.add_modifier(Modifier::UNDERLINED),
+            key: plain.add_modifier(Modifier::BOLD),
+            error: plain.add_modifier(Modifier::BOLD),
+            mention: plain.add_modifier(Modifier::BOLD | Modifier::UNDERLINED),
+        }
+    }
+
+    fn builtin(name: &str) -> Option<Self> {
+        match name {
+            "dark" => Some(Self::dark()),
+            "light" => Some(Self::light()),
+            "high_contrast" | "high-contrast" => Some(Self::high_contrast()),
+            _ => None,
+        }
+    }
+
+    fn styles_mut(&mut self) -> [(&'static str, &mut Style); 16] {
+        [
+            ("background", &mut self.background),
+            ("border", &mut self.border),
+            ("header", &mut self.header),
+            ("row", &mut self.row),
+            ("alt_row", &mut self.alt_row),
+            ("selected", &mut self.selected),
+            ("text", &mut self.text),
+            ("title", &mut self.title),
+            ("secondary", &mut self.secondary),
+            ("badge", &mut self.badge),
+            ("menu", &mut self.menu),
+            ("menu_selected", &mut self.menu_selected),
+            ("editing", &mut self.editing),
+            ("key", &mut self.key),
+            ("error", &mut self.error),
+            ("mention", &mut self.mention),
+        ]
+    }
+
+    /// 自定义主题文件路径：~/.config/chat-cli/theme.toml
+    fn path() -> Option<PathBuf> {
+        config_dir().map(|dir| dir.join("theme.toml"))
+    }
+
+    fn load() -> Result<Self> {
+        let support = ColorSupport::detect();
+        let file = match Self::path().filter(|path| path.exists()) {
+            None => ThemeFile::default(),
+            Some(path) => config::Config::builder()
+                .add_source(config::File::from(path.clone()))
+                .build()
+                .and_then(|config| config.try_deserialize::<ThemeFile>())
+                .map_err(|err| format_err!("invalid theme {}: {}", path.display(), err))?,
+        };
+        Ok(file.resolve(&settings().ui.theme, 0)?.adapt(support))
+    }
+
+    /// 按终端能力降级颜色
+    fn adapt(mut self, support: ColorSupport) -> Self {
+        if support == ColorSupport::NoColor {
+            return Self::monochrome();
+        }
+        for (_, style) in self.styles_mut() {
+            style.fg = style.fg.map(|color| support.convert(color));
+            style.bg = style.bg.map(|color| support.convert(color));
+        }
+        self
+    }
+}
+
+/// 自定义主题文件，使用时在 config.toml 中设置 `ui.theme = "solarized"`
+///
+/// ```toml
+/// [themes.solarized]
+/// extends = "dark"
+/// header = { fg = "#fdf6e3", bg = "#268bd2", modifiers = ["bold"] }
+/// ```
+#[derive(Deserialize, Default)]
+struct ThemeFile {
+    #[serde(default)]
+    themes: HashMap<String, UserTheme>,
+}
+
+/// 用户自定义主题，在已有主题基础上覆盖部分样式
+#[derive(Deserialize)]
+struct UserTheme {
+    #[serde(default = "default_extends")]
+    extends: String,
+    #[serde(flatten)]
+    styles: HashMap<String, StyleSpec>,
+}
+
+fn default_extends() -> String {
+    "dark".to_string()
+}
+
+#[derive(Deserialize)]
+struct StyleSpec {
+    fg: Option<String>,
+    bg: Option<String>,
+    #[serde(default)]
+    modifiers: Vec<String>,
+}
+
+/// 自定义主题最多的继承层数，避免循环继承
+const MAX_EXTENDS_DEPTH: usize = 8;
+
+impl ThemeFile {
+    fn resolve(&self, name: &str, depth: usize) -> Result<Theme> {
+        if depth > MAX_EXTENDS_DEPTH {
+            return Err(format_err!("theme `{name}` extends too deep, is there a cycle?"));
+        }
+        let Some(user_theme) = self.themes.get(name) else {
+            return Theme::builtin(name).ok_or_else(|| format_err!("unknown theme `{name}`"));
+        };
+        let mut theme = self.resolve(&user_theme.extends, depth + 1)?;
+        for (key, spec) in &user_theme.styles {
+            let style = theme
+                .styles_mut()
+                .into_iter()
+                .find_map(|(name, style)| (name == key).then_some(style))
+                .ok_or_else(|| format_err!("unknown style `{key}` in theme `{name}`"))?;
+            *style = spec.to_style(*style)?;
+        }
+        Ok(theme)
+    }
+}
+
+impl StyleSpec {
+    fn to_style(&self, base: Style) -> Result<Style> {
+        let parse = |color: &String| Color::from_str(color).map_err(|_| format_err!("invalid color `{color}`"));
+        let mut style = base;
+        if let Some(fg) = &self.fg {
+            style = style.fg(parse(fg)?);
+        }
+        if let Some(bg) = &self.bg {
+            style = style.bg(parse(bg)?);
+        }
+        for modifier in &self.modifiers {
+            style = style.add_modifier(match modifier.to_ascii_lowercase().as_str() {
+                "bold" => Modifier::BOLD,
+                "dim" => Modifier::DIM,
+                "italic" => Modifier::ITALIC,
+                "underlined" => Modifier::UNDERLINED,
+                "reversed" => Modifier::REVERSED,
+                _ => return Err(format_err!("invalid modifier `{modifier}`")),
+            });
+        }
+        Ok(style)
+    }
+}
+
+/// 终端的颜色能力
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub(crate) enum ColorSupport {
+    NoColor,
+    Ansi16,
+    Ansi256,
+    TrueColor,
+}
+
+/// 16色对应的RGB值，用于就近匹配
+const ANSI_16: [(Color, (u8, u8, u8)); 16] = [
+    (Color::Black, (0, 0, 0)),
+    (Color::Red, (205, 0, 0)),
+    (Color::Green, (0, 205, 0)),
+    (Color::Yellow, (205, 205, 0)),
+    (Color::Blue, (0, 0, 238)),
+    (Color::Magenta, (205, 0, 205)),
+    (Color::Cyan, (0, 205, 205)),
+    (Color::Gray, (229, 229, 229)),
+    (Color::DarkGray, (127, 127, 127)),
+    (Color::LightRed, (255, 0, 0)),
+    (Color::LightGreen, (0, 255, 0)),
+    (Color::LightYellow, (255, 255, 0)),
+    (Color::LightBlue, (92, 92, 255)),
+    (Color::LightMagenta, (255, 0, 255)),
+    (Color::LightCyan, (0, 255, 255)),
+    (Color::White, (255, 255, 255)),
+];
+
+/// 256色中6x6x6色块每一级的取值
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+impl ColorSupport {
+    /// 根据 NO_COLOR、COLORTERM、TERM 环境变量判断
+    pub(crate) fn detect() -> Self {
+        let env = |key| std::env::var(key).unwrap_or_default();
+        Self::from_env(&env("NO_COLOR"), &env("COLORTERM"), &env("TERM"))
+    }
+
+    fn from_env(no_color: &str, color_term: &str, term: &str) -> Self {
+        if !no_color.is_empty() || term == "dumb" {
+            ColorSupport::NoColor
+        } else if color_term == "truecolor" || color_term == "24bit" {
+            ColorSupport::TrueColor
+        } else if term.contains("256color") {
+            ColorSupport::Ansi256
+        } else {
+            ColorSupport::Ansi16
+        }
+    }
+
+    fn convert(&self, color: Color) -> Color {
+        match (self, color) {
+            (ColorSupport::TrueColor, _) | (_, Color::Reset) => color,
+            (ColorSupport::Ansi256, Color::Rgb(r, g, b)) => Color::Indexed(rgb_to_ansi256(r, g, b)),
+            (ColorSupport::Ansi16, Color::Rgb(r, g, b)) => rgb_to_ansi16(r, g, b),
+            (ColorSupport::Ansi16, Color::Indexed(index)) => {
+                let (r, g, b) = ansi256_to_rgb(index);
+                rgb_to_ansi16(r, g, b)
+            }
+            _ => color,
+        }
+    }
+}
+
+fn distance((r1, g1, b1): (u8, u8, u8), (r2, g2, b2): (u8, u8, u8)) -> u32 {
+    let d = |a: u8, b: u8| (i32::from(a) - i32::from(b)).pow(2) as u32;
+    d(r1, r2) + d(g1, g2) + d(b1, b2)
+}
+
+fn rgb_to_ansi16(r: u8, g: u8, b: u8) -> Color {
+    ANSI_16
+        .iter()
+        .min_by_key(|(_, rgb)| distance(*rgb, (r, g, b)))
+        .map(|(color, _)| *color)
+        .unwrap_or(Color::Reset)
+}
+
+fn rgb_to_ansi256(r: u8, g: u8, b: u8) -> u8 {
+    let level = |v: u8| {
+        CUBE_LEVELS
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, level)| u8::abs_diff(**level, v))
+            .map(|(i, _)| i as u8)
+            .unwrap_or(0)
+    };
+    let (ri, gi, bi) = (level(r), level(g), level(b));
+    let cube_index = 16 + 36 * ri + 6 * gi + bi;
+    // 灰阶：232-255，从8开始每级加10
+    let average = ((u16::from(r) + u16::from(g) + u16::from(b)) / 3) as u8;
+    let gray_index = 232 + (average.saturating_sub(3) / 10).min(23);
+    if distance(ansi256_to_rgb(gray_index), (r, g, b)) < distance(ansi256_to_rgb(cube_index), (r, g, b)) {
+        gray_index
+    } else {
+        cube_index
+    }
+}
+
+fn ansi256_to_rgb(index: u8) -> (u8, u8, u8) {
+    match index {
+        0..=15 => ANSI_16[usize::from(index)].1,
+        16..=231 => {
+            let i = index - 16;
+            (
+                CUBE_LEVELS[usize::from(i / 36)],
+                CUBE_LEVELS[usize::from(i / 6 % 6)],
+                CUBE_LEVELS[usize::from(i % 6)],
+            )
+        }
+        _ => {
+            let v = 8 + (index - 232) * 10;
+            (v, v, v)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{rgb_to_ansi16, rgb_to_ansi256, ColorSupport, Theme, ThemeFile};
+    use ratatui::style::{Color, Modifier};
+
+    #[test]
+    fn test_detect_color_support() {
+        assert_eq!(ColorSupport::from_env("1", "truecolor", "xterm-256color"), ColorSupport::NoColor);
+        assert_eq!(ColorSupport::from_env("", "truecolor", "xterm"), ColorSupport::TrueColor);
+        assert_eq!(ColorSupport::from_env("", "", "xterm-256color"), ColorSupport::Ansi256);
+        assert_eq!(ColorSupport::from_env("", "", "xterm"), ColorSupport::Ansi16);
+    }
+
+    #[test]
+    fn test_downgrade_colors() {
+        assert_eq!(rgb_to_ansi256(255, 0, 0), 196);
+        assert_eq!(rgb_to_ansi256(128, 128, 128), 244);
+        assert_eq!(rgb_to_ansi16(250, 10, 10), Color::LightRed);
+        let theme = Theme::dark().adapt(ColorSupport::Ansi16);
+        assert!(matches!(theme.header.bg, Some(color) if !matches!(color, Color::Rgb(..) | Color::Indexed(_))));
+        assert_eq!(Theme::dark().adapt(ColorSupport::NoColor).selected.add_modifier, Modifier::REVERSED);
+    }
+
+    #[test]
+    fn test_user_theme() {
+        let file: ThemeFile = toml_file(
+            r##"
+            [themes.mine]
+            extends = "light"
+            badge = { fg = "black", bg = "#ffcc00", modifiers = ["bold"] }
+            "##,
+        );
+        let theme = file.resolve("mine", 0).unwrap();
+        assert_eq!(theme.badge.bg, Some(Color::Rgb(0xff, 0xcc, 0x00)));
+        assert_eq!(theme.header, Theme::light().header);
+
+        let cycle: ThemeFile = toml_file("[themes.a]\nextends = \"b\"\n[themes.b]\nextends = \"a\"\n");
+        assert!(cycle.resolve("a", 0).is_err());
+        let unknown: ThemeFile = toml_file("[themes.a]\nfoo = { fg = \"red\" }\n");
+        assert!(unknown.resolve("a", 0).is_err());
+    }
+
+    fn toml_file(content: &str) -> ThemeFile {
+        config::Config::builder()
+            .add_source(config::File::from_str(content, config::FileFormat::Toml))
+            .build()
+            .and_then(|config| config.try_deserialize())
+            .unwrap()
+    }
+}
@@ -1,20 +1,76 @@
 use chrono::{DateTime, Local};
+use common::config::settings;
+use common::profile::Profile;
 use jsonwebtoken::{decode, DecodingKey, EncodingKey, TokenData, Validation};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::ops::Add;
 use std::sync::{Arc, LazyLock, Mutex};
 use std::time::Duration;
 
-// 存储当前用户信息
-pub(crate) static CURRENT_USER: LazyLock<Arc<Mutex<CurrentUser>>, fn() -> Arc<Mutex<CurrentUser>>> = LazyLock::new(|| {
-    return Arc::new(Mutex::new(CurrentUser { user: None, token: None }));
+// 各 profile 的会话互相隔离，切换账号时保留其他账号的登陆状态
+static SESSIONS: LazyLock<Mutex<Sessions>> = LazyLock::new(|| {
+    let name = settings().profile.clone().unwrap_or_default();
+    let session = CurrentUser {
+        profile: settings().profile.clone(),
+        server: settings().server.clone(),
+        user: None,
+        token: None,
+    };
+    Mutex::new(Sessions {
+        active: name.clone(),
+        sessions: HashMap::from([(name, Arc::new(Mutex::new(session)))]),
+    })
 });
 
+struct Sessions {
+    /// 当前使用的 profile，未使用 profile 时为空
+    active: String,
+    sessions: HashMap<String, Arc<Mutex<CurrentUser>>>,
+}
+
 pub(crate) struct CurrentUser {
+    pub(crate) profile: Option<String>,
+    /// 服务端地址
+    pub(crate) server: String,
     pub(crate) user: Option<User>,
     pub(crate) token: Option<String>,
 }
 
+/// 当前账号的会话
+pub(crate) fn current_user() -> Arc<Mutex<CurrentUser>> {
+    let sessions = SESSIONS.lock().unwrap();
+    sessions.sessions[&sessions.active].clone()
+}
+
+/// profile 对应的账号是否已登陆
+pub(crate) fn is_logged_in(profile: &str) -> bool {
+    SESSIONS
+        .lock()
+        .unwrap()
+        .sessions
+        .get(profile)
+        .is_some_and(|session| session.lock().unwrap().token.is_some())
+}
+
+/// 切换到 profile 对应的会话，首次切换时创建
+pub(crate) fn switch_profile(profile: &Profile) -> Arc<Mutex<CurrentUser>> {
+    let mut sessions = SESSIONS.lock().unwrap();
+    sessions.active = profile.name.clone();
+    sessions
+        .sessions
+        .entry(profile.name.clone())
+        .or_insert_with(|| {
+            Arc::new(Mutex::new(CurrentUser {
+                profile: Some(profile.name.clone()),
+                server: profile.server.trim_end_matches('/').to_string(),
+                user: None,
+                token: None,
+            }))
+        })
+        .clone()
+}
+
 const KEYS: LazyLock<Keys, fn() -> Keys> = LazyLock::new(|| {
     let secret = std::env::var("JWT_SECRET").unwrap_or("abc".to_string());
     Keys::new(secret.as_bytes())
@@ -23,8 +79,11 @@ const KEYS: LazyLock<Keys, fn() -> Keys> = LazyLock::new(|| {
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct User {
     pub id: i32,
+    #[serde(deserialize_with = "common::sanitize::line")]
     pub name: String,
+    #[serde(default, deserialize_with = "common::sanitize::optional_text")]
     pub email: Option<String>,
+    #[serde(default, deserialize_with = "common::sanitize::optional_text")]
     pub phone: Option<String>,
     pub dgraph_uid: String,
     pub role: Role,
@@ -27,16 +27,25 @@
 //
 // See also https://github.com/rhysd/tui-textarea and https://github.com/sayanarijit/tui-input/
 
+use crate::theme::theme;
+use common::text::display_width;
+use common::{command, emoji, mention};
+use ratatui::style::Modifier;
+use ratatui::text::{Line, Span};
+use unicode_segmentation::UnicodeSegmentation;
+
 /// App holds the state of the application
 pub struct Input {
     /// Current value of the input box
     pub(crate) input: String,
-    /// Position of cursor in the editor area.
+    /// Position of cursor in the editor area, 字符序号，总是在字形簇（如国旗、带肤色的表情）的边界上
     pub(crate) character_index: usize,
     /// Current input mode
     // pub(crate) current_mode: CurrentMode,
     /// History of recorded messages
     pub(crate) messages: Vec<String>,
+    /// 正在补全的@提及、命令名或表情短代码：替换开始的字符位置、候选和当前候选的序号
+    completion: Option<(usize, Vec<String>, usize)>,
 }
 
 impl Input {
@@ -46,23 +55,74 @@ impl Input {
             // current_mode: CurrentMode::Normal,
             messages: Vec::new(),
             character_index: 0,
+            completion: None,
+        }
+    }
+
+    /// 带初始内容的输入框，光标在末尾
+    pub(crate) fn with_text(input: String) -> Self {
+        let character_index = input.chars().count();
+        Self {
+            input,
+            messages: Vec::new(),
+            character_index,
+            completion: None,
+        }
+    }
+
+    /// 字形簇边界的字符位置，光标按字形簇移动和删除
+    fn boundaries(&self) -> Vec<usize> {
+        let mut position = 0;
+        let mut boundaries = vec![0];
+        for grapheme in self.input.graphemes(true) {
+            position += grapheme.chars().count();
+            boundaries.push(position);
         }
+        boundaries
     }
 
     pub(crate) fn move_cursor_left(&mut self) {
-        let cursor_moved_left = self.character_index.saturating_sub(1);
-        self.character_index = self.clamp_cursor(cursor_moved_left);
+        self.completion = None;
+        self.character_index = self.boundaries().into_iter().rev().find(|index| *index < self.character_index).unwrap_or(0);
     }
 
     pub(crate) fn move_cursor_right(&mut self) {
-        let cursor_moved_right = self.character_index.saturating_add(1);
-        self.character_index = self.clamp_cursor(cursor_moved_right);
+        self.completion = None;
+        let cursor_moved_right = self.boundaries().into_iter().find(|index| *index > self.character_index);
+        self.character_index = cursor_moved_right.unwrap_or(self.character_index);
     }
 
+    /// 输入一个字符，输入法上屏的文字和组成国旗等字形簇的多个字符逐个输入，光标停在输入的字符之后
     pub(crate) fn enter_char(&mut self, new_char: char) {
+        self.completion = None;
         let index = self.byte_index();
         self.input.insert(index, new_char);
-        self.move_cursor_right();
+        self.character_index += 1;
+    }
+
+    /// 插入粘贴的文字，光标移到插入的文字之后
+    pub(crate) fn insert_str(&mut self, text: &str) {
+        self.completion = None;
+        let index = self.byte_index();
+        self.input.insert_str(index, text);
+        self.character_index += text.chars().count();
+    }
+
+    /// 光标前刚输入完整的 `:短代码:` 时替换为表情
+    pub(crate) fn expand_shortcode(&mut self) {
+        let Some(colon) = self.character_index.checked_sub(1) else {
+            return;
+        };
+        if self.input.chars().nth(colon) != Some(':') {
+            return;
+        }
+        let Some((start, emoji)) = emoji::pending(&self.input, colon).and_then(|(start, code)| Some((start, emoji::lookup(&code)?))) else {
+            return;
+        };
+        let before = self.input.chars().take(start).collect::<String>();
+        let after = self.input.chars().skip(self.character_index).collect::<String>();
+        self.input = format!("{before}{emoji}{after}");
+        self.character_index = start + emoji.chars().count();
     }
 
     /// Returns the byte index based on the character position.
@@ -77,7 +137,9 @@ impl Input {
             .unwrap_or(self.input.len())
     }
 
+    /// 删除光标前的整个字形簇
     pub(crate) fn delete_char(&mut self) {
+        self.completion = None;
         let is_not_cursor_leftmost = self.character_index != 0;
         if is_not_cursor_leftmost {
             // Method "remove" is not used on the saved text for deleting the selected char.
@@ -85,26 +147,84 @@ impl Input {
             // Using remove would require special care because of char boundaries.
 
             let current_index = self.character_index;
-            let from_left_to_current_index = current_index - 1;
+            self.move_cursor_left();
+            let from_left_to_current_index = self.character_index;
 
-            // Getting all characters before the selected character.
+            // Getting all characters before the selected grapheme.
             let before_char_to_delete = self.input.chars().take(from_left_to_current_index);
-            // Getting all characters after selected character.
+            // Getting all characters after selected grapheme.
             let after_char_to_delete = self.input.chars().skip(current_index);
 
             // Put all characters together except the selected one.
             // By leaving the selected one out, it is forgotten and therefore deleted.
             self.input = before_char_to_delete.chain(after_char_to_delete).collect();
-            self.move_cursor_left();
         }
     }
 
-    pub(crate) fn clamp_cursor(&self, new_cursor_pos: usize) -> usize {
-        new_cursor_pos.clamp(0, self.input.chars().count())
+    /// 光标所在的列，中文和表情占两列
+    pub(crate) fn cursor_column(&self) -> u16 {
+        let before = self.input.chars().take(self.character_index).collect::<String>();
+        u16::try_from(display_width(&before)).unwrap_or(u16::MAX)
+    }
+
+    /// 输入框的内容，光标所在的字形簇反色显示
+    pub(crate) fn line(&self) -> Line<'static> {
+        let before = self.input.chars().take(self.character_index).collect::<String>();
+        let rest = self.input.chars().skip(self.character_index).collect::<String>();
+        let cursor = rest.graphemes(true).next().unwrap_or(" ").to_string();
+        let after = rest.graphemes(true).skip(1).collect::<String>();
+        Line::from(vec![
+            Span::raw(before),
+            Span::styled(cursor, theme().editing.add_modifier(Modifier::REVERSED)),
+            Span::raw(after),
+        ])
+    }
+
+    /// 光标前正在输入的表情短代码的候选，用于显示补全提示
+    pub(crate) fn shortcode_candidates(&self) -> Vec<(&'static str, &'static str)> {
+        emoji::pending(&self.input, self.character_index).map_or_else(Vec::new, |(_, prefix)| emoji::candidates(&prefix))
     }
 
     pub(crate) fn reset_cursor(&mut self) {
         self.character_index = 0;
+        self.completion = None;
+    }
+
+    /// 补全命令名、光标前的表情短代码或@提及，连续调用时依次切换候选
+    pub(crate) fn complete(&mut self, names: &[String]) {
+        let commands = command::complete(&self.input);
+        let (start, candidates, index) = match self.completion.take() {
+            Some((start, candidates, index)) => {
+                let index = (index + 1) % candidates.len();
+                (start, candidates, index)
+            }
+            // 命令名从开头的 `/` 之后开始替换
+            None if !commands.is_empty() && self.character_index == self.input.chars().count() => {
+                (1, commands.iter().map(|name| name[1..].to_string()).collect(), 0)
+            }
+            // 短代码连同开头的 `:` 替换为表情
+            None if !self.shortcode_candidates().is_empty() => {
+                let (colon, _) = emoji::pending(&self.input, self.character_index).unwrap_or_default();
+                (colon, self.shortcode_candidates().into_iter().map(|(_, emoji)| emoji.to_string()).collect(), 0)
+            }
+            None => {
+                let Some((at, prefix)) = mention::pending(&self.input, self.character_index) else {
+                    return;
+                };
+                let candidates = mention::candidates(&prefix, names);
+                if candidates.is_empty() {
+                    return;
+                }
+                (at + 1, candidates, 0)
+            }
+        };
+        // 替换 `start` 和光标之间的内容
+        let name = &candidates[index];
+        let before = self.input.chars().take(start).collect::<String>();
+        let after = self.input.chars().skip(self.character_index).collect::<String>();
+        self.input = format!("{before}{name}{after}");
+        self.character_index = start + name.chars().count();
+        self.completion = Some((start, candidates, index));
     }
 
     pub(crate) fn submit_message(&mut self) {
@@ -112,5 +232,58 @@ impl Input {
         self.input.clear();
         self.reset_cursor();
     }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Input;
+
+    #[test]
+    fn test_complete() {
+        let names = ["bob", "bobby", "alice"].map(String::from);
+        let mut input = Input::with_text("hi @bo".to_string());
+        input.complete(&names);
+        assert_eq!(input.input, "hi @bob");
+        input.complete(&names);
+        assert_eq!(input.input, "hi @bobby");
+        input.complete(&names);
+        assert_eq!(input.input, "hi @bob");
+        input.enter_char(' ');
+        input.complete(&names);
+        assert_eq!(input.input, "hi @bob ");
+
+        let mut input = Input::with_text("/hi".to_string());
+        input.complete(&names);
+        assert_eq!(input.input, "/history");
+
+        let mut input = Input::with_text("好 :thumbsu".to_string());
+        input.complete(&names);
+        assert_eq!(input.input, "好 👍");
+        input.enter_char(' ');
+        for c in ":tada:".chars() {
+            input.enter_char(c);
+            input.expand_shortcode();
+        }
+        assert_eq!(input.input, "好 👍 🎉");
+    }
 
-}
\ No newline at end of file
+    #[test]
+    fn test_graphemes() {
+        // 国旗由两个字符组成，带肤色的表情由表情和肤色两个字符组成
+        let mut input = Input::new();
+        for c in "a🇨🇳👍🏽中".chars() {
+            input.enter_char(c);
+        }
+        assert_eq!(input.cursor_column(), 7);
+        input.move_cursor_left();
+        input.move_cursor_left();
+        assert_eq!(input.character_index, 3);
+        input.delete_char();
+        assert_eq!(input.input, "a👍🏽中");
+        assert_eq!(input.cursor_column(), 1);
+        input.move_cursor_right();
+        input.insert_str("🇯🇵");
+        assert_eq!(input.input, "a👍🏽🇯🇵中");
+        assert_eq!(input.character_index, 5);
+    }
+}
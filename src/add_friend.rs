@@ -1,18 +1,22 @@
-use crate::datetime::datetime_format;
+use common::datetime::{datetime_format, relative_time};
+use common::friend::{FriendReqVo, FriendRequestStatus, ReviewSummary};
+use common::config::settings;
+use common::fuzzy;
+use common::sanitize::sanitize;
+use crate::friend::fetch_friends;
 use crate::token::CURRENT_USER;
-use crate::{style, HOST};
+use crate::{delimiter, host, http_client, style};
 use chrono::{DateTime, Local};
 use dialoguer::theme::ColorfulTheme;
-use dialoguer::{Confirm, Input};
-use indexmap::IndexMap;
-use reqwest::Client;
-use serde::{Deserialize, Serialize};
-use std::fmt::{Display, Formatter};
+use dialoguer::{Confirm, Input, MultiSelect};
+use reqwest::Url;
+use serde::Deserialize;
+use std::collections::HashSet;
 
 pub(crate) async fn add_friend_select() {
     let selection = dialoguer::Select::with_theme(&ColorfulTheme::default())
         .with_prompt("请选择：")
-        .items(&vec!["添加好友", "好友申请"])
+        .items(&["添加好友", "好友申请", "已发送的申请"])
         .interact()
         .unwrap();
     match selection {
@@ -22,6 +26,9 @@ pub(crate) async fn add_friend_select() {
         1 => {
             friend_request().await;
         }
+        2 => {
+            sent_request().await;
+        }
         _ => {
             println!("error selection");
             std::process::exit(1);
@@ -29,42 +36,204 @@ pub(crate) async fn add_friend_select() {
     }
 }
 
+/// 发出的好友申请
 #[derive(Deserialize)]
-struct FriendReqVo {
+struct SentFriendReqVo {
     id: i32,
-    request_id: i32,
-    request_name: String,
+    target_id: i32,
+    #[serde(deserialize_with = "common::sanitize::line")]
+    target_name: String,
     #[serde(with = "datetime_format")]
     create_time: DateTime<Local>,
+    #[serde(default, deserialize_with = "common::sanitize::optional_text")]
     reason: Option<String>,
     status: FriendRequestStatus,
 }
 
-#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
-pub enum FriendRequestStatus {
-    WAIT,
-    APPROVE,
-    REJECT,
+async fn friend_request() {
+    let filters = FriendRequestStatus::FILTERS;
+    let filter_names = filters.iter().map(|filter| FriendRequestStatus::filter_name(*filter)).collect::<Vec<_>>();
+    let filter = filters[dialoguer::Select::with_theme(&ColorfulTheme::default())
+        .with_prompt("筛选好友申请")
+        .items(&filter_names)
+        .default(0)
+        .interact()
+        .unwrap()];
+    let url = format!("{}/friend/req", host());
+    let res = http_client()
+        .get(url)
+        .header(
+            "Authorization",
+            format!("Bearer {}", CURRENT_USER.lock().unwrap().token),
+        )
+        .send()
+        .await;
+    let friend_reqs = match res {
+        Ok(res) if res.status().is_success() => match res.json::<Vec<FriendReqVo>>().await {
+            Ok(friend_reqs) => friend_reqs
+                .into_iter()
+                .filter(|req| FriendRequestStatus::matches(filter, req.status))
+                .collect::<Vec<_>>(),
+            Err(e) => {
+                println!("Failed to parse response: {}", e);
+                std::process::exit(1);
+            }
+        },
+        Ok(res) => {
+            println!("Failed to get friend request: HTTP {}", res.status());
+            std::process::exit(1);
+        }
+        Err(err) => {
+            println!("Failed to get friend request: {}", err);
+            std::process::exit(1);
+        }
+    };
+    if friend_reqs.is_empty() {
+        println!("暂无{}的好友申请", FriendRequestStatus::filter_name(filter));
+        return;
+    }
+    let options = friend_reqs.iter().map(friend_req_option).collect::<Vec<_>>();
+    let pending = friend_reqs
+        .iter()
+        .filter(|req| req.status == FriendRequestStatus::WAIT)
+        .collect::<Vec<_>>();
+    if pending.is_empty() {
+        // 没有待处理的申请，只展示列表
+        for option in options {
+            println!("{option}");
+        }
+        return;
+    }
+    let actions = ["全部同意", "全部拒绝", "勾选后同意", "勾选后拒绝", "逐个处理"];
+    let action = dialoguer::Select::with_theme(&ColorfulTheme::default())
+        .with_prompt(format!("{} 条待处理的好友申请", pending.len()))
+        .items(&actions)
+        .default(4)
+        .interact()
+        .unwrap();
+    // 待处理的申请及处理结果，None 表示跳过
+    let decisions: Vec<(&FriendReqVo, Option<FriendRequestStatus>)> = match action {
+        0 | 1 => {
+            let status = if action == 0 { FriendRequestStatus::APPROVE } else { FriendRequestStatus::REJECT };
+            if !Confirm::with_theme(&ColorfulTheme::default())
+                .with_prompt(format!("{} {} 条好友申请么？", actions[action], pending.len()))
+                .interact()
+                .unwrap()
+            {
+                return;
+            }
+            pending.into_iter().map(|req| (req, Some(status))).collect()
+        }
+        2 | 3 => {
+            let status = if action == 2 { FriendRequestStatus::APPROVE } else { FriendRequestStatus::REJECT };
+            let items = pending.iter().map(|req| friend_req_option(req)).collect::<Vec<_>>();
+            let selections = MultiSelect::with_theme(&ColorfulTheme::default())
+                .with_prompt("空格勾选，回车确认")
+                .items(&items)
+                .interact()
+                .unwrap();
+            selections.into_iter().map(|i| (pending[i], Some(status))).collect()
+        }
+        _ => pending
+            .into_iter()
+            .map(|req| {
+                let choice = dialoguer::Select::with_theme(&ColorfulTheme::default())
+                    .with_prompt(friend_req_option(req))
+                    .items(&["同意", "拒绝", "跳过"])
+                    .default(0)
+                    .interact()
+                    .unwrap();
+                let status = match choice {
+                    0 => Some(FriendRequestStatus::APPROVE),
+                    1 => Some(FriendRequestStatus::REJECT),
+                    _ => None,
+                };
+                (req, status)
+            })
+            .collect(),
+    };
+    let mut summary = ReviewSummary::default();
+    for (req, status) in decisions {
+        if let Some(status) = status {
+            summary.record(&req.request_name, status, review(&req.id, status).await);
+        }
+    }
+    delimiter();
+    println!("{summary}");
 }
 
-impl Display for FriendRequestStatus {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "{}",
-            match self {
-                FriendRequestStatus::WAIT => "待处理",
-                FriendRequestStatus::APPROVE => "已同意",
-                FriendRequestStatus::REJECT => "已拒绝",
-            }
+fn friend_req_option(req: &FriendReqVo) -> String {
+    format!(
+        "姓名：{}\n  备注：{}\n  {} · {}",
+        req.request_name,
+        req.reason(),
+        req.status,
+        relative_time(req.create_time, Local::now())
+    )
+}
+
+async fn fetch_sent_requests() -> Result<Vec<SentFriendReqVo>, String> {
+    let url = format!("{}/friend/req/sent", host());
+    let res = http_client()
+        .get(url)
+        .header(
+            "Authorization",
+            format!("Bearer {}", CURRENT_USER.lock().unwrap().token),
         )
+        .send()
+        .await;
+    match res {
+        Ok(res) if res.status().is_success() => res.json::<Vec<SentFriendReqVo>>().await.map_err(|e| e.to_string()),
+        Ok(res) => Err(format!("HTTP {}", res.status())),
+        Err(err) => Err(err.to_string()),
     }
 }
 
-async fn friend_request() {
-    let url = format!("{HOST}/friend/req");
-    let res = Client::new()
-        .get(url)
+async fn sent_request() {
+    let sent_reqs = match fetch_sent_requests().await {
+        Ok(sent_reqs) => sent_reqs,
+        Err(err) => {
+            println!("Failed to get sent request: {}", err);
+            std::process::exit(1);
+        }
+    };
+    if sent_reqs.is_empty() {
+        println!("暂无已发送的申请");
+        return;
+    }
+    let options = sent_reqs
+        .iter()
+        .map(|req| {
+            format!(
+                "姓名：{}\n  备注：{}\n  {} · {}",
+                req.target_name,
+                req.reason.clone().unwrap_or("请求添加好友".to_string()),
+                req.status,
+                relative_time(req.create_time, Local::now())
+            )
+        })
+        .collect::<Vec<_>>();
+    let selection = dialoguer::Select::with_theme(&ColorfulTheme::default())
+        .with_prompt("已发送的申请")
+        .items(&options)
+        .interact()
+        .unwrap();
+    let req = &sent_reqs[selection];
+    // 只能撤回待处理的申请
+    if req.status == FriendRequestStatus::WAIT
+        && Confirm::with_theme(&ColorfulTheme::default())
+            .with_prompt(format!("撤回对{}的好友申请么？", req.target_name))
+            .interact()
+            .unwrap()
+    {
+        cancel_request(&req.id).await;
+    }
+}
+
+async fn cancel_request(id: &i32) {
+    let url = format!("{}/friend/req/{id}", host());
+    let res = http_client()
+        .delete(url)
         .header(
             "Authorization",
             format!("Bearer {}", CURRENT_USER.lock().unwrap().token),
@@ -74,51 +243,21 @@ async fn friend_request() {
     match res {
         Ok(res) => {
             if res.status().is_success() {
-                match res.json::<Vec<FriendReqVo>>().await {
-                    Ok(friend_reqs) if !friend_reqs.is_empty() => {
-                        let option_2_id = friend_reqs.into_iter().map(|req| {
-                            (format!("姓名：{}\n  备注：{}\n  {}", req.request_name, req.reason.clone().unwrap_or("请求添加好友".to_string()), req.status), req)
-                        }).collect::<IndexMap<String, FriendReqVo>>();
-                        let options = option_2_id.keys().map(|x| x.clone()).collect::<Vec<_>>();
-                        let selection = dialoguer::Select::with_theme(&ColorfulTheme::default())
-                            .with_prompt("好友申请列表")
-                            .items(&options)
-                            .interact()
-                            .unwrap();
-                        let req = option_2_id.get(&options[selection]).unwrap();
-                        if req.status == FriendRequestStatus::WAIT {
-                            if Confirm::with_theme(&ColorfulTheme::default())
-                                .with_prompt("同意好友申请么？")
-                                .interact()
-                                .unwrap()
-                            {
-                                review(&req.id, FriendRequestStatus::APPROVE).await;
-                            } else {
-                                review(&req.id, FriendRequestStatus::REJECT).await;
-                            }
-                        }
-                    }
-                    Ok(_) => {
-                        println!("暂无好友申请");
-                        std::process::exit(1);
-                    }
-                    Err(e) => {
-                        println!("Failed to parse response: {}", e);
-                        std::process::exit(1);
-                    }
-                }
+                println!("已撤回");
+            } else {
+                println!("撤回失败: {}", sanitize(&res.text().await.unwrap()));
             }
         }
         Err(err) => {
-            println!("Failed to get friend request: {}", err);
+            println!("Failed to send request: {}", err);
             std::process::exit(1);
         }
     }
 }
 
-async fn review(id: &i32, status: FriendRequestStatus) {
-    let url = format!("{HOST}/friend/req");
-    let res = Client::new()
+async fn review(id: &i32, status: FriendRequestStatus) -> Result<(), String> {
+    let url = format!("{}/friend/req", host());
+    let res = http_client()
         .post(url)
         .header(
             "Authorization",
@@ -131,16 +270,27 @@ async fn review(id: &i32, status: FriendRequestStatus) {
         .send()
         .await;
     match res {
-        Ok(res) => {
-            if res.status().is_success() {
-                println!("操作成功");
-            } else {
-                println!("操作失败: {}", res.text().await.unwrap());
-            }
-        }
-        Err(err) => {
-            println!("Failed to send request: {}", err);
-            std::process::exit(1);
+        Ok(res) if res.status().is_success() => Ok(()),
+        Ok(res) => Err(res.text().await.map_or_else(|_| "HTTP error".to_string(), |text| sanitize(&text))),
+        Err(err) => Err(err.to_string()),
+    }
+}
+
+/// 搜索到的用户与当前账号的关系
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Relation {
+    Stranger,
+    Friend,
+    /// 已发送申请，等待对方处理
+    Pending,
+}
+
+impl Relation {
+    fn label(&self) -> &'static str {
+        match self {
+            Relation::Stranger => "",
+            Relation::Friend => " (好友)",
+            Relation::Pending => " (已申请)",
         }
     }
 }
@@ -150,57 +300,168 @@ pub(crate) async fn add_friend() {
         .with_prompt("好友名称")
         .interact_text()
         .unwrap();
-    style::loading(format!("搜索好友: {}", name));
-    match find_friend(name).await {
-        Ok(friends) => {
-            let name_2_id = friends.into_iter().map(|friend| (friend.name, friend.id)).collect::<IndexMap<String, i32>>();
-            let names = name_2_id.keys().map(|name| name.as_str()).collect::<Vec<_>>();
-            let selection = dialoguer::Select::with_theme(&ColorfulTheme::default())
-                .with_prompt("请选择添加哪个好友")
-                .items(&names)
+    let spinner = style::spinner(format!("搜索好友: {}", name));
+    let result = find_friend(&name).await;
+    spinner.finish_and_clear();
+    let users = match result {
+        Ok(users) if users.is_empty() => {
+            println!("没有找到{name}");
+            return;
+        }
+        Ok(users) => fuzzy::rank(&name, users, |user| user.name.as_str()),
+        Err(err) => {
+            println!("Failed to find friend: {}", err);
+            std::process::exit(1);
+        }
+    };
+    let (friend_ids, pending_ids) = relations().await;
+    let relation = |user: &FindFriendRes| {
+        if friend_ids.contains(&user.id) {
+            Relation::Friend
+        } else if pending_ids.contains(&user.id) {
+            Relation::Pending
+        } else {
+            Relation::Stranger
+        }
+    };
+    let Some(user) = select_user(&users, &relation) else {
+        return;
+    };
+    preview(user, relation(user));
+    match relation(user) {
+        Relation::Friend => println!("{}已经是你的好友", user.name),
+        Relation::Pending => println!("已向{}发送过申请，等待对方处理", user.name),
+        Relation::Stranger => {
+            if Confirm::with_theme(&ColorfulTheme::default())
+                .with_prompt(format!("向{}发送好友申请么？", user.name))
+                .default(true)
                 .interact()
-                .unwrap();
-            let url = format!("{HOST}/friend/req/{}", name_2_id.get(names[selection]).unwrap());
-            let res = Client::new()
-                .post(url)
-                .header("Content-Type", "application/json")
-                .header(
-                    "Authorization",
-                    format!("Bearer {}", CURRENT_USER.lock().unwrap().token),
-                )
-                .json(&serde_json::json!({}))
-                .send()
-                .await;
-            match res {
-                Ok(res) => {
-                    if res.status().is_success() {
-                        println!("添加成功");
-                    } else {
-                        println!("添加失败: {}", res.text().await.unwrap());
-                    }
-                }
-                Err(err) => {
-                    println!("Failed to send request: {}", err);
-                    std::process::exit(1);
-                }
+                .unwrap()
+            {
+                send_request(user).await;
+            }
+        }
+    }
+}
+
+/// 分页选择用户，返回 None 表示取消
+fn select_user<'a>(users: &'a [FindFriendRes], relation: &impl Fn(&FindFriendRes) -> Relation) -> Option<&'a FindFriendRes> {
+    const PREV: &str = "上一页";
+    const NEXT: &str = "下一页";
+    const CANCEL: &str = "取消";
+    let page_size = settings().page_size.user_search.max(1);
+    let pages = users.len().div_ceil(page_size);
+    let mut page = 0;
+    loop {
+        let start = page * page_size;
+        let page_users = &users[start..users.len().min(start + page_size)];
+        let mut items = page_users
+            .iter()
+            .map(|user| format!("{}{}", user.name, relation(user).label()))
+            .collect::<Vec<_>>();
+        if page > 0 {
+            items.push(PREV.to_string());
+        }
+        if page + 1 < pages {
+            items.push(NEXT.to_string());
+        }
+        items.push(CANCEL.to_string());
+        let selection = dialoguer::Select::with_theme(&ColorfulTheme::default())
+            .with_prompt(format!("请选择添加哪个好友（第 {}/{} 页，共 {} 人）", page + 1, pages, users.len()))
+            .items(&items)
+            .default(0)
+            .interact()
+            .unwrap();
+        if let Some(user) = page_users.get(selection) {
+            return Some(user);
+        }
+        match items[selection].as_str() {
+            PREV => page -= 1,
+            NEXT => page += 1,
+            _ => return None,
+        }
+    }
+}
+
+/// 发送申请前预览用户资料
+fn preview(user: &FindFriendRes, relation: Relation) {
+    delimiter();
+    println!("用户名：{}{}", user.name, relation.label());
+    println!("邮箱：{}", user.email.as_deref().unwrap_or("-"));
+    println!("手机：{}", user.phone.as_deref().unwrap_or("-"));
+    delimiter();
+}
+
+async fn send_request(user: &FindFriendRes) {
+    let reason: String = Input::with_theme(&ColorfulTheme::default())
+        .with_prompt("申请理由（可选）")
+        .allow_empty(true)
+        .interact_text()
+        .unwrap();
+    let reason = Some(reason.trim().to_string()).filter(|reason| !reason.is_empty());
+    let url = format!("{}/friend/req/{}", host(), user.id);
+    let res = http_client()
+        .post(url)
+        .header("Content-Type", "application/json")
+        .header(
+            "Authorization",
+            format!("Bearer {}", CURRENT_USER.lock().unwrap().token),
+        )
+        .json(&serde_json::json!({ "reason": reason }))
+        .send()
+        .await;
+    match res {
+        Ok(res) => {
+            if res.status().is_success() {
+                println!("添加成功");
+            } else {
+                println!("添加失败: {}", sanitize(&res.text().await.unwrap()));
             }
         }
         Err(err) => {
-            println!("Failed to find friend: {}", err);
+            println!("Failed to send request: {}", err);
             std::process::exit(1);
         }
     }
 }
 
+/// 好友和待处理的已发送申请的用户id，获取失败时视为空
+async fn relations() -> (HashSet<i32>, HashSet<i32>) {
+    let friend_ids = fetch_friends()
+        .await
+        .map(|friends| friends.iter().map(|friend| friend.id).collect())
+        .unwrap_or_default();
+    let pending_ids = fetch_sent_requests()
+        .await
+        .map(|reqs| {
+            reqs.iter()
+                .filter(|req| req.status == FriendRequestStatus::WAIT)
+                .map(|req| req.target_id)
+                .collect()
+        })
+        .unwrap_or_default();
+    (friend_ids, pending_ids)
+}
+
 #[derive(Deserialize)]
 struct FindFriendRes {
     id: i32,
+    #[serde(deserialize_with = "common::sanitize::line")]
     name: String,
+    #[serde(default, deserialize_with = "common::sanitize::optional_text")]
+    email: Option<String>,
+    #[serde(default, deserialize_with = "common::sanitize::optional_text")]
+    phone: Option<String>,
 }
 
-async fn find_friend(name: String) -> Result<Vec<FindFriendRes>, String> {
-    let url = format!("{HOST}/user/find/{name}");
-    let res = Client::new()
+async fn find_friend(name: &str) -> Result<Vec<FindFriendRes>, String> {
+    // 用户名作为路径的一段，需要转义
+    let mut url = Url::parse(host()).map_err(|err| err.to_string())?;
+    url.path_segments_mut()
+        .map_err(|_| format!("invalid server {}", host()))?
+        .pop_if_empty()
+        .extend(["user", "find", name]);
+    let res = http_client()
         .get(url)
         .header(
             "Authorization",
@@ -211,27 +472,11 @@ async fn find_friend(name: String) -> Result<Vec<FindFriendRes>, String> {
     match res {
         Ok(res) => {
             if res.status().is_success() {
-                match res.json::<Vec<FindFriendRes>>().await {
-                    Ok(friends) => {
-                        println!("Found {} friends", friends.len());
-                        for friend in &friends {
-                            println!("{}", friend.name);
-                        }
-                        Ok(friends)
-                    }
-                    Err(e) => {
-                        println!("Failed to parse response: {}", e);
-                        Err(e.to_string())
-                    }
-                }
+                res.json::<Vec<FindFriendRes>>().await.map_err(|e| e.to_string())
             } else {
-                println!("Find failed: HTTP {}", res.status());
-                Err(res.text().await.unwrap())
+                Err(format!("HTTP {}: {}", res.status(), sanitize(&res.text().await.unwrap_or_default())))
             }
         }
-        Err(err) => {
-            println!("Failed to send find request: {}", err);
-            Err(err.to_string())
-        }
+        Err(err) => Err(err.to_string()),
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,100 @@
+use crate::token::CURRENT_USER;
+use crate::{host, stream_client, style};
+use common::attachment::{content_type, save_path, Attachment};
+use common::sanitize::sanitize;
+use futures::StreamExt;
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+/// 每次读取上传文件的字节数
+const CHUNK_SIZE: usize = 64 * 1024;
+
+#[derive(Deserialize)]
+struct Uploaded {
+    url: String,
+}
+
+/// 上传文件并显示进度，返回发送附件消息需要的文件信息
+pub(crate) async fn upload(path: &Path) -> Result<Attachment, String> {
+    let name = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .ok_or_else(|| format!("{} 不是文件", path.display()))?
+        .to_string();
+    let file = tokio::fs::File::open(path).await.map_err(|err| format!("打开{}失败: {err}", path.display()))?;
+    let size = file.metadata().await.map_err(|err| err.to_string())?.len();
+    let content_type = content_type(&name).to_string();
+    let progress = style::progress(size, format!("上传 {name}"));
+    let body_progress = progress.clone();
+    // 按块读取文件，边读边上传
+    let stream = futures::stream::unfold(file, move |mut file| {
+        let progress = body_progress.clone();
+        async move {
+            let mut chunk = vec![0; CHUNK_SIZE];
+            match file.read(&mut chunk).await {
+                Ok(0) => None,
+                Ok(n) => {
+                    chunk.truncate(n);
+                    progress.inc(n as u64);
+                    Some((Ok::<_, std::io::Error>(chunk), file))
+                }
+                Err(err) => Some((Err(err), file)),
+            }
+        }
+    });
+    let res = stream_client()
+        .post(format!("{}/file", host()))
+        .query(&[("name", name.as_str())])
+        .header("Authorization", format!("Bearer {}", CURRENT_USER.lock().unwrap().token))
+        .header("Content-Type", &content_type)
+        .header("Content-Length", size)
+        .body(reqwest::Body::wrap_stream(stream))
+        .send()
+        .await
+        .map_err(|err| err.to_string());
+    progress.finish_and_clear();
+    let res = res?;
+    if !res.status().is_success() {
+        return Err(format!("{}, {}", res.status(), sanitize(&res.text().await.unwrap_or_default())));
+    }
+    let uploaded = res.json::<Uploaded>().await.map_err(|err| err.to_string())?;
+    Ok(Attachment { name, size, url: uploaded.url, content_type })
+}
+
+/// 下载附件到 `dir` 并显示进度，返回保存的路径
+pub(crate) async fn download(attachment: &Attachment, dir: &Path) -> Result<PathBuf, String> {
+    // 只给自己的服务端带上登录信息
+    let (url, own_server) = attachment.download_url(host())?;
+    let mut request = stream_client().get(url);
+    if own_server {
+        request = request.header("Authorization", format!("Bearer {}", CURRENT_USER.lock().unwrap().token));
+    }
+    let res = request.send().await.map_err(|err| err.to_string())?;
+    if !res.status().is_success() {
+        return Err(format!("HTTP {}", res.status()));
+    }
+    tokio::fs::create_dir_all(dir).await.map_err(|err| format!("创建{}失败: {err}", dir.display()))?;
+    let path = save_path(dir, &attachment.name);
+    let mut file = tokio::fs::File::create(&path).await.map_err(|err| format!("创建{}失败: {err}", path.display()))?;
+    let progress = style::progress(res.content_length().unwrap_or(attachment.size), format!("下载 {}", attachment.name));
+    let mut stream = res.bytes_stream();
+    let result = async {
+        while let Some(chunk) = stream.next().await {
+            let chunk = chunk.map_err(|err| err.to_string())?;
+            file.write_all(&chunk).await.map_err(|err| err.to_string())?;
+            progress.inc(chunk.len() as u64);
+        }
+        file.flush().await.map_err(|err| err.to_string())
+    }
+    .await;
+    progress.finish_and_clear();
+    match result {
+        Ok(_) => Ok(path),
+        Err(err) => {
+            // 不保留下载了一半的文件
+            let _ = tokio::fs::remove_file(&path).await;
+            Err(err)
+        }
+    }
+}
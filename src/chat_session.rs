@@ -0,0 +1,705 @@
+use crate::friend::{request_friends, save_contact_book};
+use crate::token::CURRENT_USER;
+use crate::group::fetch_groups;
+use crate::{attachment, console, e2e, host, http_client, mention, style};
+use common::attachment::{download_dir, expand_home};
+use common::command::{help, parse, ChatInput, Command};
+use common::config::settings;
+use common::contact::ContactBook;
+use common::e2e::{fingerprint, KeyCheck, KeyStore};
+use common::edit::{amend, check_recall, own_message};
+use common::emoji;
+use common::group::{is_gone, GROUP_GONE};
+use common::mention::mentions;
+use chrono::{Local, TimeZone};
+use common::message::{
+    ChatMessage, HistoryMsg, MessageDetail, MessageTarget, MessageTargetGroup, MessageTargetUser, ReactionMessage, ReadIndexMessage, TypingMessage,
+};
+use common::notify::{should_notify, Notification, Notifier};
+use common::reaction::{react, reacted, resolve, summary, PICKER};
+use common::text::{single_line, truncate};
+use common::outbox::{Outbox, Queued, MAX_ATTEMPTS};
+use common::receipt::{Delivery, Receipts};
+use common::typing::TypingIndicator;
+use common::sanitize::sanitize;
+use crossterm::style::Stylize;
+use crossterm::terminal::ClearType::CurrentLine;
+use crossterm::terminal::{Clear, SetTitle};
+use crossterm::{cursor, execute};
+use std::collections::{HashMap, HashSet};
+use std::io::stdout;
+use std::time::Instant;
+
+/// 终端标题
+const TITLE: &str = "chat-cli";
+
+/// 单聊和群聊共用的聊天状态，处理输入的消息和斜杠命令
+pub(crate) struct ChatSession {
+    target: MessageTarget,
+    /// 聊天记录，包括聊天过程中收到的消息
+    history: Vec<HistoryMsg>,
+    /// 末尾已显示的聊天记录条数，`/more` 从这里往前显示
+    shown: usize,
+    /// 发送者id到显示名称
+    names: HashMap<i32, String>,
+    /// @提及的候选
+    mention_names: Vec<String>,
+    book: ContactBook,
+    my_uid: i32,
+    /// 正在输入的成员，提示显示在终端标题上，不打断输入
+    typing: TypingIndicator,
+    typing_status: Option<String>,
+    /// 自己发出的消息的状态
+    receipts: Receipts,
+    /// 发送失败等待重发的消息，包括其他会话的
+    outbox: Outbox,
+    /// 端到端加密的密钥
+    keys: KeyStore,
+    /// 对方变化后的公钥，再次输入 `/encrypt on` 时信任
+    pending_key: Option<String>,
+    /// 其他会话的新消息通知
+    notifier: Notifier,
+    /// 聊天过程中其他会话通知过的消息数和有人@我的会话，显示在状态栏
+    unread: u32,
+    mentioned: HashSet<MessageTarget>,
+    /// 其他会话通知中显示的群名和好友用户名
+    group_names: HashMap<i32, String>,
+    friend_names: HashMap<i32, String>,
+}
+
+impl ChatSession {
+    pub(crate) fn new(
+        target: MessageTarget,
+        history: Vec<HistoryMsg>,
+        names: HashMap<i32, String>,
+        mention_names: Vec<String>,
+        book: ContactBook,
+    ) -> Self {
+        let my_uid = CURRENT_USER.lock().unwrap().user.id;
+        let mut outbox = Outbox::load(host(), my_uid).unwrap_or_else(|err| {
+            println!("{err}");
+            Outbox::default()
+        });
+        let keys = KeyStore::load(host(), my_uid).unwrap_or_else(|err| {
+            println!("{err}");
+            KeyStore::default()
+        });
+        outbox.reveal(&keys);
+        let mut session = Self {
+            target,
+            history: Vec::new(),
+            shown: 0,
+            names,
+            mention_names,
+            book,
+            my_uid,
+            typing: TypingIndicator::default(),
+            typing_status: None,
+            receipts: Receipts::default(),
+            outbox,
+            keys,
+            pending_key: None,
+            notifier: Notifier::default(),
+            unread: 0,
+            mentioned: HashSet::new(),
+            group_names: HashMap::new(),
+            friend_names: HashMap::new(),
+        };
+        let mut history = history;
+        history.iter_mut().for_each(|msg| session.reveal(msg));
+        session.history = history;
+        session
+    }
+
+    /// 单聊中的加密消息替换为原文，对方的公钥只在 `/encrypt on` 时获取并记住
+    fn reveal(&self, msg: &mut HistoryMsg) {
+        if let MessageTarget::User(MessageTargetUser { uid }) = self.target {
+            self.keys.reveal(msg, uid, self.my_uid);
+        }
+    }
+
+    /// 对方用新的公钥发来消息时提示核对指纹
+    fn warn_key_change(&mut self, msg: &HistoryMsg) {
+        let Some(envelope) = msg.encrypted.as_ref().filter(|_| msg.from_uid != self.my_uid) else {
+            return;
+        };
+        if self.keys.peer_key(msg.from_uid).is_some_and(|key| key != envelope.sender_key) {
+            println!("{}", format!("对方的密钥已变化，新指纹：{}", fingerprint(&envelope.sender_key)).red());
+            println!("{}", "请通过其他渠道核对指纹，确认后输入 /encrypt on 信任新密钥".red());
+        }
+    }
+
+    fn save_keys(&self) {
+        if let Err(err) = self.keys.save() {
+            println!("保存失败: {err}");
+        }
+    }
+
+    pub(crate) fn book(&self) -> &ContactBook {
+        &self.book
+    }
+
+    /// 发送者是否已知，群聊中有新成员时需要重新加载成员
+    pub(crate) fn knows(&self, uid: i32) -> bool {
+        uid == self.my_uid || self.names.contains_key(&uid)
+    }
+
+    /// 获取群列表和好友列表，用于其他会话的通知，获取失败时通知中显示id
+    pub(crate) async fn load_conversation_names(&mut self) {
+        if let Ok(groups) = fetch_groups().await {
+            self.group_names = groups.into_iter().map(|group| (group.gid, group.name)).collect();
+        }
+        if let Ok(friends) = request_friends().await {
+            self.friend_names = friends.into_iter().map(|friend| (friend.id, friend.name)).collect();
+        }
+    }
+
+    pub(crate) fn set_names(&mut self, names: HashMap<i32, String>, mention_names: Vec<String>) {
+        self.names = names;
+        self.mention_names = mention_names;
+    }
+
+    /// 显示最近的聊天记录和还没有发出的消息
+    pub(crate) fn print_recent(&mut self) {
+        self.print_history(settings().page_size.history);
+        if let MessageTarget::User(MessageTargetUser { uid }) = self.target {
+            if self.keys.is_enabled(uid) {
+                println!("{}", "🔒 已开启端到端加密，输入 /fingerprint 核对指纹".green());
+            }
+        }
+        for queued in self.outbox.queued(self.target) {
+            let time = Local.timestamp_millis_opt(queued.created_at).single().unwrap_or_else(Local::now);
+            mention::print_message(&time, "You", &format!("{} {}", style::markdown(&queued.msg), queued_status(queued).red()));
+        }
+    }
+
+    /// 收到消息流中的消息，显示当前会话的新消息并返回是否显示了。
+    /// 自己发出的消息从重发队列中移除，避免重复发送；编辑和撤回更新聊天记录后另起一行提示
+    pub(crate) fn receive(&mut self, message: &ChatMessage) -> bool {
+        let from_uid = message.payload.from_uid;
+        let conversation = message.payload.conversation(self.my_uid);
+        if message.payload.detail.amended().is_some() {
+            if conversation == self.target && !self.book.is_blocked(from_uid) {
+                self.on_amend(message);
+            }
+            return false;
+        }
+        if from_uid == self.my_uid && self.outbox.echoed(&message.payload) {
+            self.save_outbox();
+        }
+        if self.book.is_blocked(from_uid) {
+            return false;
+        }
+        if conversation != self.target {
+            if from_uid != self.my_uid {
+                self.notify(message, conversation);
+            }
+            return false;
+        }
+        let mut msg = HistoryMsg::from(message);
+        self.warn_key_change(&msg);
+        self.reveal(&mut msg);
+        self.typing.clear(from_uid);
+        self.show_typing();
+        if from_uid == self.my_uid && self.receipts.delivered(message.payload.client_id.as_deref()) {
+            // 发送时已经显示过，之后可能又显示了其他内容，不再回头替换，只提示送达
+            println!("{}", format!("  {} {}", Delivery::Delivered, truncate(&single_line(&msg.msg), 30)).dark_grey());
+        } else {
+            self.print(&msg);
+        }
+        self.history.push(msg);
+        self.shown += 1;
+        true
+    }
+
+    /// 其他会话的新消息，按免打扰和通知设置发出通知
+    fn notify(&mut self, message: &ChatMessage, conversation: MessageTarget) {
+        let content = message.payload.detail.get_content();
+        let mentioned = mentions(&content, &mention::my_name());
+        if !should_notify(&settings().notify, self.book.is_muted(conversation), mentioned, Local::now().time()) {
+            return;
+        }
+        let sender = self.sender_name(message.payload.from_uid);
+        let title = match conversation {
+            MessageTarget::User(_) => sender.clone(),
+            MessageTarget::Group(MessageTargetGroup { gid }) => self.group_names.get(&gid).cloned().unwrap_or_else(|| format!("群{gid}")),
+        };
+        self.notifier.notify(&Notification::new(&title, &sender, &content, mentioned));
+        self.unread += 1;
+        if mentioned {
+            self.mentioned.insert(conversation);
+        }
+        self.notifier.update_status(self.unread, self.mentioned.len());
+    }
+
+    fn on_amend(&mut self, message: &ChatMessage) {
+        let name = self.sender_name(message.payload.from_uid);
+        let Some(msg) = amend(&mut self.history, &message.payload) else {
+            return;
+        };
+        if let MessageDetail::Recall(_) = message.payload.detail {
+            println!("{}", format!("{name} 撤回了一条消息").dark_grey());
+        } else {
+            let msg = msg.clone();
+            println!("{}", format!("{name} 编辑了一条消息：").dark_grey());
+            self.print(&msg);
+        }
+    }
+
+    /// 重发到时间的消息，多次失败后提示手动重试
+    pub(crate) async fn flush_outbox(&mut self) {
+        let now = Local::now().timestamp_millis();
+        let due = self.outbox.due(now);
+        if due.is_empty() {
+            return;
+        }
+        for queued in due {
+            match send(&queued).await {
+                // 群已解散的消息不再重发
+                Ok(_) => self.outbox.sent(&queued.client_id),
+                Err(err) if err == GROUP_GONE => self.outbox.sent(&queued.client_id),
+                Err(_) => {
+                    self.outbox.attempt_failed(&queued.client_id, now);
+                    if queued.target == self.target && queued.attempts + 1 >= MAX_ATTEMPTS {
+                        println!("{}", format!("「{}」发送失败，输入 /retry 重试", queued.msg).red());
+                    }
+                }
+            }
+        }
+        self.save_outbox();
+    }
+
+    fn save_outbox(&self) {
+        if let Err(err) = self.outbox.save() {
+            println!("保存失败: {err}");
+        }
+    }
+
+    /// 收到正在输入的通知，只处理当前会话中其他人的通知
+    pub(crate) fn on_typing(&mut self, message: &TypingMessage) {
+        if message.conversation() != self.target || message.from_uid == self.my_uid || self.book.is_blocked(message.from_uid) {
+            return;
+        }
+        self.typing.on_typing(message.from_uid, Instant::now());
+        self.show_typing();
+    }
+
+    /// 收到表情回应，更新聊天记录，其他人回应时提示
+    pub(crate) fn on_reaction(&mut self, message: &ReactionMessage) {
+        if message.conversation(self.my_uid) != self.target || self.book.is_blocked(message.from_uid) {
+            return;
+        }
+        let name = self.sender_name(message.from_uid);
+        let Some(msg) = react(&mut self.history, message) else {
+            return;
+        };
+        if !message.removed && message.from_uid != self.my_uid {
+            let preview = truncate(&single_line(&msg.display()), 20);
+            println!("{}", format!("{name} 回应了「{preview}」 {}", message.emoji).dark_grey());
+        }
+    }
+
+    /// 其他人的已读位置更新，自己最后一条消息变为已读时提示
+    pub(crate) fn on_read(&mut self, message: &ReadIndexMessage) {
+        if message.conversation() != self.target || message.uid == self.my_uid {
+            return;
+        }
+        let last = self.visible().into_iter().rev().find(|msg| msg.from_uid == self.my_uid).map(|msg| msg.mid);
+        let unread = last.filter(|mid| self.receipts.state(*mid) != Delivery::Read);
+        if self.receipts.read(message.mid) && unread.is_some_and(|mid| mid <= message.mid) {
+            println!("{}", format!("  {}", Delivery::Read).dark_grey());
+        }
+    }
+
+    /// 在终端标题上显示谁正在输入，超时后恢复
+    pub(crate) fn show_typing(&mut self) {
+        let status = self.typing.status(Instant::now(), |uid| self.sender_name(uid));
+        if status != self.typing_status {
+            let title = status.as_ref().map_or(TITLE.to_string(), |status| format!("{status} - {TITLE}"));
+            let _ = execute!(stdout(), SetTitle(title));
+            self.typing_status = status;
+        }
+    }
+
+    /// 处理一行输入，返回是否继续聊天
+    pub(crate) async fn handle_input(&mut self, input: &str) -> bool {
+        let command = match parse(input) {
+            Ok(ChatInput::Text(text)) if text.is_empty() => return true,
+            Ok(ChatInput::Text(text)) => return self.send_text(&text, None).await,
+            Ok(ChatInput::Command(command)) => command,
+            Err(err) => {
+                println!("{err}");
+                return true;
+            }
+        };
+        match command {
+            Command::Exit => {
+                println!("退出...");
+                return false;
+            }
+            Command::Help(name) => match help(name.as_deref()) {
+                Ok(lines) => lines.iter().for_each(|line| println!("{line}")),
+                Err(err) => println!("{err}"),
+            },
+            Command::History(count) => self.print_history(count.unwrap_or(settings().page_size.history)),
+            Command::More => self.print_more(),
+            Command::Reply { index, text } => {
+                let visible = self.visible();
+                match visible.len().checked_sub(index).map(|position| visible[position].mid) {
+                    Some(mid) => return self.send_text(&text, Some(mid)).await,
+                    None => println!("没有第{index}条消息"),
+                }
+            }
+            Command::Edit { .. } if self.encrypted() => println!("加密会话暂不支持编辑"),
+            Command::Edit { index, text } => match own_message(&self.visible(), index, self.my_uid) {
+                Ok(msg) => {
+                    let Some(text) = mention::expand_input(&emoji::expand(&text), &self.mention_names) else {
+                        return true;
+                    };
+                    let body = serde_json::json!({ "edit": msg.mid, "msg": text });
+                    if let Err(err) = post(self.target, body).await {
+                        println!("编辑失败: {err}");
+                    }
+                }
+                Err(err) => println!("{err}"),
+            },
+            Command::Recall(index) => {
+                let visible = self.visible();
+                let result = own_message(&visible, index, self.my_uid)
+                    .and_then(|msg| check_recall(msg, Local::now(), settings().chat.recall_limit).map(|_| msg.mid));
+                match result {
+                    Ok(mid) => {
+                        if let Err(err) = post(self.target, serde_json::json!({ "recall": mid })).await {
+                            println!("撤回失败: {err}");
+                        }
+                    }
+                    Err(err) => println!("{err}"),
+                }
+            }
+            Command::React { index, emoji } => {
+                let visible = self.visible();
+                let Some(msg) = visible.len().checked_sub(index).map(|position| visible[position]) else {
+                    println!("没有第{index}条消息");
+                    return true;
+                };
+                let Some(emoji) = emoji.as_deref().map(resolve) else {
+                    let choices = PICKER.iter().enumerate().map(|(i, emoji)| format!("{}.{emoji}", i + 1)).collect::<Vec<_>>();
+                    println!("可选表情：{}", choices.join(" "));
+                    println!("用法：/react <序号> <表情或编号>");
+                    return true;
+                };
+                let removed = reacted(&msg.reactions, self.my_uid, &emoji);
+                let body = serde_json::json!({ "react": msg.mid, "emoji": emoji, "remove": removed });
+                if let Err(err) = post(self.target, body).await {
+                    println!("回应失败: {err}");
+                }
+            }
+            // 附件不加密，加密会话中不发送
+            Command::SendFile(_) if self.encrypted() => println!("加密会话暂不支持发送附件"),
+            Command::SendFile(path) => match attachment::upload(&expand_home(&path)).await {
+                Ok(attachment) => {
+                    let content = attachment.to_content();
+                    let body = serde_json::json!({
+                        "msg": content.content,
+                        "content_type": content.content_type,
+                        "properties": content.properties,
+                    });
+                    if let Err(err) = post(self.target, body).await {
+                        println!("Send message failed: {err}");
+                    }
+                }
+                Err(err) => println!("上传失败: {err}"),
+            },
+            Command::Download { mid, dir } => {
+                let Some(found) = self.history.iter().find(|msg| msg.mid == mid && !msg.recalled).and_then(|msg| msg.attachment.clone()) else {
+                    println!("消息{mid}不是附件");
+                    return true;
+                };
+                let dir = dir.map_or_else(download_dir, |dir| expand_home(&dir));
+                match attachment::download(&found, &dir).await {
+                    Ok(path) => println!("已保存到 {}", path.display()),
+                    Err(err) => println!("下载失败: {err}"),
+                }
+            }
+            Command::Search(keyword) => self.search(&keyword),
+            Command::Clear => console::clean_all(),
+            Command::Me(action) => return self.send_text(&format!("* {} {action}", mention::my_name()), None).await,
+            Command::Who => {
+                let mut names = self.names.values().cloned().collect::<Vec<_>>();
+                names.sort();
+                println!("成员（{}）：{}", names.len() + 1, ["You".to_string()].into_iter().chain(names).collect::<Vec<_>>().join("、"));
+            }
+            Command::Mute => {
+                let muted = !self.book.is_muted(self.target);
+                self.book.set_muted(self.target, muted);
+                save_contact_book(&self.book, if muted { "已开启免打扰" } else { "已关闭免打扰" });
+            }
+            Command::Encrypt(enabled) => self.set_encrypted(enabled).await,
+            Command::Fingerprint => self.print_fingerprints(),
+            Command::Retry => match self.outbox.retry(self.target, Local::now().timestamp_millis()) {
+                0 => println!("没有发送失败的消息"),
+                count => {
+                    println!("正在重发{count}条消息");
+                    self.flush_outbox().await;
+                }
+            },
+        }
+        true
+    }
+
+    /// 当前单聊是否开启了加密
+    fn encrypted(&self) -> bool {
+        matches!(self.target, MessageTarget::User(MessageTargetUser { uid }) if self.keys.is_enabled(uid))
+    }
+
+    /// 开启或关闭加密，`enabled` 为空时切换。开启时发布自己的公钥并获取对方的公钥，
+    /// 对方的公钥变化时先提示核对指纹，再次开启才信任新公钥
+    async fn set_encrypted(&mut self, enabled: Option<bool>) {
+        let MessageTarget::User(MessageTargetUser { uid }) = self.target else {
+            println!("只有单聊支持端到端加密");
+            return;
+        };
+        if !enabled.unwrap_or(!self.keys.is_enabled(uid)) {
+            self.keys.set_enabled(uid, false);
+            self.save_keys();
+            println!("已关闭端到端加密");
+            return;
+        }
+        if let Err(err) = e2e::publish(&self.keys).await {
+            println!("发布公钥失败: {err}");
+            return;
+        }
+        let key = match e2e::fetch(uid).await {
+            Ok(key) => key,
+            Err(err) => {
+                println!("获取对方公钥失败: {err}");
+                return;
+            }
+        };
+        if self.keys.check(uid, &key) == KeyCheck::Changed {
+            if self.pending_key.as_deref() != Some(key.as_str()) {
+                let old = self.keys.peer_key(uid).map(fingerprint).unwrap_or_default();
+                println!("{}", "对方的密钥已变化，可能是对方换了设备，也可能是有人冒充".red());
+                println!("原指纹：{old}");
+                println!("新指纹：{}", fingerprint(&key));
+                println!("请通过其他渠道核对新指纹，确认后再次输入 /encrypt on 信任新密钥");
+                self.pending_key = Some(key);
+                return;
+            }
+            self.keys.trust(uid, &key);
+        }
+        self.pending_key = None;
+        self.keys.set_enabled(uid, true);
+        self.save_keys();
+        println!("{}", "🔒 已开启端到端加密".green());
+        self.print_fingerprints();
+    }
+
+    /// 显示双方的指纹，双方看到的应该一致
+    fn print_fingerprints(&self) {
+        let MessageTarget::User(MessageTargetUser { uid }) = self.target else {
+            println!("只有单聊支持端到端加密");
+            return;
+        };
+        match self.keys.public_key() {
+            Ok(key) => println!("我的指纹：{}", fingerprint(&key)),
+            Err(err) => println!("{err}"),
+        }
+        match self.keys.peer_key(uid) {
+            Some(key) => println!("{}的指纹：{}", self.sender_name(uid), fingerprint(key)),
+            None => println!("还没有对方的公钥，输入 /encrypt on 获取"),
+        }
+    }
+
+    /// 补全@提及后发送，群已解散时结束聊天
+    async fn send_text(&mut self, text: &str, reply: Option<i64>) -> bool {
+        // 展开表情短代码，补全不完整的@提及，有歧义时不发送
+        let Some(msg) = mention::expand_input(&emoji::expand(text), &self.mention_names) else {
+            return true;
+        };
+        // 加密会话先加密，重发时发送同一份密文
+        let encrypted = match self.keys.encrypt_for(self.target, &msg) {
+            Ok(encrypted) => encrypted,
+            Err(err) => {
+                println!("{err}");
+                return true;
+            }
+        };
+        // 先保存到重发队列，发送成功后移除
+        let now = Local::now().timestamp_millis();
+        let queued = self.outbox.push(self.target, &msg, encrypted, reply, now);
+        let client_id = queued.client_id.clone();
+        self.save_outbox();
+        // 输入的那一行替换为带状态的消息
+        let id = self.receipts.send(&msg, &client_id);
+        clear_lines(1);
+        let rows = self.print_outgoing(&msg, Delivery::Pending);
+        let result = send(&queued).await;
+        let keep = match result {
+            Ok(_) => {
+                self.outbox.sent(&client_id);
+                self.receipts.sent(id);
+                // 发送过程中不会显示其他内容，发送时显示的消息还在最后
+                clear_lines(rows);
+                self.print_outgoing(&msg, Delivery::Sent);
+                true
+            }
+            Err(err) if err == GROUP_GONE => {
+                self.outbox.sent(&client_id);
+                println!("{err}");
+                false
+            }
+            Err(err) => {
+                self.outbox.attempt_failed(&client_id, now);
+                self.receipts.failed(id);
+                println!("Send message failed: {err}，稍后自动重发");
+                true
+            }
+        };
+        self.save_outbox();
+        keep
+    }
+
+    /// 显示自己发出的消息，返回占的行数
+    fn print_outgoing(&self, msg: &str, state: Delivery) -> u16 {
+        mention::print_message(&Local::now(), "You", &format!("{} {}", style::markdown(msg), state.to_string().dark_grey()))
+    }
+
+    /// 没有屏蔽的聊天记录
+    fn visible(&self) -> Vec<&HistoryMsg> {
+        self.history.iter().filter(|msg| !self.book.is_blocked(msg.from_uid)).collect()
+    }
+
+    fn print_history(&mut self, count: usize) {
+        let visible = self.visible();
+        if visible.is_empty() {
+            println!("No chat history available.");
+            return;
+        }
+        let start = visible.len().saturating_sub(count);
+        visible[start..].iter().for_each(|msg| self.print(msg));
+        let shown = visible.len() - start;
+        self.shown = shown;
+    }
+
+    fn print_more(&mut self) {
+        let visible = self.visible();
+        let end = visible.len().saturating_sub(self.shown);
+        if end == 0 {
+            println!("没有更早的聊天记录");
+            return;
+        }
+        let start = end.saturating_sub(settings().page_size.history);
+        println!("---------------- 更早的聊天记录 ----------------");
+        visible[start..end].iter().for_each(|msg| self.print(msg));
+        let shown = visible.len() - start;
+        self.shown = shown;
+    }
+
+    fn search(&self, keyword: &str) {
+        let keyword = keyword.to_lowercase();
+        let matched = self
+            .visible()
+            .into_iter()
+            .filter(|msg| !msg.recalled && msg.msg.to_lowercase().contains(&keyword))
+            .collect::<Vec<_>>();
+        if matched.is_empty() {
+            println!("没有找到包含{keyword}的消息");
+            return;
+        }
+        println!("找到{}条消息：", matched.len());
+        matched.into_iter().for_each(|msg| self.print(msg));
+    }
+
+    /// 打印一条消息，自己发出的消息后面显示状态，有表情回应时另起一行显示；附件后面提示下载命令
+    fn print(&self, msg: &HistoryMsg) {
+        let content = match &msg.attachment {
+            Some(attachment) if !msg.recalled => {
+                format!("{} {}", format!("📎 {}", attachment.text()).cyan(), format!("/download {}", msg.mid).dark_grey())
+            }
+            _ if msg.recalled => msg.display(),
+            _ if msg.encrypted.is_some() => format!("🔒 {}", style::markdown(&msg.display())),
+            _ => style::markdown(&msg.display()),
+        };
+        if msg.from_uid == self.my_uid {
+            let state = self.receipts.state(msg.mid).to_string().dark_grey();
+            mention::print_message(&msg.time, "You", &format!("{content} {state}"));
+        } else {
+            mention::print_message(&msg.time, &self.sender_name(msg.from_uid), &content);
+        }
+        if !msg.reactions.is_empty() && !msg.recalled {
+            println!("    {}", summary(&msg.reactions).dark_grey());
+        }
+    }
+
+    fn sender_name(&self, uid: i32) -> String {
+        if uid == self.my_uid {
+            return "You".to_string();
+        }
+        // 当前会话以外的发送者按备注、好友用户名的顺序查找
+        self.names.get(&uid).cloned().unwrap_or_else(|| {
+            let name = self.friend_names.get(&uid).cloned().unwrap_or_else(|| uid.to_string());
+            self.book.display_name(uid, &name)
+        })
+    }
+}
+
+/// 重发队列中消息的状态
+fn queued_status(queued: &Queued) -> String {
+    if queued.failed() {
+        "发送失败，输入 /retry 重试".to_string()
+    } else {
+        format!("等待重发（已失败{}次）", queued.attempts)
+    }
+}
+
+/// 清空最后 `count` 行，如用户输入的那一行或发送时显示的消息
+fn clear_lines(count: u16) {
+    for _ in 0..count {
+        let _ = execute!(stdout(), cursor::MoveUp(1), Clear(CurrentLine));
+    }
+}
+
+/// 发送队列中的消息，客户端id用于服务端识别重发的消息。
+/// 加密消息发送密文，服务端只能看到占位文字
+async fn send(queued: &Queued) -> Result<(), String> {
+    let mut body = match &queued.encrypted {
+        Some(envelope) => {
+            let content = envelope.to_content();
+            serde_json::json!({
+                "msg": content.content,
+                "content_type": content.content_type,
+                "properties": content.properties,
+                "client_id": queued.client_id,
+            })
+        }
+        None => serde_json::json!({ "msg": queued.msg, "client_id": queued.client_id }),
+    };
+    if let Some(mid) = queued.reply {
+        body["reply"] = mid.into();
+    }
+    post(queued.target, body).await
+}
+
+/// 向会话发送消息、编辑、撤回或表情回应；群已解散或已被移出群聊时返回 `GROUP_GONE`
+async fn post(target: MessageTarget, body: serde_json::Value) -> Result<(), String> {
+    let url = match target {
+        MessageTarget::User(MessageTargetUser { uid }) => format!("{}/user/{uid}/send", host()),
+        MessageTarget::Group(MessageTargetGroup { gid }) => format!("{}/group/{gid}/send", host()),
+    };
+    let res = http_client()
+        .post(url)
+        .header("Authorization", format!("Bearer {}", CURRENT_USER.lock().unwrap().token))
+        .json(&body)
+        .send()
+        .await
+        .map_err(|err| err.to_string())?;
+    let status = res.status();
+    if status.is_success() {
+        Ok(())
+    } else if matches!(target, MessageTarget::Group(_)) && is_gone(status.as_u16()) {
+        Err(GROUP_GONE.to_string())
+    } else {
+        Err(format!("{}, {}", status, sanitize(&res.text().await.unwrap_or_default())))
+    }
+}
@@ -1,152 +0,0 @@
-use chrono::{DateTime, Local, NaiveDateTime, Offset};
-
-/// 自定义 Option<DateTime> 序列化
-pub mod opt_native_datetime_format {
-    use chrono::NaiveDateTime;
-    use serde::{self, Deserialize, Deserializer, Serializer};
-
-    const FORMAT: &'static str = "%Y-%m-%d %H:%M:%S";
-
-    pub type OK = ();
-
-    pub fn serialize<S>(date: &Option<NaiveDateTime>, serializer: S) -> Result<S::Ok, S::Error>
-    where
-        S: Serializer,
-    {
-        match date {
-            None => serializer.serialize_none(),
-            Some(t) => serializer.serialize_str(t.format(FORMAT).to_string().as_str()),
-        }
-    }
-
-    pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<NaiveDateTime>, D::Error>
-    where
-        D: Deserializer<'de>,
-    {
-        match String::deserialize(deserializer) {
-            Ok(s) => Ok(Some(
-                NaiveDateTime::parse_from_str(&s, FORMAT).map_err(serde::de::Error::custom)?,
-            )),
-            Err(_) => Ok(None),
-        }
-    }
-}
-
-/// 自定义 DateTime 序列化
-pub mod native_datetime_format {
-    use chrono::NaiveDateTime;
-    use serde::{self, Deserialize, Deserializer, Serializer};
-
-    const FORMAT: &'static str = "%Y-%m-%d %H:%M:%S";
-
-    // The signature of a serialize_with function must follow the pattern:
-    //
-    //    fn serialize<S>(&T, S) -> Result<S::Ok, S::Error>
-    //    where
-    //        S: Serializer
-    //
-    // although it may also be generic over the input types T.
-    pub fn serialize<S>(date: &NaiveDateTime, serializer: S) -> Result<S::Ok, S::Error>
-    where
-        S: Serializer,
-    {
-        let s = format!("{}", date.format(FORMAT));
-        serializer.serialize_str(&s)
-    }
-
-    // The signature of a deserialize_with function must follow the pattern:
-    //
-    //    fn deserialize<'de, D>(D) -> Result<T, D::Error>
-    //    where
-    //        D: Deserializer<'de>
-    //
-    // although it may also be generic over the output types T.
-    pub fn deserialize<'de, D>(deserializer: D) -> Result<NaiveDateTime, D::Error>
-    where
-        D: Deserializer<'de>,
-    {
-        let s = String::deserialize(deserializer)?;
-        let dt = NaiveDateTime::parse_from_str(&s, FORMAT).map_err(serde::de::Error::custom)?;
-        Ok(dt)
-    }
-}
-
-/// 自定义 Option<DateTime> 序列化
-pub mod opt_datetime_format {
-    use chrono::{DateTime, Local, Offset};
-    use serde::{self, Deserialize, Deserializer, Serializer};
-
-    const FORMAT: &'static str = "%Y-%m-%d %H:%M:%S";
-
-    pub type OK = ();
-
-    pub fn serialize<S>(date: &Option<DateTime<Local>>, serializer: S) -> Result<S::Ok, S::Error>
-    where
-        S: Serializer,
-    {
-        match date {
-            None => serializer.serialize_none(),
-            Some(t) => serializer.serialize_str(t.format(FORMAT).to_string().as_str()),
-        }
-    }
-
-    pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<DateTime<Local>>, D::Error>
-    where
-        D: Deserializer<'de>,
-    {
-        match String::deserialize(deserializer) {
-            Ok(s) => Ok(Some(
-                format!("{} {}", s, Local::now().offset().fix().to_string())
-                    .parse::<DateTime<Local>>()
-                    .map_err(serde::de::Error::custom)?,
-            )),
-            Err(_) => Ok(None),
-        }
-    }
-}
-
-/// 自定义 DateTime 序列化
-pub mod datetime_format {
-    use chrono::{DateTime, Local, Offset};
-    use serde::{self, Deserialize, Deserializer, Serializer};
-
-    const FORMAT: &'static str = "%Y-%m-%d %H:%M:%S";
-
-    // The signature of a serialize_with function must follow the pattern:
-    //
-    //    fn serialize<S>(&T, S) -> Result<S::Ok, S::Error>
-    //    where
-    //        S: Serializer
-    //
-    // although it may also be generic over the input types T.
-    pub fn serialize<S>(date: &DateTime<Local>, serializer: S) -> Result<S::Ok, S::Error>
-    where
-        S: Serializer,
-    {
-        let s = format!("{}", date.format(FORMAT));
-        serializer.serialize_str(&s)
-    }
-
-    // The signature of a deserialize_with function must follow the pattern:
-    //
-    //    fn deserialize<'de, D>(D) -> Result<T, D::Error>
-    //    where
-    //        D: Deserializer<'de>
-    //
-    // although it may also be generic over the output types T.
-    pub fn deserialize<'de, D>(deserializer: D) -> Result<DateTime<Local>, D::Error>
-    where
-        D: Deserializer<'de>,
-    {
-        let mut s = String::deserialize(deserializer)?;
-        s.push_str(Local::now().offset().fix().to_string().as_str());
-        let date_time = s
-            .parse::<DateTime<Local>>()
-            .map_err(serde::de::Error::custom)?;
-        Ok(date_time)
-    }
-}
-
-pub fn native_datetime_2_datetime(value: NaiveDateTime) -> DateTime<Local> {
-    DateTime::<Local>::from_naive_utc_and_offset(value, Local::now().offset().fix())
-}
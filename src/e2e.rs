@@ -0,0 +1,42 @@
+use crate::token::CURRENT_USER;
+use crate::{host, http_client};
+use common::e2e::KeyStore;
+use common::sanitize::sanitize;
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+struct PublishedKey {
+    public_key: String,
+}
+
+/// 发布自己的公钥，对方开启加密时从服务端获取
+pub(crate) async fn publish(keys: &KeyStore) -> Result<(), String> {
+    let res = http_client()
+        .put(format!("{}/key", host()))
+        .header("Authorization", format!("Bearer {}", CURRENT_USER.lock().unwrap().token))
+        .json(&serde_json::json!({ "public_key": keys.public_key()? }))
+        .send()
+        .await
+        .map_err(|err| err.to_string())?;
+    if !res.status().is_success() {
+        return Err(format!("{}, {}", res.status(), sanitize(&res.text().await.unwrap_or_default())));
+    }
+    Ok(())
+}
+
+/// 获取对方发布的公钥，对方还没有发布时返回错误
+pub(crate) async fn fetch(uid: i32) -> Result<String, String> {
+    let res = http_client()
+        .get(format!("{}/user/{uid}/key", host()))
+        .header("Authorization", format!("Bearer {}", CURRENT_USER.lock().unwrap().token))
+        .send()
+        .await
+        .map_err(|err| err.to_string())?;
+    if res.status() == reqwest::StatusCode::NOT_FOUND {
+        return Err("对方还没有发布公钥，需要对方也开启加密".to_string());
+    }
+    if !res.status().is_success() {
+        return Err(format!("{}, {}", res.status(), sanitize(&res.text().await.unwrap_or_default())));
+    }
+    res.json::<PublishedKey>().await.map(|key| key.public_key).map_err(|err| err.to_string())
+}
@@ -1,28 +1,40 @@
-use crate::datetime::datetime_format;
 use crate::main_select::MainSelect;
 use crate::token::CURRENT_USER;
-use crate::{delimiter, HOST};
-use chrono::{DateTime, Local};
-use crossterm::terminal::ClearType::CurrentLine;
-use crossterm::{cursor, execute, terminal};
+use crate::chat_session::ChatSession;
+use crate::{delimiter, host, http_client, stream_client};
+use common::contact::ContactBook;
+use common::message::{HistoryMsg, Message, MessageTarget, MessageTargetUser, UpdateReadIndex};
+use common::sanitize::sanitize;
+use dialoguer::theme::ColorfulTheme;
+use dialoguer::{Confirm, Input};
 use futures::StreamExt;
-use regex::Regex;
-use reqwest::{Client, StatusCode};
-use serde::{Deserialize, Serialize};
-use std::fmt::{Display, Formatter};
-use std::io::{stdout, Write};
+use reqwest::StatusCode;
+use std::collections::HashMap;
+use std::time::Duration;
+use serde::Deserialize;
 use tokio::io::AsyncBufReadExt;
 
 
 #[derive(Deserialize)]
 pub(crate) struct Friend {
     pub(crate) id: i32,
+    #[serde(deserialize_with = "common::sanitize::line")]
     pub(crate) name: String,
 }
 
-pub(crate) async fn find_friends() {
-    let client = Client::new();
-    let friends_url = format!("{HOST}/friend");
+/// 当前账号的备注和黑名单
+pub(crate) fn contact_book() -> ContactBook {
+    let uid = CURRENT_USER.lock().unwrap().user.id;
+    ContactBook::load(host(), uid).unwrap_or_else(|err| {
+        println!("{err}");
+        ContactBook::default()
+    })
+}
+
+/// 好友列表，名称为用户名
+pub(crate) async fn request_friends() -> Result<Vec<Friend>, String> {
+    let client = http_client();
+    let friends_url = format!("{}/friend", host());
     let response = client
         .get(&friends_url)
         .header(
@@ -34,33 +46,55 @@ pub(crate) async fn find_friends() {
     match response {
         Ok(res) => {
             if res.status().is_success() {
-                match res.json::<Vec<Friend>>().await {
-                    Ok(friends) if !friends.is_empty() => {
-                        select_friend(friends).await;
-                    }
-                    Ok(_) => {
-                        println!("暂无好友");
-                        std::process::exit(1);
-                    }
-                    Err(e) => {
-                        println!("Failed to read response: {}", e);
-                        std::process::exit(1);
-                    }
-                }
+                res.json::<Vec<Friend>>().await.map_err(|e| format!("Failed to read response: {}", e))
             } else {
-                println!("Failed to get friends list: HTTP {}", res.status());
-                std::process::exit(1);
+                Err(format!("Failed to get friends list: HTTP {}", res.status()))
             }
         }
+        Err(e) => Err(format!("Failed to send request: {}", e)),
+    }
+}
+
+/// 好友列表，名称已替换为备注
+pub(crate) async fn fetch_friends() -> Result<Vec<Friend>, String> {
+    let mut friends = request_friends().await?;
+    let book = contact_book();
+    for friend in &mut friends {
+        friend.name = book.display_name(friend.id, &friend.name);
+    }
+    Ok(friends)
+}
+
+/// @提及的候选：好友的用户名
+pub(crate) async fn mention_names() -> Vec<String> {
+    request_friends()
+        .await
+        .map(|friends| friends.into_iter().map(|friend| friend.name).collect())
+        .unwrap_or_default()
+}
+
+pub(crate) async fn find_friends() {
+    match fetch_friends().await {
+        Ok(friends) if !friends.is_empty() => {
+            select_friend(friends).await;
+        }
+        Ok(_) => {
+            println!("暂无好友");
+            std::process::exit(1);
+        }
         Err(e) => {
-            println!("Failed to send request: {}", e);
+            println!("{e}");
             std::process::exit(1);
         }
     };
 }
 
 pub(crate) async fn select_friend(friends: Vec<Friend>) {
-    let friend_names: Vec<&str> = friends.iter().map(|f| f.name.as_str()).collect();
+    let book = contact_book();
+    let friend_names: Vec<String> = friends
+        .iter()
+        .map(|f| if book.is_blocked(f.id) { format!("{} (已屏蔽)", f.name) } else { f.name.clone() })
+        .collect();
     match dialoguer::Select::new()
         .with_prompt(MainSelect::ChatWithFriends.to_str())
         .items(&friend_names)
@@ -68,7 +102,7 @@ pub(crate) async fn select_friend(friends: Vec<Friend>) {
         Ok(selection) => {
             let selected_friend = &friends[selection];
             delimiter();
-            chat_with_friend(selected_friend).await;
+            friend_action(selected_friend, book).await;
         }
         Err(err) => {
             eprintln!("Error: {}", err);
@@ -77,24 +111,134 @@ pub(crate) async fn select_friend(friends: Vec<Friend>) {
     };
 }
 
-pub(crate) async fn chat_with_friend(selected_friend: &Friend) {
-    let latest_mid = fetch_history(selected_friend).await;
-    if let Some(latest_mid) = latest_mid {
-        set_read_index(UpdateReadIndex::User { target_uid: selected_friend.id, mid: latest_mid }).await;
+/// 选中好友后的操作
+async fn friend_action(friend: &Friend, mut book: ContactBook) {
+    let blocked = book.is_blocked(friend.id);
+    let target = MessageTarget::User(MessageTargetUser { uid: friend.id });
+    let mut options = vec!["聊天", "设置备注", "删除好友"];
+    options.push(if book.is_muted(target) { "取消免打扰" } else { "免打扰" });
+    options.push(if blocked { "取消屏蔽" } else { "屏蔽" });
+    let selection = dialoguer::Select::with_theme(&ColorfulTheme::default())
+        .with_prompt(&friend.name)
+        .items(&options)
+        .default(0)
+        .interact()
+        .unwrap();
+    match options[selection] {
+        "聊天" => chat_with_friend(friend).await,
+        "设置备注" => {
+            let alias: String = Input::with_theme(&ColorfulTheme::default())
+                .with_prompt("备注（留空删除备注）")
+                .allow_empty(true)
+                .with_initial_text(book.alias(friend.id).unwrap_or_default())
+                .interact_text()
+                .unwrap();
+            book.set_alias(friend.id, &alias);
+            save_contact_book(&book, "备注已保存");
+        }
+        "删除好友" => remove_friend(friend).await,
+        "免打扰" => {
+            book.set_muted(target, true);
+            save_contact_book(&book, &format!("已开启与{}的免打扰", friend.name));
+        }
+        "取消免打扰" => {
+            book.set_muted(target, false);
+            save_contact_book(&book, &format!("已关闭与{}的免打扰", friend.name));
+        }
+        "屏蔽" => {
+            book.block(friend.id, &friend.name);
+            save_contact_book(&book, &format!("已屏蔽{}，不再显示对方的消息", friend.name));
+        }
+        _ => {
+            book.unblock(friend.id);
+            save_contact_book(&book, &format!("已取消屏蔽{}", friend.name));
+        }
+    }
+}
+
+pub(crate) fn save_contact_book(book: &ContactBook, success: &str) {
+    match book.save() {
+        Ok(_) => println!("{success}"),
+        Err(err) => println!("保存失败: {err}"),
+    }
+}
+
+async fn remove_friend(friend: &Friend) {
+    if !Confirm::with_theme(&ColorfulTheme::default())
+        .with_prompt(format!("确定删除好友{}么？", friend.name))
+        .interact()
+        .unwrap()
+    {
+        return;
     }
-    chat(selected_friend).await;
+    let res = http_client()
+        .delete(format!("{}/friend/{}", host(), friend.id))
+        .header(
+            "Authorization",
+            format!("Bearer {}", CURRENT_USER.lock().unwrap().token),
+        )
+        .send()
+        .await;
+    match res {
+        Ok(res) => {
+            if res.status().is_success() {
+                println!("已删除好友{}", friend.name);
+            } else {
+                println!("删除失败: {}", sanitize(&res.text().await.unwrap()));
+            }
+        }
+        Err(err) => {
+            println!("Failed to send request: {}", err);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// 黑名单，选择用户取消屏蔽
+pub(crate) async fn blocked_users() {
+    let mut book = contact_book();
+    let blocked = book.blocked().map(|(uid, name)| (uid, name.to_string())).collect::<Vec<_>>();
+    if blocked.is_empty() {
+        println!("黑名单为空");
+        return;
+    }
+    let names = blocked.iter().map(|(_, name)| name.as_str()).collect::<Vec<_>>();
+    let selection = dialoguer::Select::with_theme(&ColorfulTheme::default())
+        .with_prompt("选择要取消屏蔽的用户")
+        .items(&names)
+        .interact()
+        .unwrap();
+    let (uid, name) = &blocked[selection];
+    book.unblock(*uid);
+    save_contact_book(&book, &format!("已取消屏蔽{name}"));
 }
 
-#[derive(Serialize)]
-enum UpdateReadIndex {
-    User { target_uid: i32, mid: i64 },
-    Group { target_gid: i32, mid: i64 },
+pub(crate) async fn chat_with_friend(selected_friend: &Friend) {
+    let history = request_history(selected_friend.id).await.unwrap_or_else(|err| {
+        println!("Failed to get chat history:{}", err);
+        vec![]
+    });
+    println!("Chat with {}:", selected_friend.name);
+    println!("----------------------------------------");
+    if let Some(latest) = history.last() {
+        set_read_index(UpdateReadIndex::User { target_uid: selected_friend.id, mid: latest.mid }).await;
+    }
+    let mut session = ChatSession::new(
+        MessageTarget::User(MessageTargetUser { uid: selected_friend.id }),
+        history,
+        HashMap::from([(selected_friend.id, selected_friend.name.clone())]),
+        mention_names().await,
+        contact_book(),
+    );
+    session.load_conversation_names().await;
+    session.print_recent();
+    chat(session).await;
 }
 
 async fn set_read_index(ri: UpdateReadIndex) {
-    let client = Client::new();
+    let client = http_client();
     client
-        .put(format!("{HOST}/ri"))
+        .put(format!("{}/ri", host()))
         .header(
             "Authorization",
             format!("Bearer {}", CURRENT_USER.lock().unwrap().token),
@@ -104,10 +248,9 @@ async fn set_read_index(ri: UpdateReadIndex) {
         .await.expect("unable to set read index");
 }
 
-async fn chat(friend: &Friend) {
-    let client = Client::new();
-    let mut sse_stream = client
-        .get(format!("{HOST}/event/stream"))
+async fn chat(mut session: ChatSession) {
+    let mut sse_stream = stream_client()
+        .get(format!("{}/event/stream", host()))
         .header(
             "Authorization",
             format!("Bearer {}", CURRENT_USER.lock().unwrap().token),
@@ -121,6 +264,7 @@ async fn chat(friend: &Friend) {
     // 异步监听用户输入，使用tokio::io::BufReader及时获取用户输入数据
     let stdin = tokio::io::stdin();
     let mut reader = tokio::io::BufReader::new(stdin);
+    let mut timer = tokio::time::interval(Duration::from_secs(1));
 
     loop {
         let mut input = String::new();
@@ -131,31 +275,15 @@ async fn chat(friend: &Friend) {
                 match msg {
                     Ok(bytes) => {
                         let sse_message = String::from_utf8(bytes.to_vec()).unwrap();
-                        // 获取data
-                        let data = sse_message.lines()
-                        .into_iter()
-                        .find(|line| line.starts_with("data:"))
-                        .map(|line| line.trim_start_matches("data:").trim())
-                        .filter(|line| !line.is_empty());
-                        if let Some(event_data) = data {
-                            match serde_json::from_str::<Message>(event_data) {
+                        if let Some(message) = Message::from_sse(&sse_message) {
+                            match message {
+                                // 会话只显示与该好友的消息
                                 Ok(Message::ChatMessage(chat_message)) => {
-                                    let sender = if chat_message.payload.from_uid == friend.id {
-                                        &friend.name
-                                    } else {
-                                        // 清空用户输入的那一行
-                                        execute!(stdout(),cursor::MoveUp(1)).unwrap();
-                                        execute!(stdout(),terminal::Clear(CurrentLine)).unwrap();
-                                        "You"
-                                    };
-                                    println!("[{}] {}: {}",
-                                             chat_message.payload.created_at.format("%Y-%m-%d %H:%M:%S"),
-                                             sender,
-                                             chat_message.payload.detail.get_content(),
-                                    );
-                                    // 刷出数据
-                                    stdout().flush().unwrap();
+                                    session.receive(&chat_message);
                                 }
+                                Ok(Message::Typing(typing)) => session.on_typing(&typing),
+                                Ok(Message::ReadIndex(read)) => session.on_read(&read),
+                                Ok(Message::Reaction(reaction)) => session.on_reaction(&reaction),
                                 Ok(Message::Heartbeat(_)) => {
                                     // println!("Heartbeat received: {:?}", heartbeat_message);
                                 }
@@ -172,246 +300,35 @@ async fn chat(friend: &Friend) {
                     }
                 }
             }
-            // 处理用户输入
+            // 对方停止输入后清除提示，重发到时间的消息
+            _ = timer.tick() => {
+                session.show_typing();
+                session.flush_outbox().await;
+            }
+            // 处理用户输入，以 / 开头的是命令
             Ok(_) = input_future => {
-                if input.trim() == "exit" {
-                    println!("退出...");
+                if !session.handle_input(&input).await {
                     break;
-                } else if !input.trim().is_empty() {
-                    let url = format!("{HOST}/user/{}/send", friend.id);
-                    let res = client
-                        .post(&url)
-                        .header("Content-Type", "application/json")
-                        .header(
-                            "Authorization",
-                            format!("Bearer {}", CURRENT_USER.lock().unwrap().token),
-                        )
-                        .body(serde_json::json!({
-                            "msg": replace_whitespace(&input),
-                        }).to_string())
-                        .send()
-                        .await;
-
-                    match res {
-                        Ok(res) => {
-                            if res.status() != StatusCode::OK {
-                                println!("Send message failed: {}, {}", res.status(), res.text().await.unwrap());
-                            }
-                        }
-                        Err(err) => {
-                            println!("Send message failed: {}", err);
-                        }
-                    }
                 }
             }
         }
     }
 }
 
-// 替换掉换行符
-fn replace_whitespace(text: &str) -> String {
-    let re = Regex::new(r"[\s\r\n]+").unwrap();
-    re.replace_all(&text, "").into_owned()
-}
-
-async fn fetch_history(friend: &Friend) -> Option<i64> {
-    let url = format!("{HOST}/user/{}/history", friend.id);
-    let res = Client::new()
+/// 与好友的聊天记录
+pub(crate) async fn request_history(uid: i32) -> Result<Vec<HistoryMsg>, String> {
+    let url = format!("{}/user/{}/history", host(), uid);
+    let res = http_client()
         .get(url)
         .header(
             "Authorization",
             format!("Bearer {}", CURRENT_USER.lock().unwrap().token),
         )
         .send()
-        .await;
-    match res {
-        Ok(res) => match res.status() {
-            StatusCode::OK => {
-                let res = res.json::<Vec<UserHistoryMsg>>().await;
-                match res {
-                    Ok(res) => {
-                        println!("Chat with {}:", friend.name);
-                        println!("----------------------------------------");
-                        if res.is_empty() {
-                            println!("No chat history available.");
-                            None
-                        } else {
-                            for msg in &res {
-                                let sender = if msg.from_uid == friend.id {
-                                    &friend.name
-                                } else {
-                                    "You"
-                                };
-                                println!(
-                                    "[{}] {}: {}",
-                                    msg.time.format("%Y-%m-%d %H:%M:%S"),
-                                    sender,
-                                    msg.msg
-                                );
-                            }
-                            Some(res.last()?.mid)
-                        }
-                    }
-                    Err(err) => {
-                        println!("Failed to parse chat history:{}", err);
-                        None
-                    }
-                }
-            }
-            _ => {
-                println!("Failed to get chat history:{}", res.status());
-                None
-            }
-        },
-        Err(err) => {
-            println!("Failed to get chat history:{}", err);
-            None
-        }
-    }
-}
-
-/// 历史聊天记录
-#[derive(Deserialize, Serialize)]
-struct UserHistoryMsg {
-    /// 消息id
-    mid: i64,
-    /// 消息内容
-    msg: String,
-    /// 消息发送时间
-    #[serde(with = "datetime_format")]
-    time: DateTime<Local>,
-    /// 消息发送者id
-    from_uid: i32,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub enum Message {
-    ChatMessage(ChatMessage),
-    Heartbeat(HeartbeatMessage),
-}
-
-// 也可以使用strum库来实现
-impl Display for Message {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "{}",
-            match self {
-                Message::ChatMessage(_) => "Chat",
-                Message::Heartbeat(_) => "Heartbeat",
-            }
-        )
-    }
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct HeartbeatMessage {
-    #[serde(with = "datetime_format")]
-    time: DateTime<Local>,
-}
-
-#[derive(Deserialize, Serialize, Clone, Debug)]
-pub struct ChatMessage {
-    /// Message id
-    pub mid: i64,
-    pub payload: ChatMessagePayload,
-}
-
-#[derive(Serialize, Deserialize, Clone, Debug)]
-pub struct ChatMessagePayload {
-    /// Sender id
-    pub from_uid: i32,
-
-    #[serde(with = "datetime_format")]
-    /// The create time of the message.
-    pub created_at: DateTime<Local>,
-
-    /// Message target
-    pub target: MessageTarget,
-
-    /// Message detail
-    pub detail: MessageDetail,
-}
-
-#[derive(Serialize, Deserialize, Copy, Clone, Debug, Hash, Eq, PartialEq)]
-pub enum MessageTarget {
-    User(MessageTargetUser),
-    Group(MessageTargetGroup),
-}
-
-impl From<MessageTarget> for String {
-    fn from(value: MessageTarget) -> Self {
-        match value {
-            MessageTarget::User(MessageTargetUser { uid }) => format!("MessageTargetUser:{uid}"),
-            MessageTarget::Group(MessageTargetGroup { gid }) => {
-                format!("MessageTargetGroup:{gid}")
-            }
-        }
-    }
-}
-
-#[derive(Serialize, Deserialize, Copy, Clone, Debug, Hash, Eq, PartialEq)]
-pub struct MessageTargetUser {
-    pub uid: i32,
-}
-
-#[derive(Serialize, Deserialize, Copy, Clone, Debug, Hash, Eq, PartialEq)]
-pub struct MessageTargetGroup {
-    pub gid: i32,
-}
-
-#[derive(Serialize, Deserialize, Clone, Debug)]
-pub enum MessageDetail {
-    Normal(MessageNormal),
-    Replay(MessageReplay),
-}
-
-impl MessageDetail {
-    pub fn get_content(&self) -> String {
-        match self {
-            MessageDetail::Normal(msg) => msg.content.content.clone(),
-            MessageDetail::Replay(msg) => msg.content.content.clone(),
-        }
-    }
-}
-
-#[derive(Serialize, Deserialize, Clone, Debug)]
-pub struct MessageNormal {
-    pub content: MessageContent,
-}
-
-#[derive(Serialize, Deserialize, Clone, Debug)]
-pub struct MessageReplay {
-    pub mid: i64,
-    pub content: MessageContent,
-}
-
-#[derive(Serialize, Deserialize, Clone, Debug)]
-pub struct MessageContent {
-    /// Extended attributes
-    // pub properties: Option<HashMap<String, Value>>,
-    /// Content type
-    // pub content_type: String,
-    /// Content
-    pub(crate) content: String,
-}
-
-
-#[cfg(test)]
-mod test {
-    use serde_json::json;
-
-    #[test]
-    fn test_get_friend_history() {
-        let history = json!({"ChatMessage":{"mid":98,"payload":{"from_uid":10,"created_at":"2024-09-12T23:15:05.264972+08:00","target":{"User":{"uid":11}},"detail":{"Normal":{"content":{"content":"hello world!!!!!"}}}}}});
-        let result = serde_json::from_slice::<super::Message>(history.to_string().as_bytes());
-        match result {
-            Ok(msg) => {
-                println!("{:?}", msg)
-            }
-            Err(err) => {
-                println!("{}", err)
-            }
-        }
+        .await
+        .map_err(|err| err.to_string())?;
+    match res.status() {
+        StatusCode::OK => res.json::<Vec<HistoryMsg>>().await.map_err(|err| err.to_string()),
+        status => Err(status.to_string()),
     }
 }
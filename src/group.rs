@@ -0,0 +1,589 @@
+use crate::chat_session::ChatSession;
+use crate::friend::{contact_book, fetch_friends, save_contact_book, Friend};
+use crate::friend;
+use crate::main_select::MainSelect;
+use crate::token::CURRENT_USER;
+use crate::{delimiter, host, http_client, stream_client, GroupCommands};
+use common::contact::ContactBook;
+use common::group::{is_gone, role_of, sort_members, GroupMember, GroupRole, GroupVo, GROUP_GONE};
+use common::message::{HistoryMsg, Message, MessageTarget, MessageTargetGroup, UpdateReadIndex};
+use common::sanitize::sanitize;
+use common::text::indent_continuation;
+use dialoguer::theme::ColorfulTheme;
+use dialoguer::{Confirm, Input, MultiSelect};
+use futures::StreamExt;
+use reqwest::Response;
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::io::AsyncBufReadExt;
+
+fn authorization() -> String {
+    format!("Bearer {}", CURRENT_USER.lock().unwrap().token)
+}
+
+/// 检查响应状态，群已解散或已被移出群聊时返回 `GROUP_GONE`
+async fn check(res: reqwest::Result<Response>) -> Result<Response, String> {
+    let res = res.map_err(|err| format!("Failed to send request: {err}"))?;
+    let status = res.status();
+    if status.is_success() {
+        Ok(res)
+    } else if is_gone(status.as_u16()) {
+        Err(GROUP_GONE.to_string())
+    } else {
+        Err(format!("HTTP {}: {}", status, sanitize(&res.text().await.unwrap_or_default())))
+    }
+}
+
+pub(crate) async fn fetch_groups() -> Result<Vec<GroupVo>, String> {
+    let res = http_client()
+        .get(format!("{}/group", host()))
+        .header("Authorization", authorization())
+        .send()
+        .await;
+    check(res).await?.json::<Vec<GroupVo>>().await.map_err(|e| e.to_string())
+}
+
+async fn create_group(name: &str, members: &[i32]) -> Result<GroupVo, String> {
+    let res = http_client()
+        .post(format!("{}/group", host()))
+        .header("Authorization", authorization())
+        .json(&serde_json::json!({ "name": name, "members": members }))
+        .send()
+        .await;
+    check(res).await?.json::<GroupVo>().await.map_err(|e| e.to_string())
+}
+
+/// 群成员，名称为用户名，按角色排序
+pub(crate) async fn request_members(gid: i32) -> Result<Vec<GroupMember>, String> {
+    let res = http_client()
+        .get(format!("{}/group/{gid}/member", host()))
+        .header("Authorization", authorization())
+        .send()
+        .await;
+    let mut members = check(res).await?.json::<Vec<GroupMember>>().await.map_err(|e| e.to_string())?;
+    sort_members(&mut members);
+    Ok(members)
+}
+
+/// 群成员，名称已替换为备注
+async fn fetch_members(gid: i32) -> Result<Vec<GroupMember>, String> {
+    let mut members = request_members(gid).await?;
+    let book = contact_book();
+    for member in &mut members {
+        member.name = book.display_name(member.uid, &member.name);
+    }
+    Ok(members)
+}
+
+async fn invite(gid: i32, members: &[i32]) -> Result<(), String> {
+    let res = http_client()
+        .post(format!("{}/group/{gid}/member", host()))
+        .header("Authorization", authorization())
+        .json(&serde_json::json!({ "members": members }))
+        .send()
+        .await;
+    check(res).await.map(|_| ())
+}
+
+async fn remove_member(gid: i32, uid: i32) -> Result<(), String> {
+    let res = http_client()
+        .delete(format!("{}/group/{gid}/member/{uid}", host()))
+        .header("Authorization", authorization())
+        .send()
+        .await;
+    check(res).await.map(|_| ())
+}
+
+async fn rename(gid: i32, name: &str) -> Result<(), String> {
+    let res = http_client()
+        .put(format!("{}/group/{gid}", host()))
+        .header("Authorization", authorization())
+        .json(&serde_json::json!({ "name": name }))
+        .send()
+        .await;
+    check(res).await.map(|_| ())
+}
+
+/// 发布公告，内容为空时撤下公告
+async fn announce(gid: i32, content: &str) -> Result<(), String> {
+    let res = http_client()
+        .put(format!("{}/group/{gid}/announcement", host()))
+        .header("Authorization", authorization())
+        .json(&serde_json::json!({ "content": content }))
+        .send()
+        .await;
+    check(res).await.map(|_| ())
+}
+
+async fn set_role(gid: i32, uid: i32, role: GroupRole) -> Result<(), String> {
+    let res = http_client()
+        .put(format!("{}/group/{gid}/member/{uid}/role", host()))
+        .header("Authorization", authorization())
+        .json(&serde_json::json!({ "role": role }))
+        .send()
+        .await;
+    check(res).await.map(|_| ())
+}
+
+async fn leave(gid: i32) -> Result<(), String> {
+    let res = http_client()
+        .post(format!("{}/group/{gid}/leave", host()))
+        .header("Authorization", authorization())
+        .send()
+        .await;
+    check(res).await.map(|_| ())
+}
+
+pub(crate) async fn fetch_history(gid: i32) -> Result<Vec<HistoryMsg>, String> {
+    let res = http_client()
+        .get(format!("{}/group/{gid}/history", host()))
+        .header("Authorization", authorization())
+        .send()
+        .await;
+    check(res).await?.json::<Vec<HistoryMsg>>().await.map_err(|e| e.to_string())
+}
+
+async fn set_read_index(ri: UpdateReadIndex) {
+    let _ = http_client()
+        .put(format!("{}/ri", host()))
+        .header("Authorization", authorization())
+        .json(&ri)
+        .send()
+        .await;
+}
+
+/// 群聊列表，选择群聊后进行聊天或管理
+pub(crate) async fn group_select() {
+    let groups = fetch_groups().await.unwrap_or_else(exit_with);
+    let mut options = vec!["创建群聊".to_string()];
+    options.extend(groups.iter().map(|group| group.name.clone()));
+    let selection = dialoguer::Select::with_theme(&ColorfulTheme::default())
+        .with_prompt(MainSelect::ChatInGroups.to_str())
+        .items(&options)
+        .default(0)
+        .interact()
+        .unwrap();
+    delimiter();
+    match selection {
+        0 => create_interactive().await,
+        i => group_action(&groups[i - 1]).await,
+    }
+}
+
+async fn create_interactive() {
+    let name: String = Input::with_theme(&ColorfulTheme::default())
+        .with_prompt("群名称")
+        .interact_text()
+        .unwrap();
+    let friends = fetch_friends().await.unwrap_or_default();
+    let members = select_friends(&friends, "邀请好友入群（可不选）");
+    match create_group(name.trim(), &members).await {
+        Ok(group) => println!("已创建群聊{}", group.name),
+        Err(err) => println!("创建失败: {err}"),
+    }
+}
+
+/// 多选好友，返回选中好友的id
+fn select_friends(friends: &[Friend], prompt: &str) -> Vec<i32> {
+    if friends.is_empty() {
+        return vec![];
+    }
+    let names = friends.iter().map(|friend| friend.name.as_str()).collect::<Vec<_>>();
+    MultiSelect::with_theme(&ColorfulTheme::default())
+        .with_prompt(prompt)
+        .items(&names)
+        .interact()
+        .unwrap()
+        .into_iter()
+        .map(|i| friends[i].id)
+        .collect()
+}
+
+async fn group_action(group: &GroupVo) {
+    let my_uid = CURRENT_USER.lock().unwrap().user.id;
+    let role = match fetch_members(group.gid).await {
+        Ok(members) => role_of(&members, my_uid),
+        Err(err) => {
+            println!("{err}");
+            return;
+        }
+    };
+    let mut book = contact_book();
+    let target = MessageTarget::Group(MessageTargetGroup { gid: group.gid });
+    let mut options = vec!["聊天", "成员列表", "邀请好友"];
+    if role.can_manage() {
+        options.extend(["发布公告", "移除成员"]);
+    }
+    if role == GroupRole::Owner {
+        options.extend(["修改群名称", "设置管理员"]);
+    }
+    options.push(if book.is_muted(target) { "取消免打扰" } else { "免打扰" });
+    options.push("退出群聊");
+    let selection = dialoguer::Select::with_theme(&ColorfulTheme::default())
+        .with_prompt(&group.name)
+        .items(&options)
+        .default(0)
+        .interact()
+        .unwrap();
+    let result = match options[selection] {
+        "聊天" => {
+            chat_in_group(group).await;
+            Ok(())
+        }
+        "成员列表" => print_members(group).await,
+        "邀请好友" => invite_interactive(group).await,
+        "发布公告" => {
+            let content: String = Input::with_theme(&ColorfulTheme::default())
+                .with_prompt("公告（留空撤下公告）")
+                .allow_empty(true)
+                .with_initial_text(group.announcement.as_deref().unwrap_or_default())
+                .interact_text()
+                .unwrap();
+            announce(group.gid, content.trim()).await.map(|_| println!("公告已更新"))
+        }
+        "修改群名称" => {
+            let name: String = Input::with_theme(&ColorfulTheme::default())
+                .with_prompt("群名称")
+                .with_initial_text(&group.name)
+                .interact_text()
+                .unwrap();
+            rename(group.gid, name.trim()).await.map(|_| println!("已修改群名称为{}", name.trim()))
+        }
+        "设置管理员" => admin_interactive(group).await,
+        "移除成员" => remove_interactive(group, role).await,
+        "免打扰" | "取消免打扰" => {
+            let muted = !book.is_muted(target);
+            book.set_muted(target, muted);
+            save_contact_book(&book, if muted { "已开启免打扰" } else { "已关闭免打扰" });
+            Ok(())
+        }
+        _ => {
+            if Confirm::with_theme(&ColorfulTheme::default())
+                .with_prompt(format!("确定退出{}么？", group.name))
+                .interact()
+                .unwrap()
+            {
+                leave(group.gid).await.map(|_| println!("已退出{}", group.name))
+            } else {
+                Ok(())
+            }
+        }
+    };
+    if let Err(err) = result {
+        println!("{err}");
+    }
+}
+
+async fn print_members(group: &GroupVo) -> Result<(), String> {
+    let members = fetch_members(group.gid).await?;
+    println!("{} 共 {} 人", group.name, members.len());
+    for member in &members {
+        println!("  {}\t{}", member.name, member.role);
+    }
+    delimiter();
+    Ok(())
+}
+
+async fn invite_interactive(group: &GroupVo) -> Result<(), String> {
+    let members = fetch_members(group.gid).await?;
+    // 只能邀请不在群里的好友
+    let friends = fetch_friends()
+        .await?
+        .into_iter()
+        .filter(|friend| members.iter().all(|member| member.uid != friend.id))
+        .collect::<Vec<_>>();
+    if friends.is_empty() {
+        println!("好友都已在群中");
+        return Ok(());
+    }
+    let selected = select_friends(&friends, "选择要邀请的好友");
+    if selected.is_empty() {
+        return Ok(());
+    }
+    invite(group.gid, &selected).await?;
+    println!("已邀请 {} 位好友", selected.len());
+    Ok(())
+}
+
+async fn remove_interactive(group: &GroupVo, role: GroupRole) -> Result<(), String> {
+    let members = fetch_members(group.gid)
+        .await?
+        .into_iter()
+        .filter(|member| role.can_remove(member.role))
+        .collect::<Vec<_>>();
+    let Some(member) = select_member(&members, "选择要移除的成员") else {
+        println!("没有可以移除的成员");
+        return Ok(());
+    };
+    if Confirm::with_theme(&ColorfulTheme::default())
+        .with_prompt(format!("确定将{}移出群聊么？", member.name))
+        .interact()
+        .unwrap()
+    {
+        remove_member(group.gid, member.uid).await?;
+        println!("已将{}移出群聊", member.name);
+    }
+    Ok(())
+}
+
+/// 群主设置或取消管理员
+async fn admin_interactive(group: &GroupVo) -> Result<(), String> {
+    let members = fetch_members(group.gid)
+        .await?
+        .into_iter()
+        .filter(|member| member.role != GroupRole::Owner)
+        .collect::<Vec<_>>();
+    let Some(member) = select_member(&members, "选择成员，管理员将被取消") else {
+        println!("群里只有你自己");
+        return Ok(());
+    };
+    let role = match member.role {
+        GroupRole::Admin => GroupRole::Member,
+        _ => GroupRole::Admin,
+    };
+    set_role(group.gid, member.uid, role).await?;
+    println!("已将{}设为{role}", member.name);
+    Ok(())
+}
+
+fn select_member<'a>(members: &'a [GroupMember], prompt: &str) -> Option<&'a GroupMember> {
+    if members.is_empty() {
+        return None;
+    }
+    let names = members
+        .iter()
+        .map(|member| format!("{} ({})", member.name, member.role))
+        .collect::<Vec<_>>();
+    let selection = dialoguer::Select::with_theme(&ColorfulTheme::default())
+        .with_prompt(prompt)
+        .items(&names)
+        .interact()
+        .unwrap();
+    members.get(selection)
+}
+
+/// 群聊，群解散或被移出群聊时退出
+pub(crate) async fn chat_in_group(group: &GroupVo) {
+    let book = contact_book();
+    let members = match request_members(group.gid).await {
+        Ok(members) => members,
+        Err(err) => {
+            println!("{err}");
+            return;
+        }
+    };
+    let friend_names = friend::mention_names().await;
+    let (names, mention_names) = member_names(&members, &book, &friend_names);
+    println!("Chat in {}:", group.name);
+    // 公告置顶显示
+    if let Some(announcement) = group.announcement.as_deref().filter(|content| !content.is_empty()) {
+        println!("[公告] {}", indent_continuation(announcement));
+    }
+    println!("----------------------------------------");
+    let history = match fetch_history(group.gid).await {
+        Ok(history) => history,
+        Err(err) => {
+            println!("{err}");
+            return;
+        }
+    };
+    if let Some(msg) = history.last() {
+        set_read_index(UpdateReadIndex::Group { target_gid: group.gid, mid: msg.mid }).await;
+    }
+    let target = MessageTarget::Group(MessageTargetGroup { gid: group.gid });
+    let mut session = ChatSession::new(target, history, names, mention_names, book);
+    session.load_conversation_names().await;
+    session.print_recent();
+
+    let mut sse_stream = match stream_client()
+        .get(format!("{}/event/stream", host()))
+        .header("Authorization", authorization())
+        .header("User-Agent", "Chat-Cli/1.0")
+        .send()
+        .await
+    {
+        Ok(res) => res.bytes_stream(),
+        Err(err) => {
+            println!("SSE错误: {err}");
+            return;
+        }
+    };
+    let stdin = tokio::io::stdin();
+    let mut reader = tokio::io::BufReader::new(stdin);
+    let mut timer = tokio::time::interval(Duration::from_secs(1));
+    loop {
+        let mut input = String::new();
+        let input_future = reader.read_line(&mut input);
+        tokio::select! {
+            Some(msg) = sse_stream.next() => {
+                let Ok(bytes) = msg else {
+                    eprintln!("SSE错误");
+                    break;
+                };
+                let sse_message = String::from_utf8_lossy(&bytes).to_string();
+                let chat_message = match Message::from_sse(&sse_message) {
+                    Some(Ok(Message::ChatMessage(chat_message))) => chat_message,
+                    Some(Ok(Message::Typing(typing))) => {
+                        session.on_typing(&typing);
+                        continue;
+                    }
+                    Some(Ok(Message::ReadIndex(read))) => {
+                        session.on_read(&read);
+                        continue;
+                    }
+                    Some(Ok(Message::Reaction(reaction))) => {
+                        session.on_reaction(&reaction);
+                        continue;
+                    }
+                    _ => continue,
+                };
+                let payload = &chat_message.payload;
+                if payload.target == target && !session.knows(payload.from_uid) {
+                    // 新成员入群
+                    if let Ok(members) = request_members(group.gid).await {
+                        let (names, mention_names) = member_names(&members, session.book(), &friend_names);
+                        session.set_names(names, mention_names);
+                    }
+                }
+                if session.receive(&chat_message) {
+                    set_read_index(UpdateReadIndex::Group { target_gid: group.gid, mid: chat_message.mid }).await;
+                }
+            }
+            // 其他人停止输入后清除提示，重发到时间的消息
+            _ = timer.tick() => {
+                session.show_typing();
+                session.flush_outbox().await;
+            }
+            // 处理用户输入，以 / 开头的是命令
+            Ok(_) = input_future => {
+                if !session.handle_input(&input).await {
+                    break;
+                }
+            }
+        }
+    }
+}
+
+/// 成员id到显示名称的映射，以及@提及的候选：群成员在前，其次是好友
+fn member_names(members: &[GroupMember], book: &ContactBook, friend_names: &[String]) -> (HashMap<i32, String>, Vec<String>) {
+    let names = members
+        .iter()
+        .map(|member| (member.uid, book.display_name(member.uid, &member.name)))
+        .collect();
+    let mut mention_names = members.iter().map(|member| member.name.clone()).collect::<Vec<_>>();
+    mention_names.extend(friend_names.iter().cloned());
+    (names, mention_names)
+}
+
+fn exit_with<T>(err: String) -> T {
+    println!("{err}");
+    std::process::exit(1);
+}
+
+/// 按id或名称查找群聊
+async fn find_group(group: &str) -> Result<GroupVo, String> {
+    let groups = fetch_groups().await?;
+    groups
+        .iter()
+        .find(|vo| vo.gid.to_string() == group)
+        .or_else(|| groups.iter().find(|vo| vo.name == group))
+        .cloned()
+        .ok_or_else(|| format!("群聊{group}不存在"))
+}
+
+/// 按名称或备注查找好友
+fn find_friends_by_name(friends: &[Friend], names: &[String]) -> Result<Vec<i32>, String> {
+    names
+        .iter()
+        .map(|name| {
+            friends
+                .iter()
+                .find(|friend| &friend.name == name)
+                .map(|friend| friend.id)
+                .ok_or_else(|| format!("好友{name}不存在"))
+        })
+        .collect()
+}
+
+pub(crate) async fn group_command(command: GroupCommands) {
+    let result = match command {
+        GroupCommands::List => fetch_groups().await.map(|groups| {
+            let my_uid = CURRENT_USER.lock().unwrap().user.id;
+            for group in groups {
+                let owner = if group.is_owner(my_uid) { " (群主)" } else { "" };
+                println!("{}\t{}{owner}", group.gid, group.name);
+                if let Some(announcement) = group.announcement.as_deref().filter(|content| !content.is_empty()) {
+                    println!("\t[公告] {}", indent_continuation(announcement));
+                }
+            }
+        }),
+        GroupCommands::Create { name, member } => async {
+            let friends = fetch_friends().await?;
+            let members = find_friends_by_name(&friends, &member)?;
+            let group = create_group(&name, &members).await?;
+            println!("已创建群聊{}，群号{}", group.name, group.gid);
+            Ok(())
+        }
+        .await,
+        GroupCommands::Members { group } => async { print_members(&find_group(&group).await?).await }.await,
+        GroupCommands::Invite { group, friends } => async {
+            let group = find_group(&group).await?;
+            let members = find_friends_by_name(&fetch_friends().await?, &friends)?;
+            invite(group.gid, &members).await?;
+            println!("已邀请 {} 位好友加入{}", members.len(), group.name);
+            Ok(())
+        }
+        .await,
+        GroupCommands::Remove { group, member } => async {
+            let group = find_group(&group).await?;
+            let uid = fetch_members(group.gid)
+                .await?
+                .into_iter()
+                .find(|vo| vo.name == member)
+                .map(|vo| vo.uid)
+                .ok_or_else(|| format!("{member}不在群中"))?;
+            remove_member(group.gid, uid).await?;
+            println!("已将{member}移出{}", group.name);
+            Ok(())
+        }
+        .await,
+        GroupCommands::Rename { group, name } => async {
+            let group = find_group(&group).await?;
+            rename(group.gid, &name).await?;
+            println!("已将{}修改为{name}", group.name);
+            Ok(())
+        }
+        .await,
+        GroupCommands::Announce { group, content } => async {
+            let group = find_group(&group).await?;
+            announce(group.gid, content.trim()).await?;
+            println!("已更新{}的公告", group.name);
+            Ok(())
+        }
+        .await,
+        GroupCommands::Role { group, member, admin } => async {
+            let group = find_group(&group).await?;
+            let uid = fetch_members(group.gid)
+                .await?
+                .into_iter()
+                .find(|vo| vo.name == member)
+                .map(|vo| vo.uid)
+                .ok_or_else(|| format!("{member}不在群中"))?;
+            let role = if admin { GroupRole::Admin } else { GroupRole::Member };
+            set_role(group.gid, uid, role).await?;
+            println!("已将{member}设为{role}");
+            Ok(())
+        }
+        .await,
+        GroupCommands::Leave { group } => async {
+            let group = find_group(&group).await?;
+            leave(group.gid).await?;
+            println!("已退出{}", group.name);
+            Ok(())
+        }
+        .await,
+    };
+    if let Err(err) = result {
+        exit_with::<()>(err);
+    }
+}
@@ -1,4 +1,3 @@
-mod datetime;
 mod friend;
 mod main_select;
 mod user;
@@ -7,9 +6,18 @@ mod recent_chat;
 mod console;
 mod add_friend;
 mod style;
+mod profile;
+mod group;
+mod mention;
+mod chat_session;
+mod attachment;
+mod e2e;
 use clap::{Parser, Subcommand};
+use common::config;
+use common::config::settings;
 use futures::StreamExt;
 use reqwest;
+use reqwest::Client;
 use serde::Deserialize;
 use std::fmt::Display;
 use tokio::io::AsyncBufReadExt;
@@ -21,17 +29,52 @@ pub(crate) fn delimiter() {
     println!("{DELIMITER}");
 }
 
-#[cfg(feature = "release")]
-static HOST: &str = include_str!("../config/release");
-#[cfg(not(feature = "release"))]
-static HOST: &str = "http://localhost:3000";
+/// 服务端地址
+pub(crate) fn host() -> &'static str {
+    &settings().server
+}
+
+/// 按配置的超时时间创建HTTP客户端
+pub(crate) fn http_client() -> Client {
+    let timeout = &settings().timeout;
+    Client::builder()
+        .connect_timeout(timeout.connect())
+        .timeout(timeout.request())
+        .build()
+        .expect("unable to build http client")
+}
+
+/// 消息流是长连接，只限制连接超时
+pub(crate) fn stream_client() -> Client {
+    Client::builder()
+        .connect_timeout(settings().timeout.connect())
+        .build()
+        .expect("unable to build http client")
+}
 
 #[tokio::main]
 async fn main() {
     let cli = Cli::parse();
+    // 管理 profile 时不校验当前 profile 是否存在
+    if let Commands::Profile { command } = cli.command {
+        profile::profile(command);
+        return;
+    }
+    if let Err(err) = config::init(cli.server, cli.profile) {
+        eprintln!("配置文件有误: {err}");
+        std::process::exit(1);
+    }
     let _ = match cli.command {
         Commands::Register { name, password } => user::register(name, password).await,
         Commands::Login { name, password } => user::login(name, password).await,
+        Commands::Group { command } => {
+            if !user::resume().await {
+                println!("请先登陆: chat-cli login");
+                std::process::exit(1);
+            }
+            group::group_command(command).await
+        }
+        Commands::Profile { .. } => unreachable!(),
     };
 
 }
@@ -39,6 +82,12 @@ async fn main() {
 #[derive(Parser)]
 #[command(version="0.1",about="A Chat Client", long_about = None)]
 struct Cli {
+    /// 服务端地址，覆盖配置文件、环境变量和 profile
+    #[arg(long, global = true)]
+    server: Option<String>,
+    /// 使用的 profile，默认为 `profile use` 选中的 profile
+    #[arg(long, global = true)]
+    profile: Option<String>,
     #[command(subcommand)]
     command: Commands,
 }
@@ -56,14 +105,114 @@ enum Commands {
     },
     /// 登陆
     Login {
-        /// 用户名
+        /// 用户名，默认使用 profile 中的用户名
+        #[arg(short, long)]
+        name: Option<String>,
+        /// 密码，未指定时恢复 profile 中保存的登陆状态或提示输入
         #[arg(short, long)]
+        password: Option<String>,
+    },
+    /// 管理群聊，需要先登陆
+    Group {
+        #[command(subcommand)]
+        command: GroupCommands,
+    },
+    /// 管理服务端与账号
+    Profile {
+        #[command(subcommand)]
+        command: ProfileCommands,
+    },
+}
+
+#[derive(Subcommand)]
+enum ProfileCommands {
+    /// 列出所有 profile
+    List,
+    /// 添加 profile
+    Add {
+        /// profile 名称
         name: String,
-        /// 密码
+        /// 服务端地址
         #[arg(short, long)]
-        password: String,
+        server: String,
+        /// 用户名
+        #[arg(short = 'n', long)]
+        username: Option<String>,
+    },
+    /// 删除 profile
+    Remove {
+        /// profile 名称
+        name: String,
+    },
+    /// 设置默认使用的 profile
+    Use {
+        /// profile 名称
+        name: String,
+    },
+}
+#[derive(Subcommand)]
+pub(crate) enum GroupCommands {
+    /// 列出加入的群聊
+    List,
+    /// 创建群聊
+    Create {
+        /// 群名称
+        name: String,
+        /// 邀请入群的好友，可指定多次
+        #[arg(short, long)]
+        member: Vec<String>,
+    },
+    /// 列出群成员
+    Members {
+        /// 群号或群名称
+        group: String,
+    },
+    /// 邀请好友入群
+    Invite {
+        /// 群号或群名称
+        group: String,
+        /// 好友名称或备注
+        #[arg(required = true)]
+        friends: Vec<String>,
+    },
+    /// 移除群成员，群主和管理员可用
+    Remove {
+        /// 群号或群名称
+        group: String,
+        /// 成员名称
+        member: String,
+    },
+    /// 修改群名称，仅群主可用
+    Rename {
+        /// 群号或群名称
+        group: String,
+        /// 新的群名称
+        name: String,
+    },
+    /// 发布公告，内容为空时撤下公告，群主和管理员可用
+    Announce {
+        /// 群号或群名称
+        group: String,
+        /// 公告内容
+        content: String,
+    },
+    /// 设置或取消管理员，仅群主可用
+    Role {
+        /// 群号或群名称
+        group: String,
+        /// 成员名称
+        member: String,
+        /// 设为管理员，未指定时取消管理员
+        #[arg(long)]
+        admin: bool,
+    },
+    /// 退出群聊
+    Leave {
+        /// 群号或群名称
+        group: String,
     },
 }
+
 /// 校验用户名
 /// 用户名必须是纯英文
 fn check_name(name: &str) -> Result<String, String> {
@@ -1,11 +1,13 @@
-use crate::main_select::MainSelect::{AddFriend, ChatInGroups, ChatWithFriends, RecentChat};
-use crate::{add_friend, friend, recent_chat};
+use crate::main_select::MainSelect::{AddFriend, BlockedUsers, ChatInGroups, ChatWithFriends, Mentions, RecentChat};
+use crate::{add_friend, friend, group, mention, recent_chat};
 
 pub(crate) enum MainSelect {
     AddFriend,
     RecentChat,
+    Mentions,
     ChatWithFriends,
     ChatInGroups,
+    BlockedUsers,
 }
 
 impl MainSelect {
@@ -13,8 +15,10 @@ impl MainSelect {
         vec![
             AddFriend.to_str(),
             RecentChat.to_str(),
+            Mentions.to_str(),
             ChatWithFriends.to_str(),
             ChatInGroups.to_str(),
+            BlockedUsers.to_str(),
         ]
     }
 
@@ -22,8 +26,10 @@ impl MainSelect {
         match self {
             AddFriend => "添加好友",
             RecentChat => "最近消息",
+            Mentions => "提到我的",
             ChatWithFriends => "好友列表",
             ChatInGroups => "群聊列表",
+            BlockedUsers => "黑名单",
         }
     }
 
@@ -32,8 +38,10 @@ impl MainSelect {
         match s {
             "添加好友" => AddFriend,
             "最近消息" => RecentChat,
+            "提到我的" => Mentions,
             "好友列表" => ChatWithFriends,
             "群聊列表" => ChatInGroups,
+            "黑名单" => BlockedUsers,
             _ => panic!("Invalid string"),
         }
     }
@@ -52,12 +60,10 @@ impl MainSelect {
         match self {
             AddFriend => add_friend::add_friend_select().await,
             RecentChat => recent_chat::recent_chat().await,
+            Mentions => mention::mentions_view().await,
             ChatWithFriends => friend::find_friends().await,
-            ChatInGroups => chat_in_groups(),
+            ChatInGroups => group::group_select().await,
+            BlockedUsers => friend::blocked_users().await,
         }
     }
 }
-
-fn chat_in_groups() {
-    todo!()
-}
@@ -0,0 +1,141 @@
+use crate::recent_chat::{fetch_recent_chats, open_conversation, ChatVo};
+use crate::token::CURRENT_USER;
+use crate::{delimiter, friend, group, style};
+use chrono::{DateTime, Local};
+use common::mention::{expand, mentions};
+use common::text::{indent_continuation, rows};
+use common::message::{MessageTarget, MessageTargetGroup, MessageTargetUser};
+use crossterm::style::Stylize;
+use crossterm::terminal;
+use dialoguer::theme::ColorfulTheme;
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::io::{stdout, Write};
+
+pub(crate) fn my_name() -> String {
+    CURRENT_USER.lock().unwrap().user.name.clone()
+}
+
+/// 打印一条消息，提到我的消息高亮显示发送人；`msg` 可能已经带有样式，不再整体加样式。
+/// 多行消息的后续行加上前缀，只有消息开头顶格显示。返回在终端中占的行数，包括自动换行的行
+pub(crate) fn print_message(time: &DateTime<Local>, sender: &str, msg: &str) -> u16 {
+    let header = format!("[{}] {sender}:", time.format("%Y-%m-%d %H:%M:%S"));
+    let line = if mentions(msg, &my_name()) {
+        format!("{} {}", header.yellow().bold(), indent_continuation(msg))
+    } else {
+        format!("{header} {}", indent_continuation(msg))
+    };
+    println!("{line}");
+    stdout().flush().unwrap();
+    let columns = terminal::size().map_or(usize::MAX, |(columns, _)| usize::from(columns));
+    u16::try_from(rows(&line, columns)).unwrap_or(u16::MAX)
+}
+
+/// 补全输入中不完整的@提及，有歧义时提示可能的名称并返回 None
+pub(crate) fn expand_input(input: &str, names: &[String]) -> Option<String> {
+    match expand(input, names) {
+        Ok(msg) => Some(msg),
+        Err(err) => {
+            println!("{err}，请补全后重新发送");
+            None
+        }
+    }
+}
+
+/// 提到我的消息
+struct Mention {
+    target: MessageTarget,
+    /// 会话名称
+    conversation: String,
+    sender: String,
+    msg: String,
+    time: DateTime<Local>,
+}
+
+/// 汇总最近会话中提到我的消息，选择后打开对应会话
+pub(crate) async fn mentions_view() {
+    let spinner = style::spinner("正在查找提到我的消息...".to_string());
+    let result = collect().await;
+    spinner.finish_and_clear();
+    let mentions = match result {
+        Ok(mentions) if mentions.is_empty() => {
+            println!("最近没有人@你");
+            return;
+        }
+        Ok(mentions) => mentions,
+        Err(err) => {
+            println!("{err}");
+            return;
+        }
+    };
+    let options = mentions
+        .iter()
+        .map(|mention| {
+            format!(
+                "[{}] {} · {}: {}",
+                mention.time.format("%Y-%m-%d %H:%M"),
+                mention.conversation,
+                mention.sender,
+                mention.msg
+            )
+        })
+        .collect::<Vec<_>>();
+    let selection = dialoguer::Select::with_theme(&ColorfulTheme::default())
+        .with_prompt("提到我的")
+        .items(&options)
+        .default(0)
+        .interact_opt()
+        .unwrap();
+    delimiter();
+    if let Some(mention) = selection.map(|index| &mentions[index]) {
+        open_conversation(mention.target, &mention.conversation).await;
+    }
+}
+
+/// 逐个读取最近会话的聊天记录，按时间倒序返回提到我的消息
+async fn collect() -> Result<Vec<Mention>, String> {
+    let my_name = my_name();
+    let book = friend::contact_book();
+    let mut mentioned = vec![];
+    for chat_vo in fetch_recent_chats().await?.into_iter().filter_map(|chat_vo| chat_vo.apply_contacts(&book)) {
+        let (target, conversation, history, names) = match chat_vo {
+            ChatVo::User { uid, user_name, .. } => {
+                let history = friend::request_history(uid).await?;
+                (
+                    MessageTarget::User(MessageTargetUser { uid }),
+                    user_name.clone(),
+                    history,
+                    HashMap::from([(uid, user_name)]),
+                )
+            }
+            ChatVo::Group { gid, group_name, .. } => {
+                // 群已解散时跳过
+                let Ok(history) = group::fetch_history(gid).await else {
+                    continue;
+                };
+                let names = match group::request_members(gid).await {
+                    Ok(members) => members
+                        .into_iter()
+                        .map(|member| (member.uid, book.display_name(member.uid, &member.name)))
+                        .collect(),
+                    Err(_) => HashMap::new(),
+                };
+                (MessageTarget::Group(MessageTargetGroup { gid }), group_name, history, names)
+            }
+        };
+        mentioned.extend(
+            history
+                .into_iter()
+                .filter(|msg| !msg.recalled && !book.is_blocked(msg.from_uid) && mentions(&msg.msg, &my_name))
+                .map(|msg| Mention {
+                    target,
+                    conversation: conversation.clone(),
+                    sender: names.get(&msg.from_uid).cloned().unwrap_or_else(|| msg.from_uid.to_string()),
+                    msg: msg.display(),
+                    time: msg.time,
+                }),
+        );
+    }
+    mentioned.sort_by_key(|mention| Reverse(mention.time));
+    Ok(mentioned)
+}
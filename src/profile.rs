@@ -0,0 +1,52 @@
+use crate::{delimiter, ProfileCommands};
+use common::profile::{Profile, Profiles};
+use crossterm::style::Stylize;
+
+pub(crate) fn profile(command: ProfileCommands) {
+    let result = Profiles::load().and_then(|mut profiles| match command {
+        ProfileCommands::List => {
+            list(&profiles);
+            Ok(())
+        }
+        ProfileCommands::Add { name, server, username } => {
+            let server = server.trim_end_matches('/').to_string();
+            profiles.add(Profile { name: name.clone(), server, username, token: None })?;
+            profiles.save()?;
+            println!("已添加{name}");
+            Ok(())
+        }
+        ProfileCommands::Remove { name } => {
+            profiles.remove(&name)?;
+            profiles.save()?;
+            println!("已删除{name}");
+            Ok(())
+        }
+        ProfileCommands::Use { name } => {
+            profiles.use_profile(&name)?;
+            profiles.save()?;
+            println!("已切换到{name}");
+            Ok(())
+        }
+    });
+    if let Err(err) = result {
+        println!("{err}");
+        std::process::exit(1);
+    }
+}
+
+fn list(profiles: &Profiles) {
+    if profiles.profiles.is_empty() {
+        println!("还没有 profile，使用 `chat-cli profile add <name> --server <url>` 添加");
+        return;
+    }
+    let name_width = profiles.profiles.iter().map(|profile| profile.name.len()).max().unwrap_or(0);
+    let server_width = profiles.profiles.iter().map(|profile| profile.server.len()).max().unwrap_or(0);
+    for profile in &profiles.profiles {
+        // `*` 标记默认使用的 profile
+        let marker = if profiles.current.as_ref() == Some(&profile.name) { "*" } else { " " };
+        let name = format!("{marker} {:<name_width$}", profile.name);
+        let username = profile.username.as_deref().unwrap_or("-");
+        println!("{}  {:<server_width$}  {}", name.bold(), profile.server, username.dark_grey());
+    }
+    delimiter();
+}
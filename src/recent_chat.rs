@@ -1,130 +1,203 @@
-use crate::datetime::datetime_format;
 use crate::friend::Friend;
 use crate::token::CURRENT_USER;
-use crate::{console, delimiter, friend, HOST};
+use crate::{console, delimiter, friend, group, host, http_client, mention};
+use common::contact::{ContactBook, BLOCKED_MESSAGE};
+use common::group::GROUP_GONE;
+use common::mention::mentions;
+use common::message::{MessageTarget, MessageTargetGroup, MessageTargetUser};
 use chrono::{DateTime, Local};
+use common::config::settings;
+use common::datetime::datetime_format;
+use common::unread::{badge, unread_format};
+use crossterm::style::Stylize;
 use indexmap::IndexMap;
-use reqwest::Client;
 use serde::{Deserialize, Serialize};
 
-pub(crate) async fn recent_chat() {
-    delimiter();
-    let url = format!("{HOST}/user/history/100");
-    let res = Client::new()
+/// 最近聊天列表
+pub(crate) async fn fetch_recent_chats() -> Result<Vec<ChatVo>, String> {
+    let url = format!("{}/user/history/{}", host(), settings().page_size.recent_chat);
+    let res = http_client()
         .get(url)
         .header(
             "Authorization",
             format!("Bearer {}", CURRENT_USER.lock().unwrap().token),
         )
         .send()
-        .await;
-    if let Ok(res) = res {
-        if res.status().is_success() {
-            let res = res.json::<Vec<ChatVo>>().await;
-            match res {
-                Ok(chatVos) if !chatVos.is_empty() => {
-                    let select_to_id: IndexMap<String, (Option<i32>, Option<i32>, String)> = chatVos.into_iter().map(|chat_vo| {
-                        match chat_vo {
-                            ChatVo::User {
-                                uid,
-                                user_name,
-                                mid: _mid,
-                                msg,
-                                msg_time,
-                                unread,
-                            } => {
-                                delimiter();
-                                match unread {
-                                    None => (format!("好友: {}\n  时间: {}\n  {}", user_name, msg_time, msg), (Some(uid), None, user_name)),
-                                    Some(unread) => (format!("好友: {}\n  时间: {}\n  {}\n  未读: {}", user_name, msg_time, msg, unread), (Some(uid), None, user_name)),
-                                }
-                            }
-                            ChatVo::Group {
-                                gid,
-                                group_name,
-                                uid: _uid,
-                                user_name,
-                                mid: _mid,
-                                msg,
-                                msg_time,
-                                unread,
-                            } => {
-                                delimiter();
-                                match unread {
-                                    None => (format!("群: {}\n  时间: {}\n  {}: {}", group_name, msg_time, user_name, msg), (None, Some(gid), group_name)),
-                                    Some(unread) => (format!("群: {}\n  时间: {}\n  {}: {}\n  未读: {}", user_name, msg_time, user_name, msg, unread), (None, Some(gid), group_name)),
-                                }
-                            }
-                        }
-                    }).collect();
-                    let options = select_to_id.keys().map(|s| s.as_str()).collect::<Vec<_>>();
-                    let selection = dialoguer::Select::with_theme(&dialoguer::theme::ColorfulTheme::default())
-                        .with_prompt("最近聊天列表")
-                        .items(&options)
-                        .interact()
-                        .unwrap();
-                    match select_to_id.get(options[selection]).unwrap() {
-                        (Some(uid), None, name) => {
-                            console::clean_all();
-                            friend::chat_with_friend(&Friend { id: *uid, name: name.to_string() }).await;
-                        }
-                        (None, Some(gid), name) => { todo!() }
-                        _ => {
-                            println!("error selection");
-                            std::process::exit(1);
-                        }
-                    };
+        .await
+        .map_err(|err| err.to_string())?;
+    if !res.status().is_success() {
+        return Err(format!("HTTP {}", res.status()));
+    }
+    res.json::<Vec<ChatVo>>().await.map_err(|err| err.to_string())
+}
+
+pub(crate) async fn recent_chat() {
+    delimiter();
+    match fetch_recent_chats().await {
+        Ok(chatVos) if !chatVos.is_empty() => {
+            let book = friend::contact_book();
+            let my_name = mention::my_name();
+            let select_to_id: IndexMap<String, (Option<i32>, Option<i32>, String)> = chatVos.into_iter().filter_map(|chat_vo| chat_vo.apply_contacts(&book)).map(|chat_vo| {
+                match chat_vo {
+                    ChatVo::User {
+                        uid,
+                        user_name,
+                        mid: _mid,
+                        msg,
+                        msg_time,
+                        unread,
+                    } => {
+                        delimiter();
+                        let mentioned = unread.is_some_and(|unread| unread > 0) && mentions(&msg, &my_name);
+                        let badge = conversation_badge(&book, MessageTarget::User(MessageTargetUser { uid }), unread, mentioned);
+                        (format!("好友: {}{}\n  时间: {}\n  {}", user_name, badge, msg_time, msg), (Some(uid), None, user_name))
+                    }
+                    ChatVo::Group {
+                        gid,
+                        group_name,
+                        uid: _uid,
+                        user_name,
+                        mid: _mid,
+                        msg,
+                        msg_time,
+                        unread,
+                    } => {
+                        delimiter();
+                        let mentioned = unread.is_some_and(|unread| unread > 0) && mentions(&msg, &my_name);
+                        let badge = conversation_badge(&book, MessageTarget::Group(MessageTargetGroup { gid }), unread, mentioned);
+                        (format!("群: {}{}\n  时间: {}\n  {}: {}", group_name, badge, msg_time, user_name, msg), (None, Some(gid), group_name))
+                    }
                 }
-                Ok(_) => {
-                    println!("暂无聊天记录");
-                    std::process::exit(1);
+            }).collect();
+            let options = select_to_id.keys().map(|s| s.as_str()).collect::<Vec<_>>();
+            let selection = dialoguer::Select::with_theme(&dialoguer::theme::ColorfulTheme::default())
+                .with_prompt("最近聊天列表")
+                .items(&options)
+                .interact()
+                .unwrap();
+            match select_to_id.get(options[selection]).unwrap() {
+                (Some(uid), None, name) => {
+                    open_conversation(MessageTarget::User(MessageTargetUser { uid: *uid }), name).await;
+                }
+                (None, Some(gid), name) => {
+                    open_conversation(MessageTarget::Group(MessageTargetGroup { gid: *gid }), name).await;
                 }
-                Err(e) => {
-                    println!("Fail to get recent chat, {e}");
+                _ => {
+                    println!("error selection");
                     std::process::exit(1);
                 }
+            };
+        }
+        Ok(_) => {
+            println!("暂无聊天记录");
+            std::process::exit(1);
+        }
+        Err(e) => {
+            println!("Fail to get recent chat, {e}");
+            std::process::exit(1);
+        }
+    }
+}
+
+/// 未读数徽标
+fn unread_badge(unread: u32) -> String {
+    format!(" {} ", badge(unread)).white().on_red().bold().to_string()
+}
+
+/// 打开单聊或群聊，群已解散时提示
+pub(crate) async fn open_conversation(target: MessageTarget, name: &str) {
+    match target {
+        MessageTarget::User(MessageTargetUser { uid }) => {
+            console::clean_all();
+            friend::chat_with_friend(&Friend { id: uid, name: name.to_string() }).await;
+        }
+        MessageTarget::Group(MessageTargetGroup { gid }) => match group::fetch_groups().await {
+            Ok(groups) => match groups.into_iter().find(|group| group.gid == gid) {
+                Some(group) => {
+                    console::clean_all();
+                    group::chat_in_group(&group).await;
+                }
+                None => println!("{GROUP_GONE}"),
+            },
+            Err(err) => println!("{err}"),
+        },
+    }
+}
+
+/// 会话名称后的标记：有人@我时优先提示，免打扰的会话显示灰色未读数
+fn conversation_badge(book: &ContactBook, target: MessageTarget, unread: Option<u32>, mentioned: bool) -> String {
+    let unread = unread.filter(|unread| *unread > 0);
+    let mention = if mentioned { format!(" {}", "[有人@我]".red().bold()) } else { String::new() };
+    let badge = match (book.is_muted(target), unread) {
+        (true, Some(unread)) => format!(" (免打扰) {}", badge(unread).dark_grey()),
+        (true, None) => " (免打扰)".to_string(),
+        (false, Some(unread)) => format!(" {}", unread_badge(unread)),
+        (false, None) => String::new(),
+    };
+    format!("{mention}{badge}")
+}
+
+impl ChatVo {
+    /// 好友名称替换为备注，隐藏被屏蔽用户的会话和群聊消息
+    pub(crate) fn apply_contacts(mut self, book: &ContactBook) -> Option<Self> {
+        match &mut self {
+            ChatVo::User { uid, .. } if book.is_blocked(*uid) => return None,
+            ChatVo::User { uid, user_name, .. } => *user_name = book.display_name(*uid, user_name),
+            ChatVo::Group { uid, user_name, msg, .. } => {
+                *user_name = book.display_name(*uid, user_name);
+                if book.is_blocked(*uid) {
+                    *msg = BLOCKED_MESSAGE.to_string();
+                }
             }
         }
+        Some(self)
     }
 }
 
 /// 聊天记录
 #[derive(Debug, Serialize, Deserialize, Hash, Eq, PartialEq)]
-enum ChatVo {
+pub(crate) enum ChatVo {
     /// UserChat
     User {
         /// id of friend
         uid: i32,
         /// name of friend
+        #[serde(deserialize_with = "common::sanitize::line")]
         user_name: String,
         /// message id
         mid: i64,
         /// message content
+        #[serde(deserialize_with = "common::sanitize::text")]
         msg: String,
         /// message time
         #[serde(with = "datetime_format")]
         msg_time: DateTime<Local>,
         /// unread message count
-        unread: Option<String>,
+        #[serde(default, with = "unread_format")]
+        unread: Option<u32>,
     },
     /// GroupChat
     Group {
         /// id of group
         gid: i32,
         /// name of group
+        #[serde(deserialize_with = "common::sanitize::line")]
         group_name: String,
         /// id of friend
         uid: i32,
         /// name of friend
+        #[serde(deserialize_with = "common::sanitize::line")]
         user_name: String,
         /// message id
         mid: i64,
         /// message content
+        #[serde(deserialize_with = "common::sanitize::text")]
         msg: String,
         /// message time
         #[serde(with = "datetime_format")]
         msg_time: DateTime<Local>,
         /// unread message count
-        unread: Option<String>,
+        #[serde(default, with = "unread_format")]
+        unread: Option<u32>,
     },
 }
\ No newline at end of file
@@ -1,42 +1,74 @@
-use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
-use rand::Rng;
-use std::thread;
+use common::markdown::{code_box, parse, Block, Span};
+use crossterm::style::Stylize;
+use indicatif::{ProgressBar, ProgressStyle};
 use std::time::Duration;
 
-pub(crate) fn loading(title: String) {
-    let styles = [
-        // ("Rough bar:", "█  ", "red"),
-        // ("Fine bar: ", "█▉▊▋▌▍▎▏  ", "yellow"),
-        // ("Vertical: ", "█▇▆▅▄▃▂▁  ", "green"),
-        (title +": ", "█▓▒░  ", "blue"),
-        // ("Blocky:   ", "█▛▌▖  ", "magenta"),
-    ];
+/// 请求进行中的加载动画，请求结束后调用 `finish_and_clear`
+pub(crate) fn spinner(message: String) -> ProgressBar {
+    let pb = ProgressBar::new_spinner();
+    pb.set_style(ProgressStyle::with_template("{spinner:.blue} {msg}").unwrap());
+    pb.set_message(message);
+    pb.enable_steady_tick(Duration::from_millis(100));
+    pb
+}
 
-    let m = MultiProgress::new();
+/// 上传、下载文件的进度条，`len` 为总字节数
+pub(crate) fn progress(len: u64, message: String) -> ProgressBar {
+    let pb = ProgressBar::new(len);
+    pb.set_style(
+        ProgressStyle::with_template("{msg} [{bar:30.blue}] {bytes}/{total_bytes} {bytes_per_sec}")
+            .unwrap()
+            .progress_chars("=> "),
+    );
+    pb.set_message(message);
+    pb
+}
 
-    let handles: Vec<_> = styles
+/// 按 Markdown 子集显示消息：粗体、斜体、行内代码、带边框的代码块、引用，链接用 OSC 8 超链接，在支持的终端中可以点击
+pub(crate) fn markdown(text: &str) -> String {
+    let blocks = parse(text);
+    let mut lines = vec![];
+    let mut index = 0;
+    while index < blocks.len() {
+        match &blocks[index] {
+            Block::Text(spans) => lines.push(styled(spans)),
+            Block::Quote(spans) => lines.push(format!("{}{}", "│ ".dark_grey(), styled(spans))),
+            Block::Code(_) => {
+                // 连续的代码行放在同一个框里
+                let code = blocks[index..]
+                    .iter()
+                    .map_while(|block| match block {
+                        Block::Code(line) => Some(line.as_str()),
+                        _ => None,
+                    })
+                    .collect::<Vec<_>>();
+                index += code.len() - 1;
+                lines.extend(code_box(&code).into_iter().map(|line| line.dark_cyan().to_string()));
+            }
+        }
+        index += 1;
+    }
+    lines.join("\n")
+}
+
+fn styled(spans: &[Span]) -> String {
+    spans
         .iter()
-        .map(|s| {
-            let pb = m.add(ProgressBar::new(512));
-            pb.set_style(
-                ProgressStyle::with_template(&format!("{{prefix:.bold}}▕{{bar:.{}}}▏{{msg}}", s.2))
-                    .unwrap()
-                    .progress_chars(s.1),
-            );
-            pb.set_prefix(s.0.clone());
-            let wait = Duration::from_millis(rand::thread_rng().gen_range(0..10));
-            thread::spawn(move || {
-                for i in 0..512 {
-                    thread::sleep(wait);
-                    pb.inc(1);
-                    pb.set_message(format!("{:3}%", 100 * i / 512));
-                }
-                pb.finish_with_message("100%");
-            })
+        .map(|span| {
+            let mut text = span.text.clone().stylize();
+            if span.style.bold {
+                text = text.bold();
+            }
+            if span.style.italic {
+                text = text.italic();
+            }
+            if span.style.code {
+                text = text.dark_cyan();
+            }
+            match &span.link {
+                Some(url) => format!("\x1b]8;;{url}\x1b\\{}\x1b]8;;\x1b\\", text.blue().underlined()),
+                None => text.to_string(),
+            }
         })
-        .collect();
-
-    for h in handles {
-        let _ = h.join();
-    }
-}
\ No newline at end of file
+        .collect()
+}
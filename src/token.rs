@@ -23,8 +23,11 @@ const KEYS: LazyLock<Keys, fn() -> Keys> = LazyLock::new(|| {
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct User {
     pub id: i32,
+    #[serde(deserialize_with = "common::sanitize::line")]
     pub name: String,
+    #[serde(default, deserialize_with = "common::sanitize::optional_text")]
     pub email: Option<String>,
+    #[serde(default, deserialize_with = "common::sanitize::optional_text")]
     pub phone: Option<String>,
     pub dgraph_uid: String,
     pub role: Role,
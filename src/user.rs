@@ -1,11 +1,14 @@
 use crate::main_select::MainSelect;
-use crate::token::CURRENT_USER;
-use crate::{delimiter, token, HOST};
-use reqwest::{Client, StatusCode};
+use crate::token::{CurrentUser, User, CURRENT_USER};
+use crate::{delimiter, host, http_client, token};
+use common::config::settings;
+use common::profile::{Profile, Profiles};
+use common::sanitize::sanitize;
+use reqwest::StatusCode;
 use serde::Deserialize;
 
 use dialoguer::theme::ColorfulTheme;
-use dialoguer::Input;
+use dialoguer::{Input, Password};
 use std::time::Duration;
 
 pub(crate) async fn register(name: String, password: String) {
@@ -35,8 +38,8 @@ pub(crate) async fn register(name: String, password: String) {
     println!("Phone: {}", phone);
 
     // 使用reqwest 向 POST HOST/user/register 接口注册用户
-    let url = format!("{HOST}/user/register");
-    let client = Client::new();
+    let url = format!("{}/user/register", host());
+    let client = http_client();
     let res = client
         .post(&url)
         .json(&serde_json::json!({
@@ -56,7 +59,7 @@ pub(crate) async fn register(name: String, password: String) {
                 println!("用户名已存在，请重新注册")
             }
             _ => {
-                println!("注册失败: {}", res.text().await.unwrap())
+                println!("注册失败: {}", sanitize(&res.text().await.unwrap()))
             }
         },
         Err(err) => {
@@ -65,9 +68,73 @@ pub(crate) async fn register(name: String, password: String) {
     }
 }
 
-pub(crate) async fn login(name: String, password: String) {
-    let login_url = format!("{HOST}/token/login");
-    let client = Client::new();
+/// 当前 profile 中保存的仍然有效的登陆状态，指定用户名时需与 profile 中的用户名一致
+async fn stored_session(profile: Option<&Profile>, name: &Option<String>) -> Option<(User, String)> {
+    let profile = profile.filter(|profile| name.is_none() || *name == profile.username)?;
+    let token = profile.token.clone()?;
+    let user = token::parse_token(token.as_str()).await.ok()?.claims;
+    Some((user, token))
+}
+
+/// 恢复 profile 中保存的登陆状态，用于无需进入主菜单的子命令
+pub(crate) async fn resume() -> bool {
+    let profile = current_profile();
+    match stored_session(profile.as_ref(), &None).await {
+        Some((user, token)) => {
+            let mut guard = CURRENT_USER.lock().unwrap();
+            guard.user = user;
+            guard.token = token;
+            true
+        }
+        None => false,
+    }
+}
+
+fn current_profile() -> Option<Profile> {
+    settings()
+        .profile
+        .as_ref()
+        .and_then(|name| Profiles::load().ok()?.get(name).cloned())
+}
+
+pub(crate) async fn login(name: Option<String>, password: Option<String>) {
+    let profile = current_profile();
+    // 未指定密码时优先恢复 profile 中保存的登陆状态
+    if password.is_none() {
+        if let Some((user, token)) = stored_session(profile.as_ref(), &name).await {
+            println!("已恢复{}的登陆状态", user.name);
+            delimiter();
+            start_session(user, token).await;
+            return;
+        }
+    }
+    let name = match name.or_else(|| profile.as_ref().and_then(|profile| profile.username.clone())) {
+        Some(name) => name,
+        None => Input::with_theme(&ColorfulTheme::default())
+            .with_prompt("Username")
+            .interact_text()
+            .unwrap(),
+    };
+    let password = match password {
+        Some(password) => password,
+        None => Password::with_theme(&ColorfulTheme::default())
+            .with_prompt("Password")
+            .interact()
+            .unwrap(),
+    };
+    let token = do_login(name, password).await;
+    if token.is_none() {
+        println!("Login failed. Exiting the program.");
+        std::process::exit(1);
+    }
+    let token = token.unwrap();
+    let user = token::parse_token(token.as_str()).await.unwrap().claims;
+    start_session(user, token).await;
+}
+
+async fn do_login(name: String, password: String) -> Option<String> {
+    let login_url = format!("{}/token/login", host());
+    let client = http_client();
     let response = client
         .post(&login_url)
         .json(&serde_json::json!({
@@ -76,7 +143,7 @@ pub(crate) async fn login(name: String, password: String) {
         }))
         .send()
         .await;
-    let token = match response {
+    match response {
         Ok(res) => {
             if res.status().is_success() {
                 match res.json::<LoginRes>().await {
@@ -102,30 +169,28 @@ pub(crate) async fn login(name: String, password: String) {
             println!("Failed to send login request: {}", e);
             None
         }
-    };
-
-    if token.is_none() {
-        println!("Login failed. Exiting the program.");
-        std::process::exit(1);
     }
-    let token = token.unwrap();
-    let user = token::parse_token(token.as_str()).await.unwrap().claims;
+}
+
+/// 保存登陆状态，启动token刷新后进入主菜单
+async fn start_session(user: User, token: String) {
     {
         let mut guard = CURRENT_USER.lock().unwrap();
         guard.user = user; // Create a longer-lived binding
         guard.token = token; // Create a longer-lived binding
+        save_session(&guard);
     }
-    let token = format!("Bearer {}", CURRENT_USER.lock().unwrap().token);
     // 启动异步线程，定时刷新token过期时间
     tokio::spawn(async move {
         loop {
             let renew_token_period = Duration::from_secs(60);
             tokio::time::sleep(renew_token_period).await;
-            let renew_url = format!("{HOST}/token/renew");
-            let client = Client::new();
+            let token = format!("Bearer {}", CURRENT_USER.lock().unwrap().token);
+            let renew_url = format!("{}/token/renew", host());
+            let client = http_client();
             let response = client
                 .patch(renew_url)
-                .header("Authorization", token.clone())
+                .header("Authorization", token)
                 .send()
                 .await;
             match response {
@@ -137,6 +202,7 @@ pub(crate) async fn login(name: String, password: String) {
                                 let mut guard = CURRENT_USER.lock().unwrap();
                                 guard.user = token_data.claims;
                                 guard.token = t;
+                                save_session(&guard);
                             }
                             Err(e) => {
                                 println!("Failed to parse response: {}", e);
@@ -155,6 +221,17 @@ pub(crate) async fn login(name: String, password: String) {
     MainSelect::select().await;
 }
 
+/// 把登陆状态写回当前 profile，下次登陆时无需输入密码
+fn save_session(current_user: &CurrentUser) {
+    if let Some(name) = &settings().profile {
+        let _ = Profiles::save_session(
+            name,
+            Some(current_user.user.name.clone()),
+            Some(current_user.token.clone()),
+        );
+    }
+}
+
 #[derive(Deserialize)]
 pub(crate) struct LoginRes {
     pub access_token: String,